@@ -1,81 +1,10 @@
 use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
-use vxl_iset::syscall_handler::SyscallHandler;
 use vxlvm::validator::{BulkValidator, Validator};
 use vxlvm::vm::VM;
 
-struct System;
+mod common;
 
-impl System {
-    pub fn new() -> Self {
-        return Self;
-    }
-}
-
-impl SyscallHandler<VM> for System {
-    fn execute_target_specific_call(&mut self, _call: u64, _machine: &mut VM) -> Option<u64> {
-        return None;
-    }
-
-    fn exit(&mut self, _machine: &mut VM) -> Option<u64> {
-        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
-    }
-
-    fn write_byte_terminal(&mut self, _machine: &mut VM) -> Option<u64> {
-        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
-    }
-
-    fn write_terminal(&mut self, _machine: &mut VM) -> Option<u64> {
-        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
-    }
-
-    fn read_byte_terminal(&mut self, _machine: &mut VM) -> Option<u64> {
-        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
-    }
-
-    fn read_terminal(&mut self, _machine: &mut VM) -> Option<u64> {
-        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
-    }
-
-    fn open_file(&mut self, _machine: &mut VM) -> Option<u64> {
-        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
-    }
-
-    fn close_file(&mut self, _machine: &mut VM) -> Option<u64> {
-        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
-    }
-
-    fn read_file(&mut self, _machine: &mut VM) -> Option<u64> {
-        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
-    }
-
-    fn write_file(&mut self, _machine: &mut VM) -> Option<u64> {
-        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
-    }
-
-    fn execute_file(&mut self, _machine: &mut VM) -> Option<u64> {
-        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
-    }
-
-    fn execute_vxl_file(&mut self, _machine: &mut VM) -> Option<u64> {
-        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
-    }
-
-    fn delete_file(&mut self, _machine: &mut VM) -> Option<u64> {
-        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
-    }
-
-    fn move_file(&mut self, _machine: &mut VM) -> Option<u64> {
-        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
-    }
-
-    fn copy_file(&mut self, _machine: &mut VM) -> Option<u64> {
-        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
-    }
-
-    fn time_of_day(&mut self, _machine: &mut VM) -> Option<u64> {
-        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
-    }
-}
+use common::System;
 
 fn benchmark_load_byte(c: &mut Criterion) {
     // ldb 63, $r0
@@ -106,6 +35,7 @@ fn benchmark_load_byte(c: &mut Criterion) {
             },
             |(mut vm, mut handler)| {
                 vm.run_next(&mut handler).unwrap();
+                vm
             },
             BatchSize::SmallInput,
         )
@@ -141,6 +71,7 @@ fn benchmark_load_integer(c: &mut Criterion) {
             },
             |(mut vm, mut handler)| {
                 vm.run_next(&mut handler).unwrap();
+                vm
             },
             BatchSize::SmallInput,
         )
@@ -178,6 +109,7 @@ fn benchmark_malloc(c: &mut Criterion) {
             },
             |(mut vm, mut handler)| {
                 vm.run_next(&mut handler).unwrap();
+                vm
             },
             BatchSize::SmallInput,
         )
@@ -209,6 +141,7 @@ fn benchmark_malloci(c: &mut Criterion) {
             },
             |(mut vm, mut handler)| {
                 vm.run_next(&mut handler).unwrap();
+                vm
             },
             BatchSize::SmallInput,
         )
@@ -252,6 +185,7 @@ fn benchmark_free(c: &mut Criterion) {
             },
             |(mut vm, mut handler)| {
                 vm.run_next(&mut handler).unwrap();
+                vm
             },
             BatchSize::SmallInput,
         )
@@ -303,6 +237,7 @@ fn benchmark_freea(c: &mut Criterion) {
             },
             |(mut vm, mut handler)| {
                 vm.run_next(&mut handler).unwrap();
+                vm
             },
             BatchSize::SmallInput,
         )
@@ -0,0 +1,143 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use vxl_iset::instruction::Instruction;
+use vxl_iset::instruction_arguments::{Address, Immediate, Register};
+use vxlvm::vm::{Memory, VM};
+
+mod common;
+
+use common::System;
+
+const STACK_SIZE: usize = 2000 * 1000;
+
+/// Allocates `live_blocks` blocks that stay allocated for the rest of the program.
+fn fill_heap(live_blocks: i64) -> Vec<Instruction> {
+    return vec![
+        Instruction::Ldi(Immediate::from(live_blocks), Register::R0),
+        Instruction::Ldi(Immediate::from(1i64), Register::R1),
+        Instruction::Ldi(Immediate::from(0i64), Register::R2),
+        // fill:
+        Instruction::Malloci(Immediate::from(16u64), Register::R3),
+        Instruction::Subi(Register::R0, Register::R0, Register::R1),
+        Instruction::Cmp(Register::R0, Register::R2),
+        Instruction::Jne(Address::from(3u64)),
+    ];
+}
+
+fn churn_program(live_blocks: i64, iterations: i64) -> Vec<Instruction> {
+    let mut instructions = fill_heap(live_blocks);
+
+    instructions.extend([
+        Instruction::Ldi(Immediate::from(iterations), Register::R0),
+        // churn:
+        Instruction::Malloci(Immediate::from(16u64), Register::R3),
+        Instruction::Setb(Register::R3, Register::R2, Register::R1),
+        Instruction::Free(Register::R3),
+        Instruction::Subi(Register::R0, Register::R0, Register::R1),
+        Instruction::Cmp(Register::R0, Register::R2),
+        Instruction::Jne(Address::from(8u64)),
+        Instruction::Halt,
+    ]);
+
+    return instructions;
+}
+
+fn access_program(live_blocks: i64, iterations: i64) -> Vec<Instruction> {
+    let mut instructions = fill_heap(live_blocks);
+
+    instructions.extend([
+        Instruction::Ldi(Immediate::from(iterations), Register::R0),
+        // access:
+        Instruction::Setb(Register::R3, Register::R2, Register::R0),
+        Instruction::Getb(Register::R4, Register::R3, Register::R2),
+        Instruction::Subi(Register::R0, Register::R0, Register::R1),
+        Instruction::Cmp(Register::R0, Register::R2),
+        Instruction::Jne(Address::from(8u64)),
+        Instruction::Halt,
+    ]);
+
+    return instructions;
+}
+
+fn bench_backend(c: &mut Criterion, name: &str, memory: fn() -> Memory, program: &[Instruction]) {
+    c.bench_function(name, |b| {
+        b.iter_batched(
+            || {
+                let vm = VM::new_with_memory(
+                    memory(),
+                    STACK_SIZE,
+                    program.to_vec(),
+                    0,
+                    Default::default(),
+                );
+
+                (vm, System::new())
+            },
+            |(mut vm, mut handler)| {
+                vm.run(&mut handler).unwrap();
+                vm
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn benchmark_churn(c: &mut Criterion) {
+    let instructions = churn_program(1000, 100_000);
+
+    bench_backend(c, "heap-churn-100k-tree", Memory::new, &instructions);
+    bench_backend(c, "heap-churn-100k-slab", Memory::slab, &instructions);
+}
+
+fn benchmark_access(c: &mut Criterion) {
+    let instructions = access_program(1000, 100_000);
+
+    bench_backend(c, "heap-access-100k-tree", Memory::new, &instructions);
+    bench_backend(c, "heap-access-100k-slab", Memory::slab, &instructions);
+}
+
+fn bench_copy(c: &mut Criterion, name: &str, size: u64, dest: u64, dest_offset: u64) {
+    // copy $r0, $r1, $r2, $r3, $r4
+    let instructions = vec![Instruction::Copy(
+        Register::R0,
+        Register::R1,
+        Register::R2,
+        Register::R3,
+        Register::R4,
+    )];
+
+    c.bench_function(name, |b| {
+        b.iter_batched(
+            || {
+                let mut vm = VM::new(instructions.clone());
+
+                vm.memory_mut().allocate(size + dest_offset).unwrap();
+                vm.memory_mut().allocate(size + dest_offset).unwrap();
+
+                let registers = vm.registers_mut();
+                registers.set_value(Register::R0 as u8, dest);
+                registers.set_value(Register::R1 as u8, dest_offset);
+                registers.set_value(Register::R2 as u8, 0);
+                registers.set_value(Register::R3 as u8, 0);
+                registers.set_value(Register::R4 as u8, size);
+
+                (vm, System::new())
+            },
+            |(mut vm, mut handler)| {
+                vm.run_next(&mut handler).unwrap();
+                vm
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn benchmark_copy(c: &mut Criterion) {
+    for (label, size) in [("1kb", 1 << 10), ("64kb", 1 << 16), ("1mb", 1 << 20)] {
+        bench_copy(c, &format!("copy-{}", label), size, 1, 0);
+        bench_copy(c, &format!("copy-{}-overlapping", label), size, 0, 16);
+    }
+}
+
+criterion_group!(benches, benchmark_churn, benchmark_access, benchmark_copy);
+
+criterion_main!(benches);
@@ -0,0 +1,126 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use vxl_iset::instruction::Instruction;
+use vxl_iset::instruction_arguments::{Address, Immediate, Register};
+use vxlvm::vm::VM;
+
+mod common;
+
+use common::System;
+
+fn power_program(base: i64, exponent: i64) -> Vec<Instruction> {
+    // The pow.vsm example from the vxasm repository with a configurable base and exponent.
+    return vec![
+        Instruction::Ldi(Immediate::from(base), Register::R0),
+        Instruction::Ldi(Immediate::from(exponent), Register::R1),
+        Instruction::Call(Address::from(4u64)),
+        Instruction::Halt,
+        // pow:
+        Instruction::Ldb(Immediate::from(1u8), Register::ROU),
+        Instruction::Mov(Register::R2, Register::R0),
+        Instruction::Mov(Register::R0, Register::R1),
+        Instruction::Ldb(Immediate::from(0u8), Register::R1),
+        Instruction::Ldb(Immediate::from(1u8), Register::R9),
+        // loop:
+        Instruction::Cmp(Register::R1, Register::R0),
+        Instruction::Jge(Address::from(14u64)),
+        Instruction::Mulu(Register::ROU, Register::ROU, Register::R2),
+        Instruction::Addu(Register::R1, Register::R1, Register::R9),
+        Instruction::Jmp(Address::from(9u64)),
+        // end:
+        Instruction::Ret,
+    ];
+}
+
+fn countdown_program(iterations: i64) -> Vec<Instruction> {
+    return vec![
+        Instruction::Ldi(Immediate::from(iterations), Register::R0),
+        Instruction::Ldi(Immediate::from(1i64), Register::R1),
+        Instruction::Ldi(Immediate::from(0i64), Register::R2),
+        // loop:
+        Instruction::Subi(Register::R0, Register::R0, Register::R1),
+        Instruction::Addi(Register::R3, Register::R3, Register::R1),
+        Instruction::Cmp(Register::R0, Register::R2),
+        Instruction::Jne(Address::from(3u64)),
+        Instruction::Halt,
+    ];
+}
+
+fn benchmark_power(c: &mut Criterion) {
+    let instructions = power_program(3, 100_000);
+
+    c.bench_function("program-power-100k", |b| {
+        b.iter_batched(
+            || (VM::new(instructions.clone()), System::new()),
+            |(mut vm, mut handler)| {
+                vm.run(&mut handler).unwrap();
+                vm
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn benchmark_countdown(c: &mut Criterion) {
+    let instructions = countdown_program(100_000);
+
+    c.bench_function("program-countdown-100k", |b| {
+        b.iter_batched(
+            || (VM::new(instructions.clone()), System::new()),
+            |(mut vm, mut handler)| {
+                vm.run(&mut handler).unwrap();
+                vm
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn benchmark_power_fused(c: &mut Criterion) {
+    let instructions = power_program(3, 100_000);
+
+    c.bench_function("program-power-100k-fused", |b| {
+        b.iter_batched(
+            || {
+                let mut vm = VM::new(instructions.clone());
+                vm.fuse_superinstructions();
+
+                (vm, System::new())
+            },
+            |(mut vm, mut handler)| {
+                vm.run(&mut handler).unwrap();
+                vm
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn benchmark_countdown_fused(c: &mut Criterion) {
+    let instructions = countdown_program(100_000);
+
+    c.bench_function("program-countdown-100k-fused", |b| {
+        b.iter_batched(
+            || {
+                let mut vm = VM::new(instructions.clone());
+                vm.fuse_superinstructions();
+
+                (vm, System::new())
+            },
+            |(mut vm, mut handler)| {
+                vm.run(&mut handler).unwrap();
+                vm
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+criterion_group!(
+    benches,
+    benchmark_power,
+    benchmark_power_fused,
+    benchmark_countdown,
+    benchmark_countdown_fused
+);
+
+criterion_main!(benches);
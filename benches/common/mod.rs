@@ -0,0 +1,77 @@
+use vxl_iset::syscall_handler::SyscallHandler;
+use vxlvm::vm::VM;
+
+/// A handler for benchmarks that don't make system calls.
+pub struct System;
+
+impl System {
+    pub fn new() -> Self {
+        return Self;
+    }
+}
+
+impl SyscallHandler<VM> for System {
+    fn execute_target_specific_call(&mut self, _call: u64, _machine: &mut VM) -> Option<u64> {
+        return None;
+    }
+
+    fn exit(&mut self, _machine: &mut VM) -> Option<u64> {
+        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
+    }
+
+    fn write_byte_terminal(&mut self, _machine: &mut VM) -> Option<u64> {
+        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
+    }
+
+    fn write_terminal(&mut self, _machine: &mut VM) -> Option<u64> {
+        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
+    }
+
+    fn read_byte_terminal(&mut self, _machine: &mut VM) -> Option<u64> {
+        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
+    }
+
+    fn read_terminal(&mut self, _machine: &mut VM) -> Option<u64> {
+        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
+    }
+
+    fn open_file(&mut self, _machine: &mut VM) -> Option<u64> {
+        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
+    }
+
+    fn close_file(&mut self, _machine: &mut VM) -> Option<u64> {
+        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
+    }
+
+    fn read_file(&mut self, _machine: &mut VM) -> Option<u64> {
+        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
+    }
+
+    fn write_file(&mut self, _machine: &mut VM) -> Option<u64> {
+        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
+    }
+
+    fn execute_file(&mut self, _machine: &mut VM) -> Option<u64> {
+        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
+    }
+
+    fn execute_vxl_file(&mut self, _machine: &mut VM) -> Option<u64> {
+        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
+    }
+
+    fn delete_file(&mut self, _machine: &mut VM) -> Option<u64> {
+        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
+    }
+
+    fn move_file(&mut self, _machine: &mut VM) -> Option<u64> {
+        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
+    }
+
+    fn copy_file(&mut self, _machine: &mut VM) -> Option<u64> {
+        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
+    }
+
+    fn time_of_day(&mut self, _machine: &mut VM) -> Option<u64> {
+        panic!("Failed. Unexpectedly reached an unimplemented handler function.");
+    }
+}
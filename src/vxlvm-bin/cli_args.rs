@@ -1,9 +1,163 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 #[derive(Parser, Debug)]
 #[clap(version, name = "vxlvm")]
 #[clap(about = "The virtual machine for executing xvl files.")]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct CLIArgs {
     /// The file to execute
-    pub input_file: String,
+    #[clap(required = true)]
+    pub input_file: Option<String>,
+
+    /// A library to link with the file before executing, may be repeated
+    #[clap(long = "link", value_name = "LIBRARY")]
+    pub libraries: Vec<String>,
+
+    /// Run the file without checking its jumps, calls, entry offset and stack use first
+    #[clap(long)]
+    pub no_verify: bool,
+
+    /// Allow files built without a checksum
+    #[clap(long)]
+    pub allow_unchecked: bool,
+
+    /// Only run files signed by one of the public keys in this directory, each stored in hex in
+    /// its own file
+    #[clap(long, value_name = "DIR")]
+    pub trusted_keys: Option<String>,
+
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Assemble a source file into an xvl file
+    Asm {
+        /// The source file to assemble
+        input_file: String,
+
+        /// The file to write
+        #[clap(short, long)]
+        output: String,
+
+        /// The checksum algorithm of the output: SHA2-224, SHA3-224, SHA-256, BLAKE2b-224, CRC32
+        /// or none
+        #[clap(long, default_value = "SHA3-224")]
+        checksum: String,
+    },
+    /// Print the instructions of a file as assembly
+    Disasm {
+        /// The file to disassemble
+        input_file: String,
+    },
+    /// Print the control flow graph of a file in the Graphviz DOT language
+    Cfg {
+        /// The file to graph
+        input_file: String,
+    },
+    /// Print the header of a file
+    Inspect {
+        /// The file to inspect
+        input_file: String,
+    },
+    /// Check a file for problems without executing it
+    Verify {
+        /// The file to verify
+        input_file: String,
+
+        /// Print the result as JSON
+        #[clap(long)]
+        json: bool,
+    },
+    /// Check that registers holding ints, floats and heap handles aren't used as another type
+    Lint {
+        /// The file to lint
+        input_file: String,
+    },
+    /// Run instructions as they are typed, keeping the machine between lines
+    Repl {
+        /// Allow loading files built without a checksum
+        #[clap(long)]
+        allow_unchecked: bool,
+
+        /// Only load files signed by one of the public keys in this directory, each stored in hex
+        /// in its own file
+        #[clap(long, value_name = "DIR")]
+        trusted_keys: Option<String>,
+    },
+    /// Run a Debug Adapter Protocol server over stdin and stdout
+    Dap {
+        /// Allow loading files built without a checksum
+        #[clap(long)]
+        allow_unchecked: bool,
+
+        /// Only load files signed by one of the public keys in this directory, each stored in hex
+        /// in its own file
+        #[clap(long, value_name = "DIR")]
+        trusted_keys: Option<String>,
+    },
+    /// Serve a file to gdb or lldb over the GDB remote protocol on a local port
+    Gdbserver {
+        /// The file to debug
+        input_file: String,
+
+        /// The port to listen on, 0 picks any free port
+        #[clap(long)]
+        port: u16,
+
+        /// A library to link with the file, may be repeated
+        #[clap(long = "link", value_name = "LIBRARY")]
+        libraries: Vec<String>,
+
+        /// Debug the file without checking its jumps, calls, entry offset and stack use first
+        #[clap(long)]
+        no_verify: bool,
+
+        /// Allow loading files built without a checksum
+        #[clap(long)]
+        allow_unchecked: bool,
+
+        /// Only load files signed by one of the public keys in this directory, each stored in hex
+        /// in its own file
+        #[clap(long, value_name = "DIR")]
+        trusted_keys: Option<String>,
+    },
+    /// Sign a file, replacing any existing signature
+    Sign {
+        /// The file to sign
+        input_file: String,
+
+        /// A file containing the 32 byte Ed25519 secret key in hex
+        #[clap(long)]
+        key: String,
+
+        /// The file to write
+        #[clap(short, long)]
+        output: String,
+    },
+    /// Link files into a single file, the first file provides the entry point
+    Link {
+        /// The files to link
+        #[clap(required = true)]
+        input_files: Vec<String>,
+
+        /// The file to write
+        #[clap(short, long)]
+        output: String,
+
+        /// The checksum algorithm of the output: SHA2-224, SHA3-224, SHA-256, BLAKE2b-224, CRC32
+        /// or none
+        #[clap(long, default_value = "SHA3-224")]
+        checksum: String,
+
+        /// Allow input files built without a checksum
+        #[clap(long)]
+        allow_unchecked: bool,
+
+        /// Only load files signed by one of the public keys in this directory, each stored in hex
+        /// in its own file
+        #[clap(long, value_name = "DIR")]
+        trusted_keys: Option<String>,
+    },
 }
@@ -0,0 +1,662 @@
+use crate::file_operations::{describe_machine_error, prepare_machine, LoadOptions};
+use crate::handler::OSHandler;
+use crate::repl::register_value;
+
+use serde_json::{json, Value};
+use vxl_iset::instruction_arguments::Register;
+use vxlvm::debugger::{Debugger, Resume, StopReason};
+use vxlvm::disassembler::register_name;
+use vxlvm::symbols::SymbolTable;
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+/// The number of instructions run between checks for a pause request.
+const RUN_CHUNK: u64 = 10_000;
+
+/// The machine is the only thread.
+const THREAD_ID: u64 = 1;
+
+const REGISTERS_REFERENCE: u64 = 1;
+const HEAP_REFERENCE: u64 = 2;
+/// The values on the stack of frame `n` are `FRAME_REFERENCE + n`.
+const FRAME_REFERENCE: u64 = 3;
+
+/// The number of bytes of each heap block shown as its value.
+const HEAP_PREVIEW: usize = 16;
+
+/// A Debug Adapter Protocol server for a single program.
+struct DapServer<'a, W: Write> {
+    output: W,
+    sequence: u64,
+    options: &'a LoadOptions,
+    session: Option<Session>,
+    /// Events to send after the response to the current request.
+    events: Vec<(&'static str, Value)>,
+}
+
+/// A launched program.
+struct Session {
+    debugger: Debugger,
+    handler: OSHandler,
+    /// What the guest has written to its terminal.
+    console: Rc<RefCell<Vec<u8>>>,
+    stop_on_entry: bool,
+    running: bool,
+    /// The instructions of the breakpoints set in each source file.
+    source_breakpoints: BTreeMap<String, Vec<usize>>,
+    instruction_breakpoints: Vec<usize>,
+}
+
+/// Collects the terminal output of the guest so that it can be sent as events.
+struct Console(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Console {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(bytes);
+
+        return Ok(bytes.len());
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        return Ok(());
+    }
+}
+
+/// Serves requests from stdin until the client disconnects.
+pub fn run_dap_server(options: &LoadOptions) -> Result<(), String> {
+    let (sender, receiver) = mpsc::channel();
+
+    // Requests are read on another thread so that a running program can be paused.
+    thread::spawn(move || {
+        let mut input = io::stdin().lock();
+
+        while let Ok(Some(message)) = read_message(&mut input) {
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut server = DapServer {
+        output: io::stdout(),
+        sequence: 0,
+        options,
+        session: None,
+        events: Vec::new(),
+    };
+
+    return server.serve(receiver).map_err(|e| format!("{}", e));
+}
+
+/// Reads a message with a `Content-Length` header, returning `None` at the end of the input.
+fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<Value>> {
+    let mut length = None;
+
+    loop {
+        let mut line = String::new();
+
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim();
+
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let length = length
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Missing Content-Length."))?;
+
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+
+    return serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+}
+
+impl<'a, W: Write> DapServer<'a, W> {
+    fn serve(&mut self, requests: Receiver<Value>) -> io::Result<()> {
+        loop {
+            let running = self.session.as_ref().is_some_and(|s| s.running);
+
+            let request = if running {
+                match requests.try_recv() {
+                    Ok(request) => Some(request),
+                    Err(TryRecvError::Empty) => None,
+                    Err(TryRecvError::Disconnected) => return Ok(()),
+                }
+            } else {
+                match requests.recv() {
+                    Ok(request) => Some(request),
+                    Err(_) => return Ok(()),
+                }
+            };
+
+            if let Some(request) = request {
+                if !self.handle_request(&request)? {
+                    return Ok(());
+                }
+            }
+
+            self.run_chunk()?;
+        }
+    }
+
+    /// Returns false once the client has disconnected.
+    fn handle_request(&mut self, request: &Value) -> io::Result<bool> {
+        let command = request["command"].as_str().unwrap_or_default();
+        let arguments = &request["arguments"];
+
+        let result = match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsInstructionBreakpoints": true,
+                "supportsSteppingGranularity": false,
+            })),
+            "launch" => self.launch(arguments),
+            "setBreakpoints" => self.set_breakpoints(arguments),
+            "setInstructionBreakpoints" => self.set_instruction_breakpoints(arguments),
+            "setExceptionBreakpoints" => Ok(json!({})),
+            "configurationDone" => self.configuration_done(),
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] })),
+            "stackTrace" => self.stack_trace(),
+            "scopes" => self.scopes(arguments),
+            "variables" => self.variables(arguments),
+            "continue" => self.resume(Resume::Continue),
+            "next" => self.resume(Resume::StepOver),
+            "stepIn" => self.resume(Resume::Step),
+            "stepOut" => self.resume(Resume::StepOut),
+            "pause" => self.pause(),
+            "disconnect" | "terminate" => {
+                self.respond(request, Ok(json!({})))?;
+
+                return Ok(false);
+            }
+            _ => Err(format!("Unsupported request {}.", command)),
+        };
+
+        self.respond(request, result)?;
+
+        for (event, body) in std::mem::take(&mut self.events) {
+            self.send_event(event, body)?;
+        }
+
+        return Ok(true);
+    }
+
+    fn session(&mut self) -> Result<&mut Session, String> {
+        return self
+            .session
+            .as_mut()
+            .ok_or_else(|| String::from("No program has been launched."));
+    }
+
+    /// Loads the program without running it. The client sets breakpoints once the `initialized`
+    /// event is sent and then sends `configurationDone`.
+    fn launch(&mut self, arguments: &Value) -> Result<Value, String> {
+        let program = arguments["program"]
+            .as_str()
+            .ok_or_else(|| String::from("The launch arguments have no program."))?;
+        let libraries: Vec<String> = arguments["libraries"]
+            .as_array()
+            .map(|libraries| {
+                libraries
+                    .iter()
+                    .filter_map(|library| library.as_str().map(String::from))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let verify = !arguments["noVerify"].as_bool().unwrap_or(false);
+
+        let machine = prepare_machine(program, &libraries, self.options, verify)?;
+        let console = Rc::new(RefCell::new(Vec::new()));
+
+        self.session = Some(Session {
+            debugger: Debugger::new(machine),
+            handler: OSHandler::with_terminal(
+                self.options.policy().clone(),
+                Box::new(io::empty()),
+                Box::new(Console(console.clone())),
+            ),
+            console,
+            stop_on_entry: arguments["stopOnEntry"].as_bool().unwrap_or(false),
+            running: false,
+            source_breakpoints: BTreeMap::new(),
+            instruction_breakpoints: Vec::new(),
+        });
+
+        self.events.push(("initialized", json!({})));
+
+        return Ok(json!({}));
+    }
+
+    fn configuration_done(&mut self) -> Result<Value, String> {
+        let session = self.session()?;
+
+        if session.stop_on_entry {
+            self.events.push(stopped_event("entry", None));
+        } else {
+            session.debugger.resume(Resume::Continue);
+            session.running = true;
+        }
+
+        return Ok(json!({}));
+    }
+
+    /// Replaces the breakpoints of a source file. Each line is moved to the first line after it
+    /// that has an instruction.
+    fn set_breakpoints(&mut self, arguments: &Value) -> Result<Value, String> {
+        let session = self.session()?;
+        let path = arguments["source"]["path"]
+            .as_str()
+            .ok_or_else(|| String::from("The source has no path."))?;
+        let lines = source_lines(
+            session.debugger.machine().symbols(),
+            session.debugger.machine().instructions().len(),
+            path,
+        );
+
+        let mut indices = Vec::new();
+        let mut breakpoints = Vec::new();
+
+        for breakpoint in arguments["breakpoints"].as_array().into_iter().flatten() {
+            let line = breakpoint["line"].as_u64().unwrap_or(0);
+
+            match lines.range(line..).next() {
+                Some((line, index)) => {
+                    indices.push(*index);
+                    breakpoints.push(json!({
+                        "verified": true,
+                        "line": line,
+                        "instructionReference": index.to_string(),
+                    }));
+                }
+                None => breakpoints.push(json!({
+                    "verified": false,
+                    "line": line,
+                    "message": "There are no instructions from this line onwards.",
+                })),
+            }
+        }
+
+        session
+            .source_breakpoints
+            .insert(String::from(path), indices);
+        session.update_breakpoints();
+
+        return Ok(json!({ "breakpoints": breakpoints }));
+    }
+
+    /// Replaces the breakpoints set by instruction index.
+    fn set_instruction_breakpoints(&mut self, arguments: &Value) -> Result<Value, String> {
+        let session = self.session()?;
+        let count = session.debugger.machine().instructions().len();
+
+        let mut indices = Vec::new();
+        let mut breakpoints = Vec::new();
+
+        for breakpoint in arguments["breakpoints"].as_array().into_iter().flatten() {
+            let index = breakpoint["instructionReference"]
+                .as_str()
+                .and_then(|reference| reference.parse::<i64>().ok())
+                .map(|index| index + breakpoint["offset"].as_i64().unwrap_or(0))
+                .filter(|index| (0..count as i64).contains(index));
+
+            match index {
+                Some(index) => {
+                    indices.push(index as usize);
+                    breakpoints.push(json!({
+                        "verified": true,
+                        "instructionReference": index.to_string(),
+                    }));
+                }
+                None => breakpoints.push(json!({
+                    "verified": false,
+                    "message": "There is no instruction here.",
+                })),
+            }
+        }
+
+        session.instruction_breakpoints = indices;
+        session.update_breakpoints();
+
+        return Ok(json!({ "breakpoints": breakpoints }));
+    }
+
+    fn stack_trace(&mut self) -> Result<Value, String> {
+        let session = self.session()?;
+        let machine = session.debugger.machine();
+        let frames = session.debugger.frames();
+
+        let stack_frames: Vec<Value> = frames
+            .iter()
+            .enumerate()
+            .map(|(id, frame)| {
+                let index = frame.index();
+                let symbols = machine.symbols();
+
+                let name = symbols
+                    .and_then(|symbols| symbols.function_at(index as u64))
+                    .map_or_else(
+                        || format!("instruction {}", index),
+                        |function| String::from(function.name()),
+                    );
+
+                let mut value = json!({
+                    "id": id,
+                    "name": name,
+                    "line": 0,
+                    "column": 0,
+                    "instructionPointerReference": index.to_string(),
+                });
+
+                if let Some(location) = symbols.and_then(|s| s.location_at(index as u64)) {
+                    value["source"] = source(location.file);
+                    value["line"] = json!(location.line);
+                    value["column"] = json!(1);
+                }
+
+                return value;
+            })
+            .collect();
+
+        return Ok(json!({
+            "stackFrames": stack_frames,
+            "totalFrames": frames.len(),
+        }));
+    }
+
+    fn scopes(&mut self, arguments: &Value) -> Result<Value, String> {
+        let frame = arguments["frameId"].as_u64().unwrap_or(0);
+
+        return Ok(json!({
+            "scopes": [
+                {
+                    "name": "Stack",
+                    "variablesReference": FRAME_REFERENCE + frame,
+                    "expensive": false,
+                },
+                {
+                    "name": "Registers",
+                    "variablesReference": REGISTERS_REFERENCE,
+                    "expensive": false,
+                },
+                {
+                    "name": "Heap",
+                    "variablesReference": HEAP_REFERENCE,
+                    "expensive": false,
+                },
+            ]
+        }));
+    }
+
+    fn variables(&mut self, arguments: &Value) -> Result<Value, String> {
+        let session = self.session()?;
+        let machine = session.debugger.machine();
+        let reference = arguments["variablesReference"].as_u64().unwrap_or(0);
+
+        let variables: Vec<Value> = match reference {
+            REGISTERS_REFERENCE => (Register::RFP as u8..16)
+                .map(Register::from_bits)
+                .map(|register| {
+                    let value = machine.registers().get_value(register as u8);
+
+                    variable(register_name(register), register_value(register, value))
+                })
+                .collect(),
+            HEAP_REFERENCE => {
+                let memory = machine.memory();
+
+                memory
+                    .addresses()
+                    .into_iter()
+                    .map(|address| {
+                        let block = memory.retrieve(&address).map_or(&[][..], |b| &b[..]);
+                        let mut value = format!("{} bytes", block.len());
+
+                        if !block.is_empty() {
+                            let preview: Vec<String> = block
+                                .iter()
+                                .take(HEAP_PREVIEW)
+                                .map(|byte| format!("{:02x}", byte))
+                                .collect();
+
+                            value.push_str(&format!(": {}", preview.join(" ")));
+
+                            if block.len() > HEAP_PREVIEW {
+                                value.push_str(" ...");
+                            }
+                        }
+
+                        if memory.is_read_only(&address) {
+                            value.push_str(" (read only)");
+                        }
+
+                        variable(format!("{:#x}", address), value)
+                    })
+                    .collect()
+            }
+            _ => {
+                let frames = session.debugger.frames();
+                let frame = reference
+                    .checked_sub(FRAME_REFERENCE)
+                    .and_then(|id| frames.get(id as usize))
+                    .ok_or_else(|| format!("Unknown variables reference {}.", reference))?;
+
+                session
+                    .debugger
+                    .frame_values(frame)
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, value)| {
+                        variable(
+                            format!("[{}]", i),
+                            format!("{} ({:#x})", value as i64, value),
+                        )
+                    })
+                    .collect()
+            }
+        };
+
+        return Ok(json!({ "variables": variables }));
+    }
+
+    fn resume(&mut self, resume: Resume) -> Result<Value, String> {
+        let session = self.session()?;
+
+        session.debugger.resume(resume);
+        session.running = true;
+
+        return Ok(json!({ "allThreadsContinued": true }));
+    }
+
+    fn pause(&mut self) -> Result<Value, String> {
+        let session = self.session()?;
+
+        if session.running {
+            session.running = false;
+            self.events.push(stopped_event("pause", None));
+        }
+
+        return Ok(json!({}));
+    }
+
+    /// Runs the program for a while if it is running, sending events for its output and for why
+    /// it stopped.
+    fn run_chunk(&mut self) -> io::Result<()> {
+        let session = match &mut self.session {
+            Some(session) if session.running => session,
+            _ => return Ok(()),
+        };
+
+        let reason = session.debugger.run(&mut session.handler, RUN_CHUNK);
+        let output = std::mem::take(&mut *session.console.borrow_mut());
+
+        let events = match reason {
+            StopReason::Limit => Vec::new(),
+            StopReason::Breakpoint => vec![stopped_event("breakpoint", None)],
+            StopReason::Step => vec![stopped_event("step", None)],
+            StopReason::Error(error) => {
+                let description = describe_machine_error(session.debugger.machine(), error);
+
+                vec![stopped_event("exception", Some(description))]
+            }
+            StopReason::Finished | StopReason::Halted => vec![
+                (
+                    "exited",
+                    json!({ "exitCode": session.handler.exit_code().unwrap_or(0) }),
+                ),
+                ("terminated", json!({})),
+            ],
+        };
+
+        if !events.is_empty() {
+            session.running = false;
+        }
+
+        if !output.is_empty() {
+            self.send_event(
+                "output",
+                json!({
+                    "category": "stdout",
+                    "output": String::from_utf8_lossy(&output),
+                }),
+            )?;
+        }
+
+        for (event, body) in events {
+            self.send_event(event, body)?;
+        }
+
+        return Ok(());
+    }
+
+    fn respond(&mut self, request: &Value, result: Result<Value, String>) -> io::Result<()> {
+        self.sequence += 1;
+
+        let mut response = json!({
+            "seq": self.sequence,
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+
+        match result {
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+
+        return self.write_message(&response);
+    }
+
+    fn send_event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.sequence += 1;
+
+        let message = json!({
+            "seq": self.sequence,
+            "type": "event",
+            "event": event,
+            "body": body,
+        });
+
+        return self.write_message(&message);
+    }
+
+    fn write_message(&mut self, message: &Value) -> io::Result<()> {
+        let body = message.to_string();
+
+        write!(
+            self.output,
+            "Content-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )?;
+
+        return self.output.flush();
+    }
+}
+
+impl Session {
+    fn update_breakpoints(&mut self) {
+        self.debugger.clear_breakpoints();
+
+        for index in self
+            .source_breakpoints
+            .values()
+            .flatten()
+            .chain(&self.instruction_breakpoints)
+        {
+            self.debugger.add_breakpoint(*index);
+        }
+    }
+}
+
+fn stopped_event(reason: &str, text: Option<String>) -> (&'static str, Value) {
+    let mut body = json!({
+        "reason": reason,
+        "threadId": THREAD_ID,
+        "allThreadsStopped": true,
+    });
+
+    if let Some(text) = text {
+        body["text"] = json!(text);
+    }
+
+    return ("stopped", body);
+}
+
+fn variable(name: impl Into<String>, value: String) -> Value {
+    return json!({
+        "name": name.into(),
+        "value": value,
+        "variablesReference": 0,
+    });
+}
+
+fn source(path: &str) -> Value {
+    let name = Path::new(path).file_name().map_or_else(
+        || String::from(path),
+        |name| name.to_string_lossy().into_owned(),
+    );
+
+    return json!({ "name": name, "path": path });
+}
+
+/// The first instruction of each line of a source file. The file names in the symbols may be
+/// relative to the directory the file was assembled in.
+fn source_lines(
+    symbols: Option<&SymbolTable>,
+    instruction_count: usize,
+    path: &str,
+) -> BTreeMap<u64, usize> {
+    let mut lines = BTreeMap::new();
+
+    let symbols = match symbols {
+        Some(symbols) => symbols,
+        None => return lines,
+    };
+
+    for index in 0..instruction_count {
+        if let Some(location) = symbols.location_at(index as u64) {
+            if !location.file.is_empty() && Path::new(path).ends_with(location.file) {
+                lines.entry(location.line).or_insert(index);
+            }
+        }
+    }
+
+    return lines;
+}
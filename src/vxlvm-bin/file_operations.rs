@@ -1,15 +1,22 @@
 use crate::handler::OSHandler;
 
-use vxl_iset::instruction::Instruction;
-use vxlvm::error::VXLVMError;
-use vxlvm::loader::Loader;
-use vxlvm::validator::BulkValidator;
-use vxlvm::vm::VM;
+use vxlvm::assembler::assemble;
+use vxlvm::cfg::ControlFlowGraph;
+use vxlvm::disassembler::disassemble_bytes;
+use vxlvm::error::{LoaderError, ProgramError, VMError, VXLVMError, VerificationError};
+use vxlvm::linker::{self, Module};
+use vxlvm::lint::check_types;
+use vxlvm::loader::{ChecksumAlgorithm, LoadPolicy, Loader};
+use vxlvm::signature::{sign_file, SigningKey, TrustedKeys};
+use vxlvm::source::ReadSource;
+use vxlvm::stack_depth::StackUsage;
+use vxlvm::validator::{verify_program, BulkValidator};
+use vxlvm::vm::{OverflowBehaviour, VM};
 
 use std::fs::OpenOptions;
-use std::io::Read;
+use std::io::{BufReader, Read};
 
-fn load_file(path: &str) -> Result<Vec<Instruction>, String> {
+fn read_file(path: &str) -> Result<Vec<u8>, String> {
     let mut file = OpenOptions::new()
         .read(true)
         .open(path)
@@ -20,45 +27,446 @@ fn load_file(path: &str) -> Result<Vec<Instruction>, String> {
     file.read_to_end(&mut contents)
         .map_err(|e| format!("Cannot read file {}. OS Error: {}", path, e))?;
 
-    let loader = Loader::load_bytes(&contents).map_err(|e| {
-        if cfg!(feature = "detailed_errors") {
-            format!("{}", e.specific_description())
-        } else {
-            format!("{}", e.short_description())
+    return Ok(contents);
+}
+
+pub fn describe_error<E: VXLVMError>(error: E) -> String {
+    if cfg!(feature = "detailed_errors") {
+        return error.specific_description();
+    } else {
+        return error.short_description();
+    }
+}
+
+/// How files are checked when they are loaded to be run or linked.
+pub struct LoadOptions {
+    /// Also used for the modules loaded by the running program.
+    policy: LoadPolicy,
+}
+
+impl LoadOptions {
+    /// Reads the trusted keys from a directory, each file containing a public key in hex.
+    pub fn new(allow_unchecked: bool, trusted_keys: Option<&str>) -> Result<Self, String> {
+        let trusted_keys = match trusted_keys {
+            Some(directory) => Some(read_trusted_keys(directory)?),
+            None => None,
+        };
+
+        return Ok(Self {
+            policy: LoadPolicy::new(allow_unchecked, trusted_keys),
+        });
+    }
+
+    pub fn policy(&self) -> &LoadPolicy {
+        return &self.policy;
+    }
+}
+
+fn read_trusted_keys(directory: &str) -> Result<TrustedKeys, String> {
+    let mut keys = TrustedKeys::new();
+
+    let entries = std::fs::read_dir(directory)
+        .map_err(|e| format!("Cannot read directory {}. OS Error: {}", directory, e))?;
+
+    for entry in entries {
+        let path = entry.map_err(|e| format!("{}", e))?.path();
+
+        if !path.is_file() {
+            continue;
         }
-    })?;
 
-    loader.validate().map_err(|e| {
-        if cfg!(feature = "detailed_errors") {
-            format!("{}", e.specific_description())
-        } else {
-            format!("{}", e.short_description())
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Cannot read file {}. OS Error: {}", path.display(), e))?;
+
+        let added = parse_hex(contents.trim())
+            .and_then(|bytes| bytes.try_into().ok())
+            .is_some_and(|key: [u8; 32]| keys.add_key(&key));
+
+        if !added {
+            return Err(format!("{} does not contain a public key.", path.display()));
         }
-    })?;
+    }
+
+    if keys.is_empty() {
+        return Err(format!("There are no trusted keys in {}.", directory));
+    }
+
+    return Ok(keys);
+}
+
+/// Loads a file a chunk at a time, checking its checksum and decoding its instructions.
+pub fn load_file(path: &str, options: &LoadOptions) -> Result<Module, String> {
+    let file = OpenOptions::new()
+        .read(true)
+        .open(path)
+        .map_err(|e| format!("{}", e))?;
+
+    let source = ReadSource::new(BufReader::new(file));
+    let (_header, module) = options
+        .policy
+        .load_from(source, BulkValidator::new())
+        .map_err(|e| match e {
+            VerificationError::Loader(LoaderError::UncheckedNotAllowed) => format!(
+                "{} has no checksum, pass --allow-unchecked to load it anyway.",
+                path
+            ),
+            _ => describe_error(e),
+        })?;
+
+    return Ok(module);
+}
+
+/// Loads each file and links them together, the first file provides the entry point.
+fn link_files(paths: &[String], options: &LoadOptions) -> Result<Module, String> {
+    let modules = paths
+        .iter()
+        .map(|path| load_file(path, options))
+        .collect::<Result<Vec<Module>, String>>()?;
+
+    return linker::link(&modules).map_err(describe_error);
+}
+
+/// Describes an error raised while running, followed by a trace of the active calls.
+pub fn describe_machine_error(machine: &VM, error: VMError) -> String {
+    let mut description = describe_error(error);
+
+    for index in machine.call_stack() {
+        description.push_str(&format!("\n    at {}", machine.describe_instruction(index)));
+    }
+
+    return description;
+}
+
+/// Runs a file after linking it with any libraries, see `prepare_machine`.
+pub fn execute_file(
+    path: &str,
+    libraries: &[String],
+    options: &LoadOptions,
+    verify: bool,
+) -> Result<(), String> {
+    let mut handler = OSHandler::new(options.policy().clone());
+    let mut machine = prepare_machine(path, libraries, options, verify)?;
+
+    return machine
+        .run(&mut handler)
+        .map_err(|e| describe_machine_error(&machine, e));
+}
+
+/// Loads a file and links it with any libraries into a machine that is ready to run. Unless
+/// `verify` is false the program is checked with `verify_program` and for stack underflows in
+/// its entry function first, underflows in other functions are only warned about.
+///
+/// When the program was verified the stack is sized to the deepest it can use, if that is known.
+/// Otherwise the machine has the default stack.
+pub fn prepare_machine(
+    path: &str,
+    libraries: &[String],
+    options: &LoadOptions,
+    verify: bool,
+) -> Result<VM, String> {
+    let mut module = load_file(path, options)?;
+
+    if !libraries.is_empty() || !module.link_table().imports().is_empty() {
+        let mut modules = vec![module];
+
+        for library in libraries {
+            modules.push(load_file(library, options)?);
+        }
+
+        module = linker::link(&modules).map_err(describe_error)?;
+    }
+
+    let usage = StackUsage::new(module.instructions(), module.entry());
+
+    if verify {
+        let mut problems = verify_program(module.instructions(), module.entry());
+        problems.extend(
+            usage
+                .entry_underflows()
+                .iter()
+                .map(|index| ProgramError::StackUnderflow(*index)),
+        );
+
+        // A function may pop what its caller pushed on purpose, so these don't stop it running.
+        for index in usage.call_underflows() {
+            eprintln!(
+                "Warning: {}",
+                describe_error(ProgramError::StackUnderflow(*index))
+            );
+        }
+
+        if !problems.is_empty() {
+            let descriptions: Vec<String> = problems.into_iter().map(describe_error).collect();
+
+            return Err(descriptions.join("\n"));
+        }
+    }
+
+    let instructions = module.instructions().to_vec();
+    let entry = module.entry() as usize;
+
+    // The depth is only a bound for programs that passed verification.
+    let depth = usage.max_depth().filter(|_| verify);
+
+    let mut machine = match depth {
+        Some(depth) => VM::new_with_options(
+            depth as usize,
+            instructions,
+            entry,
+            OverflowBehaviour::default(),
+        ),
+        None => VM::new_fixed_start(instructions, entry),
+    };
+
+    machine
+        .load_data_sections(module.data_sections().to_vec())
+        .map_err(describe_error)?;
+
+    if let Some(symbols) = module.symbols() {
+        machine.set_symbols(symbols.clone());
+    }
+
+    return Ok(machine);
+}
+
+/// Links the files into a single file with no imports, using the named checksum algorithm.
+pub fn link_to_file(
+    paths: &[String],
+    output: &str,
+    checksum: &str,
+    options: &LoadOptions,
+) -> Result<(), String> {
+    let algorithm = ChecksumAlgorithm::from_name(checksum)
+        .ok_or_else(|| format!("Unknown checksum algorithm {}.", checksum))?;
+
+    let bytes = link_files(paths, options)?.to_writer(algorithm).to_bytes();
+
+    return std::fs::write(output, bytes)
+        .map_err(|e| format!("Cannot write file {}. OS Error: {}", output, e));
+}
+
+/// Signs a file with the secret key stored in hex in `key_path`.
+pub fn sign_to_file(path: &str, key_path: &str, output: &str) -> Result<(), String> {
+    let contents = read_file(path)?;
+
+    let key_text = std::fs::read_to_string(key_path)
+        .map_err(|e| format!("Cannot read file {}. OS Error: {}", key_path, e))?;
+    let key: [u8; 32] = parse_hex(key_text.trim())
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| format!("{} does not contain a secret key.", key_path))?;
+    let key = SigningKey::from_bytes(&key);
+
+    let bytes = sign_file(&contents, &key).map_err(describe_error)?;
+
+    std::fs::write(output, bytes)
+        .map_err(|e| format!("Cannot write file {}. OS Error: {}", output, e))?;
 
-    return loader
+    println!(
+        "Signed with public key {}",
+        hex_string(&key.verifying_key().to_bytes())
+    );
+
+    return Ok(());
+}
+
+/// Assembles a source file, keeping the line of each instruction in the symbols.
+pub fn assemble_to_file(path: &str, output: &str, checksum: &str) -> Result<(), String> {
+    let algorithm = ChecksumAlgorithm::from_name(checksum)
+        .ok_or_else(|| format!("Unknown checksum algorithm {}.", checksum))?;
+
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read file {}. OS Error: {}", path, e))?;
+
+    let module = assemble(path, &source).map_err(|e| format!("{}: {}", path, describe_error(e)))?;
+    let bytes = module.to_writer(algorithm).to_bytes();
+
+    return std::fs::write(output, bytes)
+        .map_err(|e| format!("Cannot write file {}. OS Error: {}", output, e));
+}
+
+pub fn disassemble_file(path: &str) -> Result<(), String> {
+    let contents = read_file(path)?;
+
+    let output =
+        disassemble_bytes(&contents).map_err(|e| e.either(describe_error, describe_error))?;
+
+    print!("{}", output);
+
+    return Ok(());
+}
+
+/// Prints the control flow graph of a file as DOT, without verifying its checksum.
+pub fn cfg_file(path: &str) -> Result<(), String> {
+    let contents = read_file(path)?;
+
+    let loader = Loader::load_bytes(&contents).map_err(describe_error)?;
+    let symbols = loader.symbols().cloned();
+
+    let (header, instructions) = loader
         .to_instructions(BulkValidator::new())
-        .map_err(|e| {
-            if cfg!(feature = "detailed_errors") {
-                format!("{}", e.specific_description())
-            } else {
-                format!("{}", e.short_description())
-            }
-        })
-        .map(|(_header, instructions)| instructions);
+        .map_err(describe_error)?;
+
+    let graph = ControlFlowGraph::new(&instructions, header.starting_offset());
+
+    print!("{}", graph.to_dot(&instructions, symbols.as_ref()));
+
+    return Ok(());
 }
 
-pub fn execute_file(path: &str) -> Result<(), String> {
-    let mut handler = OSHandler::new();
-    let mut machine = VM::new(load_file(path)?);
+pub fn inspect_file(path: &str) -> Result<(), String> {
+    let contents = read_file(path)?;
+
+    let loader = Loader::read_bytes(&contents).map_err(describe_error)?;
+    let header = loader.get_header();
+
+    let stored_checksum = header.checksum();
+
+    println!("Version:           {}", header.version());
+    println!("Declared size:     {} bytes", header.file_size());
+    println!("Program size:      {} bytes", loader.program_bytes().len());
+    println!("Entry offset:      {}", header.starting_offset());
+    println!("Flags:             {:#010b}", header.flags());
+    println!(
+        "Hash algorithm:    {}",
+        loader
+            .checksum_algorithm()
+            .map_or("unknown", |algorithm| algorithm.name())
+    );
+
+    if header.version() >= Loader::SECTIONED_VERSION {
+        println!("Code section:      {} bytes", loader.code_bytes().len());
+
+        for (i, section) in loader.data_sections().iter().enumerate() {
+            println!("Data section {}:    {} bytes", i, section.len());
+        }
+
+        if let Some(symbols) = loader.symbols() {
+            println!("Symbols:           {} functions", symbols.functions().len());
+        }
+
+        if let Some(link_table) = loader.link_table() {
+            for export in link_table.exports() {
+                println!("Export:            {} at {}", export.name(), export.index());
+            }
+
+            for import in link_table.imports() {
+                println!(
+                    "Import:            {} at {:?}",
+                    import.name(),
+                    import.sites()
+                );
+            }
+        }
+    }
 
-    if let Err(e) = machine.run(&mut handler) {
-        if cfg!(feature = "detailed_errors") {
-            return Err(e.specific_description());
+    if let Some(signature) = loader.signature() {
+        println!("Signed by:         {}", hex_string(&signature.public_key()));
+    }
+
+    println!("Stored checksum:   {}", hex_string(&stored_checksum));
+
+    if let Some(computed_checksum) = loader.compute_checksum() {
+        println!("Computed checksum: {}", hex_string(&computed_checksum));
+
+        if stored_checksum == computed_checksum {
+            println!("Checksum matches.");
         } else {
-            return Err(e.short_description());
+            println!("Checksum does not match.");
+        }
+    }
+
+    return Ok(());
+}
+
+/// Prints every problem found in the file, failing if there were any.
+pub fn verify_file(path: &str, json: bool) -> Result<(), String> {
+    let contents = read_file(path)?;
+    let problems = Loader::verify_bytes(&contents);
+
+    if json {
+        let problems_json: Vec<serde_json::Value> = problems
+            .iter()
+            .map(|problem| {
+                let (kind, offset, instruction) = match problem {
+                    VerificationError::Loader(_) => ("loader", None, None),
+                    VerificationError::Validator(offset, _) => ("validator", Some(*offset), None),
+                    VerificationError::Program(e) => ("program", None, e.instruction()),
+                };
+
+                serde_json::json!({
+                    "kind": kind,
+                    "offset": offset,
+                    "instruction": instruction,
+                    "message": describe_error(*problem),
+                })
+            })
+            .collect();
+
+        let output = serde_json::json!({
+            "file": path,
+            "valid": problems.is_empty(),
+            "problems": problems_json,
+        });
+
+        println!("{}", output);
+    } else if problems.is_empty() {
+        println!("{}: ok", path);
+    } else {
+        for problem in &problems {
+            println!("{}: {}", path, describe_error(*problem));
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(format!("{} problem(s) found in {}.", problems.len(), path));
+    }
+
+    return Ok(());
+}
+
+/// Prints each use of a register holding the wrong type of value, see `check_types`.
+pub fn lint_file(path: &str) -> Result<(), String> {
+    let contents = read_file(path)?;
+
+    let loader = Loader::load_bytes(&contents).map_err(describe_error)?;
+    let symbols = loader.symbols().cloned();
+
+    let (header, instructions) = loader
+        .to_instructions(BulkValidator::new())
+        .map_err(describe_error)?;
+
+    let mismatches = check_types(&instructions, header.starting_offset());
+
+    for mismatch in &mismatches {
+        println!("{}: {}", path, mismatch.describe());
+
+        if let Some(symbols) = &symbols {
+            println!("    at {}", symbols.describe(mismatch.index() as u64));
         }
     }
 
+    if !mismatches.is_empty() {
+        return Err(format!(
+            "{} type mismatch(es) found in {}.",
+            mismatches.len(),
+            path
+        ));
+    }
+
     return Ok(());
 }
+
+fn hex_string(bytes: &[u8]) -> String {
+    return bytes.iter().map(|b| format!("{:02x}", b)).collect();
+}
+
+pub fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return None;
+    }
+
+    return (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok())
+        .collect();
+}
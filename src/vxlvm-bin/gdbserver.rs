@@ -0,0 +1,530 @@
+use crate::file_operations::{describe_machine_error, parse_hex, prepare_machine, LoadOptions};
+use crate::handler::OSHandler;
+
+use vxl_iset::instruction_arguments::Register;
+use vxlvm::debugger::{Debugger, Resume, StopReason};
+use vxlvm::disassembler::register_name;
+
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+
+/// The number of instructions run between checks for an interrupt from the client.
+const RUN_CHUNK: u64 = 10_000;
+
+/// Addresses with this bit set are in the heap, otherwise they are offsets into the stack so
+/// that `rsp` and `rfp` can be used as addresses directly.
+const HEAP_BIT: u64 = 1 << 63;
+/// A heap address holds the block's handle above this bit and the offset into it below.
+const HANDLE_SHIFT: u64 = 32;
+const OFFSET_MASK: u64 = (1 << HANDLE_SHIFT) - 1;
+
+const REGISTER_COUNT: u8 = 16;
+
+/// The most bytes of packet data sent or accepted, which is given to the client in hex.
+const PACKET_SIZE: usize = 0x4000;
+
+/// Signals sent in stop replies.
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+
+/// Serves one program to a debugger over the GDB remote serial protocol.
+///
+/// The 16 registers are numbered in the order they are encoded, starting with `rip` which holds
+/// the index of the next instruction and is the program counter. Breakpoint addresses are
+/// instruction indices too.
+struct GdbServer {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    debugger: Debugger,
+    handler: OSHandler,
+    /// Turned off by `QStartNoAckMode`.
+    acknowledge: bool,
+}
+
+/// Loads the file and waits for a debugger to connect on the port of the loopback address,
+/// serving it until it detaches or kills the program.
+pub fn run_gdbserver(
+    path: &str,
+    libraries: &[String],
+    port: u16,
+    options: &LoadOptions,
+    verify: bool,
+) -> Result<(), String> {
+    let machine = prepare_machine(path, libraries, options, verify)?;
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port)).map_err(|e| format!("{}", e))?;
+    let address = listener.local_addr().map_err(|e| format!("{}", e))?;
+
+    eprintln!("Listening on {}", address);
+
+    let (stream, _) = listener.accept().map_err(|e| format!("{}", e))?;
+    let _ = stream.set_nodelay(true);
+
+    let mut server = GdbServer {
+        reader: BufReader::new(stream.try_clone().map_err(|e| format!("{}", e))?),
+        writer: stream,
+        debugger: Debugger::new(machine),
+        // The program shares the terminal of the server, but its exit call must not end the
+        // server before the debugger is told.
+        handler: OSHandler::with_terminal(
+            options.policy().clone(),
+            Box::new(io::stdin()),
+            Box::new(io::stdout()),
+        ),
+        acknowledge: true,
+    };
+
+    return server.serve().map_err(|e| format!("{}", e));
+}
+
+impl GdbServer {
+    fn serve(&mut self) -> io::Result<()> {
+        while let Some(packet) = self.read_packet()? {
+            let reply = match packet.as_slice() {
+                // An interrupt while the program is already stopped.
+                [0x03] => format!("S{:02x}", SIGINT),
+                _ => match self.handle_packet(&String::from_utf8_lossy(&packet))? {
+                    Some(reply) => reply,
+                    None => return Ok(()),
+                },
+            };
+
+            self.write_packet(&reply)?;
+
+            // The reply is the last packet that is acknowledged.
+            if packet == b"QStartNoAckMode" {
+                self.acknowledge = false;
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Returns the reply, or `None` once the session is over.
+    fn handle_packet(&mut self, packet: &str) -> io::Result<Option<String>> {
+        let (command, arguments) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+
+        let reply = match command {
+            "?" => self.status(),
+            "g" => self.read_registers(),
+            "G" => self.write_registers(arguments),
+            "p" => self.read_register(arguments),
+            "P" => self.write_register(arguments),
+            "m" => self.read_memory(arguments),
+            "Z" | "z" => self.set_breakpoint(command == "Z", arguments),
+            "c" => self.resume(Resume::Continue)?,
+            "s" => self.resume(Resume::Step)?,
+            "v" => self.handle_v_packet(arguments)?,
+            "q" => self.handle_query(arguments),
+            "Q" if arguments == "StartNoAckMode" => String::from("OK"),
+            // There is a single thread.
+            "H" | "T" => String::from("OK"),
+            "D" => {
+                self.write_packet("OK")?;
+
+                return Ok(None);
+            }
+            "k" => return Ok(None),
+            // An empty reply means the packet isn't supported.
+            _ => String::new(),
+        };
+
+        return Ok(Some(reply));
+    }
+
+    fn handle_v_packet(&mut self, arguments: &str) -> io::Result<String> {
+        if arguments == "Cont?" {
+            return Ok(String::from("vCont;c;C;s;S"));
+        }
+
+        if let Some(actions) = arguments.strip_prefix("Cont;") {
+            // Only the first action matters when there is one thread.
+            let resume = match actions.chars().next() {
+                Some('s' | 'S') => Resume::Step,
+                _ => Resume::Continue,
+            };
+
+            return self.resume(resume);
+        }
+
+        if arguments.starts_with("Kill") {
+            return Ok(String::from("OK"));
+        }
+
+        return Ok(String::new());
+    }
+
+    fn handle_query(&mut self, arguments: &str) -> String {
+        if arguments.starts_with("Supported") {
+            return format!(
+                "PacketSize={:x};QStartNoAckMode+;swbreak+;vContSupported+;qXfer:features:read+",
+                PACKET_SIZE
+            );
+        }
+
+        if let Some(request) = arguments.strip_prefix("Xfer:features:read:target.xml:") {
+            return read_chunk(&target_description(), request);
+        }
+
+        return match arguments {
+            "C" => String::from("QC1"),
+            "fThreadInfo" => String::from("m1"),
+            "sThreadInfo" => String::from("l"),
+            "Attached" => String::from("1"),
+            _ => String::new(),
+        };
+    }
+
+    /// Runs towards the target until it stops, checking for an interrupt between chunks.
+    fn resume(&mut self, resume: Resume) -> io::Result<String> {
+        self.debugger.resume(resume);
+
+        loop {
+            let reason = self.debugger.run(&mut self.handler, RUN_CHUNK);
+            let _ = io::stdout().flush();
+
+            if reason != StopReason::Limit {
+                return Ok(self.stop_reply(reason));
+            }
+
+            if self.interrupted()? {
+                return Ok(format!("T{:02x}thread:1;", SIGINT));
+            }
+        }
+    }
+
+    /// Why the program last stopped, for a debugger that has just connected.
+    fn status(&self) -> String {
+        let machine = self.debugger.machine();
+
+        if machine.is_halted() || machine.ip() >= machine.instructions().len() {
+            return self.exit_reply();
+        }
+
+        return format!("T{:02x}thread:1;", SIGTRAP);
+    }
+
+    fn exit_reply(&self) -> String {
+        return format!("W{:02x}", self.handler.exit_code().unwrap_or(0) & 0xff);
+    }
+
+    fn stop_reply(&mut self, reason: StopReason) -> String {
+        return match reason {
+            StopReason::Breakpoint => format!("T{:02x}thread:1;swbreak:;", SIGTRAP),
+            StopReason::Step | StopReason::Limit => format!("T{:02x}thread:1;", SIGTRAP),
+            StopReason::Finished | StopReason::Halted => self.exit_reply(),
+            StopReason::Error(error) => {
+                let description = describe_machine_error(self.debugger.machine(), error);
+
+                // Shown on the debugger's console before it reports the signal.
+                let _ = self.write_packet(&format!("O{}", encode_hex(description.as_bytes())));
+                let _ = self.write_packet(&format!("O{}", encode_hex(b"\n")));
+
+                format!("T{:02x}thread:1;", SIGILL)
+            }
+        };
+    }
+
+    /// Returns true if the client has sent an interrupt, or has gone away.
+    fn interrupted(&mut self) -> io::Result<bool> {
+        self.reader.get_ref().set_nonblocking(true)?;
+
+        let result = match self.reader.fill_buf() {
+            Ok([]) => Ok(true),
+            Ok(bytes) => Ok(bytes[0] == 0x03),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(false),
+            Err(e) => Err(e),
+        };
+
+        self.reader.get_ref().set_nonblocking(false)?;
+
+        if let Ok(true) = result {
+            if !self.reader.buffer().is_empty() {
+                self.reader.consume(1);
+            }
+        }
+
+        return result;
+    }
+
+    fn register(&self, number: u8) -> u64 {
+        if number == Register::RIP as u8 {
+            return self.debugger.machine().ip() as u64;
+        }
+
+        return self.debugger.machine().registers().get_value(number);
+    }
+
+    fn set_register(&mut self, number: u8, value: u64) {
+        let machine = self.debugger.machine_mut();
+
+        if number == Register::RIP as u8 {
+            machine.set_ip(value as usize);
+        } else {
+            machine.registers_mut().set_value(number, value);
+        }
+    }
+
+    fn read_registers(&self) -> String {
+        return (0..REGISTER_COUNT)
+            .map(|number| encode_hex(&self.register(number).to_le_bytes()))
+            .collect();
+    }
+
+    fn write_registers(&mut self, arguments: &str) -> String {
+        let bytes = match parse_hex(arguments) {
+            Some(bytes) if bytes.len() == REGISTER_COUNT as usize * 8 => bytes,
+            _ => return String::from("E01"),
+        };
+
+        for (number, value) in bytes.chunks_exact(8).enumerate() {
+            self.set_register(number as u8, u64::from_le_bytes(value.try_into().unwrap()));
+        }
+
+        return String::from("OK");
+    }
+
+    fn read_register(&self, arguments: &str) -> String {
+        return match u8::from_str_radix(arguments, 16) {
+            Ok(number) if number < REGISTER_COUNT => {
+                encode_hex(&self.register(number).to_le_bytes())
+            }
+            _ => String::from("E01"),
+        };
+    }
+
+    fn write_register(&mut self, arguments: &str) -> String {
+        let (number, value) = match arguments.split_once('=') {
+            Some((number, value)) => (u8::from_str_radix(number, 16).ok(), parse_hex(value)),
+            None => return String::from("E01"),
+        };
+
+        return match (number, value) {
+            (Some(number), Some(value)) if number < REGISTER_COUNT && value.len() == 8 => {
+                self.set_register(number, u64::from_le_bytes(value.try_into().unwrap()));
+
+                String::from("OK")
+            }
+            _ => String::from("E01"),
+        };
+    }
+
+    /// Reads up to the end of the stack or heap block the address is in.
+    fn read_memory(&self, arguments: &str) -> String {
+        let (address, length) = match parse_address_length(arguments) {
+            Some(request) => request,
+            None => return String::from("E01"),
+        };
+
+        // Each byte is two hex digits, a longer read is cut short as the protocol allows.
+        let length = length.min(PACKET_SIZE as u64 / 2);
+        let machine = self.debugger.machine();
+
+        let bytes = if address & HEAP_BIT == 0 {
+            let end = address.saturating_add(length).min(machine.stack().size());
+
+            if address >= end {
+                None
+            } else {
+                machine.stack().get_top(end, end - address)
+            }
+        } else {
+            let handle = (address & !HEAP_BIT) >> HANDLE_SHIFT;
+            let offset = (address & OFFSET_MASK) as usize;
+
+            machine
+                .memory()
+                .retrieve(&handle)
+                .filter(|block| offset < block.len())
+                .map(|block| {
+                    &block[offset..block.len().min(offset.saturating_add(length as usize))]
+                })
+        };
+
+        return bytes.map_or_else(|| String::from("E01"), encode_hex);
+    }
+
+    /// Software and hardware breakpoints are treated the same. Watchpoints aren't supported.
+    fn set_breakpoint(&mut self, insert: bool, arguments: &str) -> String {
+        let (kind, location) = match arguments.split_once(',') {
+            Some(request) => request,
+            None => return String::from("E01"),
+        };
+
+        if kind != "0" && kind != "1" {
+            return String::new();
+        }
+
+        let index = match parse_address_length(location) {
+            Some((address, _)) => address as usize,
+            None => return String::from("E01"),
+        };
+
+        if insert && !self.debugger.add_breakpoint(index) {
+            return String::from("E01");
+        }
+
+        if !insert {
+            self.debugger.remove_breakpoint(index);
+        }
+
+        return String::from("OK");
+    }
+
+    /// Reads the data of the next packet, acknowledging it, or a lone interrupt byte. Returns
+    /// `None` when the connection is closed.
+    fn read_packet(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            let mut byte = [0];
+
+            if !read_byte(&mut self.reader, &mut byte)? {
+                return Ok(None);
+            }
+
+            match byte[0] {
+                b'$' => (),
+                0x03 => return Ok(Some(vec![0x03])),
+                // Acknowledgements, which are never negative as the connection is reliable.
+                _ => continue,
+            }
+
+            let mut data = Vec::new();
+            self.reader.read_until(b'#', &mut data)?;
+
+            if data.pop() != Some(b'#') {
+                return Ok(None);
+            }
+
+            let mut checksum = [0; 2];
+            if !read_byte(&mut self.reader, &mut checksum[..1])?
+                || !read_byte(&mut self.reader, &mut checksum[1..])?
+            {
+                return Ok(None);
+            }
+
+            let valid = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|checksum| u8::from_str_radix(checksum, 16).ok())
+                == Some(packet_checksum(&data));
+
+            if self.acknowledge {
+                self.writer.write_all(if valid { b"+" } else { b"-" })?;
+            }
+
+            if valid {
+                return Ok(Some(unescape(&data)));
+            }
+        }
+    }
+
+    fn write_packet(&mut self, data: &str) -> io::Result<()> {
+        let data = escape(data.as_bytes());
+
+        self.writer.write_all(b"$")?;
+        self.writer.write_all(&data)?;
+        write!(self.writer, "#{:02x}", packet_checksum(&data))?;
+
+        return self.writer.flush();
+    }
+}
+
+fn read_byte<R: io::Read>(reader: &mut R, byte: &mut [u8]) -> io::Result<bool> {
+    return match reader.read_exact(byte) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    };
+}
+
+fn packet_checksum(data: &[u8]) -> u8 {
+    return data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+}
+
+/// `}` is followed by a byte xor 0x20.
+fn unescape(data: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(data.len());
+    let mut iter = data.iter();
+
+    while let Some(byte) = iter.next() {
+        match (byte, iter.clone().next()) {
+            (b'}', Some(escaped)) => {
+                bytes.push(escaped ^ 0x20);
+                iter.next();
+            }
+            _ => bytes.push(*byte),
+        }
+    }
+
+    return bytes;
+}
+
+fn escape(data: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(data.len());
+
+    for byte in data {
+        if matches!(byte, b'$' | b'#' | b'}' | b'*') {
+            bytes.push(b'}');
+            bytes.push(byte ^ 0x20);
+        } else {
+            bytes.push(*byte);
+        }
+    }
+
+    return bytes;
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    return bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+}
+
+/// Parses `address,length` in hex.
+fn parse_address_length(text: &str) -> Option<(u64, u64)> {
+    let (address, length) = text.split_once(',')?;
+
+    return Some((
+        u64::from_str_radix(address, 16).ok()?,
+        u64::from_str_radix(length, 16).ok()?,
+    ));
+}
+
+/// Replies to a `qXfer` read of `offset,length` from the document.
+fn read_chunk(document: &str, request: &str) -> String {
+    let (offset, length) = match parse_address_length(request) {
+        Some(request) => request,
+        None => return String::from("E01"),
+    };
+
+    let start = (offset as usize).min(document.len());
+    let end = start.saturating_add(length as usize).min(document.len());
+    let marker = if end == document.len() { 'l' } else { 'm' };
+
+    return format!("{}{}", marker, &document[start..end]);
+}
+
+/// Describes the registers to the debugger, in the order of `g` replies.
+fn target_description() -> String {
+    let registers: String = (0..REGISTER_COUNT)
+        .map(|number| {
+            let register = Register::from_bits(number);
+            let kind = match register {
+                Register::RIP => "code_ptr",
+                Register::RFP | Register::RSP => "data_ptr",
+                _ => "int64",
+            };
+
+            format!(
+                "<reg name=\"{}\" bitsize=\"64\" type=\"{}\" regnum=\"{}\"/>",
+                register_name(register).trim_start_matches('$'),
+                kind,
+                number
+            )
+        })
+        .collect();
+
+    return format!(
+        "<?xml version=\"1.0\"?><!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+<target version=\"1.0\"><feature name=\"org.voxeon.vxlvm.core\">{}</feature></target>",
+        registers
+    );
+}
@@ -1,26 +1,54 @@
 use vxl_iset::instruction_arguments::Register;
 use vxl_iset::syscall_handler::SyscallHandler;
+use vxlvm::loader::LoadPolicy;
+use vxlvm::stdlib::StandardLibrary;
 use vxlvm::vm::VM;
 
 use std::collections::BTreeMap;
 use std::fs::File;
-use std::io::{self, stdout, Read, Write};
+use std::io::{self, Read, Write};
 use std::time;
 
 pub struct OSHandler {
     files: BTreeMap<u64, File>,
     /// Used to track where we can start searching from for a new id.
     lowest_removed_file_id: Option<u64>,
+    standard_library: StandardLibrary,
+    input: Box<dyn Read>,
+    output: Box<dyn Write>,
+    /// The code passed to `exit`, when it halts the machine instead of exiting the process.
+    exit_code: Option<u64>,
+    exit_process: bool,
 }
 
 impl OSHandler {
-    pub fn new() -> Self {
+    /// Modules loaded by the guest are checked with `policy`.
+    pub fn new(policy: LoadPolicy) -> Self {
+        let mut handler =
+            Self::with_terminal(policy, Box::new(io::stdin()), Box::new(io::stdout()));
+        handler.exit_process = true;
+
+        return handler;
+    }
+
+    /// Uses the reader and writer as the terminal of the guest. Exiting halts the machine rather
+    /// than exiting the process, see `exit_code`.
+    pub fn with_terminal(policy: LoadPolicy, input: Box<dyn Read>, output: Box<dyn Write>) -> Self {
         return Self {
             files: BTreeMap::new(),
             lowest_removed_file_id: None,
+            standard_library: StandardLibrary::with_policy(policy),
+            input,
+            output,
+            exit_code: None,
+            exit_process: false,
         };
     }
 
+    pub fn exit_code(&self) -> Option<u64> {
+        return self.exit_code;
+    }
+
     fn determine_next_id(&mut self) -> Option<u64> {
         if let Some((last, _)) = self.files.iter().next_back() {
             if *last < u64::MAX - 1 {
@@ -88,20 +116,27 @@ impl OSHandler {
 }
 
 impl SyscallHandler<VM> for OSHandler {
-    fn execute_target_specific_call(&mut self, _call: u64, _machine: &mut VM) -> Option<u64> {
-        return None;
+    fn execute_target_specific_call(&mut self, call: u64, machine: &mut VM) -> Option<u64> {
+        return self.standard_library.execute_call(call, machine);
     }
 
     fn exit(&mut self, machine: &mut VM) -> Option<u64> {
-        std::process::exit(
-            (machine.registers().get_value(Register::R0 as u8) % (u32::MAX as u64)) as i32,
-        );
+        let code = machine.registers().get_value(Register::R0 as u8);
+
+        if self.exit_process {
+            std::process::exit((code % (u32::MAX as u64)) as i32);
+        }
+
+        self.exit_code = Some(code);
+        machine.halt();
+
+        return Some(0);
     }
 
     fn write_byte_terminal(&mut self, machine: &mut VM) -> Option<u64> {
         let byte = (machine.registers().get_value(Register::R0 as u8) & 0xff) as u8;
 
-        if stdout().write(&[byte]).is_err() {
+        if self.output.write(&[byte]).is_err() {
             return Some(1);
         } else {
             return Some(0);
@@ -112,7 +147,7 @@ impl SyscallHandler<VM> for OSHandler {
         let ptr = machine.registers().get_value(Register::R0 as u8);
 
         if let Some(string_bytes) = machine.memory().retrieve(&ptr) {
-            if stdout().write(string_bytes).is_err() {
+            if self.output.write(string_bytes).is_err() {
                 return Some(2);
             }
 
@@ -125,7 +160,7 @@ impl SyscallHandler<VM> for OSHandler {
     fn read_byte_terminal(&mut self, _machine: &mut VM) -> Option<u64> {
         let mut byte = [0u8];
 
-        let _ = io::stdin().read(&mut byte);
+        let _ = self.input.read(&mut byte);
 
         return Some(byte[0] as u64);
     }
@@ -134,7 +169,7 @@ impl SyscallHandler<VM> for OSHandler {
         let ptr = machine.registers().get_value(Register::R0 as u8);
 
         if let Some(dest) = machine.memory_mut().retrieve_mutable(&ptr) {
-            if let Ok(n) = io::stdin().read(dest) {
+            if let Ok(n) = self.input.read(dest) {
                 return Some(n as u64);
             } else {
                 return Some(0);
@@ -1,15 +1,79 @@
 mod cli_args;
+mod dap;
 mod file_operations;
+mod gdbserver;
 mod handler;
+mod repl;
 
 use clap::StructOpt;
-use cli_args::CLIArgs;
-use file_operations::execute_file;
+use cli_args::{CLIArgs, Command};
+use dap::run_dap_server;
+use file_operations::{
+    assemble_to_file, cfg_file, disassemble_file, execute_file, inspect_file, link_to_file,
+    lint_file, sign_to_file, verify_file, LoadOptions,
+};
+use gdbserver::run_gdbserver;
+use repl::run_repl;
 
 fn main() {
     let cli_args = CLIArgs::parse();
 
-    match execute_file(&cli_args.input_file) {
+    let result = match cli_args.command {
+        Some(Command::Asm {
+            input_file,
+            output,
+            checksum,
+        }) => assemble_to_file(&input_file, &output, &checksum),
+        Some(Command::Disasm { input_file }) => disassemble_file(&input_file),
+        Some(Command::Cfg { input_file }) => cfg_file(&input_file),
+        Some(Command::Inspect { input_file }) => inspect_file(&input_file),
+        Some(Command::Verify { input_file, json }) => verify_file(&input_file, json),
+        Some(Command::Lint { input_file }) => lint_file(&input_file),
+        Some(Command::Repl {
+            allow_unchecked,
+            trusted_keys,
+        }) => LoadOptions::new(allow_unchecked, trusted_keys.as_deref())
+            .and_then(|options| run_repl(&options)),
+        Some(Command::Dap {
+            allow_unchecked,
+            trusted_keys,
+        }) => LoadOptions::new(allow_unchecked, trusted_keys.as_deref())
+            .and_then(|options| run_dap_server(&options)),
+        Some(Command::Gdbserver {
+            input_file,
+            port,
+            libraries,
+            no_verify,
+            allow_unchecked,
+            trusted_keys,
+        }) => LoadOptions::new(allow_unchecked, trusted_keys.as_deref())
+            .and_then(|options| run_gdbserver(&input_file, &libraries, port, &options, !no_verify)),
+        Some(Command::Sign {
+            input_file,
+            key,
+            output,
+        }) => sign_to_file(&input_file, &key, &output),
+        Some(Command::Link {
+            input_files,
+            output,
+            checksum,
+            allow_unchecked,
+            trusted_keys,
+        }) => LoadOptions::new(allow_unchecked, trusted_keys.as_deref())
+            .and_then(|options| link_to_file(&input_files, &output, &checksum, &options)),
+        // The input file is required when there is no subcommand.
+        None => LoadOptions::new(cli_args.allow_unchecked, cli_args.trusted_keys.as_deref())
+            .and_then(|options| {
+                execute_file(
+                    &cli_args.input_file.unwrap_or_default(),
+                    &cli_args.libraries,
+                    &options,
+                    !cli_args.no_verify,
+                )
+            }),
+    };
+
+    match result {
         Ok(_) => (),
         Err(e) => {
             eprintln!("{}", e);
@@ -0,0 +1,482 @@
+use crate::file_operations::{
+    describe_error, describe_machine_error, load_file, parse_hex, LoadOptions,
+};
+use crate::handler::OSHandler;
+
+use vxl_iset::instruction::Instruction;
+use vxl_iset::instruction_arguments::Register;
+use vxlvm::assembler::parse_instruction;
+use vxlvm::disassembler::register_name;
+use vxlvm::validator::{IncrementalValidator, Validator};
+use vxlvm::vm::VM;
+
+use std::io::{self, BufRead, Write};
+
+/// A line that runs more instructions than this is stopped, in case it never finishes.
+const STEP_LIMIT: u64 = 10_000_000;
+
+const HELP: &str = "Each line is an instruction such as `ldi 5, $r0`, or bytecode in hex \
+starting with 0x, which runs straight away.
+
+:regs         Print every register
+:stack        Print the values on the stack, the top first
+:heap         Print the blocks of heap memory
+:load FILE    Load an xvl file as a module, its functions can then be called
+:reset        Start again with a new machine
+:quit         Exit";
+
+/// A machine that is kept between lines, along with any bytecode that is not yet a whole
+/// instruction.
+struct Repl<'a> {
+    machine: VM,
+    handler: OSHandler,
+    validator: IncrementalValidator,
+    options: &'a LoadOptions,
+}
+
+/// Reads instructions from stdin, running each as it is entered.
+pub fn run_repl(options: &LoadOptions) -> Result<(), String> {
+    let mut repl = Repl::new(options);
+
+    return repl
+        .run(io::stdin().lock(), &mut io::stdout())
+        .map_err(|e| format!("{}", e));
+}
+
+impl<'a> Repl<'a> {
+    fn new(options: &'a LoadOptions) -> Self {
+        return Self {
+            machine: VM::new(Vec::new()),
+            handler: OSHandler::new(options.policy().clone()),
+            validator: IncrementalValidator::new(),
+            options,
+        };
+    }
+
+    fn run<R: BufRead, W: Write>(&mut self, input: R, output: &mut W) -> io::Result<()> {
+        writeln!(output, "Enter an instruction, or :help for commands.")?;
+
+        let mut lines = input.lines();
+
+        loop {
+            write!(output, "> ")?;
+            output.flush()?;
+
+            let line = match lines.next() {
+                Some(line) => line?,
+                None => break,
+            };
+
+            let line = line.trim();
+
+            if line == ":quit" {
+                break;
+            }
+
+            let text = match self.handle_line(line) {
+                Ok(text) => text,
+                Err(text) => text,
+            };
+
+            if !text.is_empty() {
+                writeln!(output, "{}", text)?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Returns the text to print.
+    fn handle_line(&mut self, line: &str) -> Result<String, String> {
+        if let Some(command) = line.strip_prefix(':') {
+            return self.run_command(command);
+        }
+
+        // Blank lines and comments.
+        if line.split(';').next().unwrap_or_default().trim().is_empty() {
+            return Ok(String::new());
+        }
+
+        if self.machine.is_halted() {
+            return Err(String::from(
+                "The machine has halted, use :reset to start again.",
+            ));
+        }
+
+        let mut text = String::new();
+
+        let instructions = match line.strip_prefix("0x") {
+            Some(hex) => {
+                let instructions = self.decode_hex(hex)?;
+
+                if self.validator.buffered() > 0 {
+                    text.push_str(&format!(
+                        "{} byte(s) waiting for the rest of the instruction.\n",
+                        self.validator.buffered()
+                    ));
+                }
+
+                instructions
+            }
+            None => vec![parse_instruction(line).map_err(describe_error)?],
+        };
+
+        text.push_str(&self.run_instructions(instructions)?);
+
+        return Ok(text.trim_end().to_string());
+    }
+
+    fn run_command(&mut self, command: &str) -> Result<String, String> {
+        let (name, argument) = match command.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (command, ""),
+        };
+
+        return match name {
+            "help" => Ok(String::from(HELP)),
+            "regs" => Ok(self.describe_registers()),
+            "stack" => Ok(self.describe_stack()),
+            "heap" => Ok(self.describe_heap()),
+            "load" if !argument.is_empty() => self.load(argument),
+            "reset" => {
+                *self = Self::new(self.options);
+
+                Ok(String::from("Started a new machine."))
+            }
+            _ => Err(format!("Unknown command :{}, see :help.", command)),
+        };
+    }
+
+    /// Adds the bytes to those already received, returning any instructions that are complete.
+    fn decode_hex(&mut self, hex: &str) -> Result<Vec<Instruction>, String> {
+        let digits: String = hex.chars().filter(|c| !c.is_whitespace()).collect();
+        let bytes = parse_hex(&digits).ok_or_else(|| String::from("Invalid hex bytes."))?;
+
+        self.validator.append_bytes(bytes);
+
+        return self.validator.take_instructions().map_err(describe_error);
+    }
+
+    /// Appends the instructions and runs until the machine passes the last one, then describes
+    /// the registers that changed.
+    fn run_instructions(&mut self, instructions: Vec<Instruction>) -> Result<String, String> {
+        if instructions.is_empty() {
+            return Ok(String::new());
+        }
+
+        let before = self.register_values();
+
+        self.machine
+            .append_instructions(instructions)
+            .map_err(describe_error)?;
+
+        let result = self.run_to_end();
+        let mut lines = Vec::new();
+
+        for (register, old) in before {
+            let value = self.machine.registers().get_value(register as u8);
+
+            if value != old {
+                lines.push(describe_register(register, value));
+            }
+        }
+
+        if let Err(description) = result {
+            // Skip whatever is left so that the next line starts with its own instructions.
+            self.machine.set_ip(self.machine.instructions().len());
+            lines.push(description);
+        }
+
+        if self.machine.is_halted() {
+            lines.push(String::from(
+                "The machine has halted, use :reset to start again.",
+            ));
+        }
+
+        return Ok(lines.join("\n"));
+    }
+
+    fn run_to_end(&mut self) -> Result<(), String> {
+        for _ in 0..STEP_LIMIT {
+            if self.machine.ip() >= self.machine.instructions().len() || self.machine.is_halted() {
+                return Ok(());
+            }
+
+            self.machine
+                .run_next(&mut self.handler)
+                .map_err(|e| describe_machine_error(&self.machine, e))?;
+        }
+
+        return Err(format!("Stopped after {} instructions.", STEP_LIMIT));
+    }
+
+    /// Every register except `rip`, which the machine doesn't use.
+    fn register_values(&self) -> Vec<(Register, u64)> {
+        return (Register::RFP as u8..16)
+            .map(Register::from_bits)
+            .map(|register| (register, self.machine.registers().get_value(register as u8)))
+            .collect();
+    }
+
+    fn describe_registers(&self) -> String {
+        let lines: Vec<String> = self
+            .register_values()
+            .into_iter()
+            .map(|(register, value)| describe_register(register, value))
+            .collect();
+
+        return lines.join("\n");
+    }
+
+    /// `rfp` is the number of bytes on the stack.
+    fn describe_stack(&self) -> String {
+        let top = self.machine.registers().get_value(Register::RFP as u8);
+        let mut lines = Vec::new();
+
+        for end in (8..=top).rev().step_by(8) {
+            match self.machine.stack().get_top_u64(end) {
+                Some(value) => {
+                    lines.push(format!("{:>8}: {} ({:#x})", end - 8, value as i64, value))
+                }
+                None => lines.push(format!("{:>8}: beyond the end of the stack", end - 8)),
+            }
+        }
+
+        if lines.is_empty() {
+            return String::from("The stack is empty.");
+        }
+
+        return lines.join("\n");
+    }
+
+    fn describe_heap(&self) -> String {
+        let memory = self.machine.memory();
+        let mut lines = Vec::new();
+
+        for address in memory.addresses() {
+            let length = memory.retrieve(&address).map_or(0, |block| block.len());
+            let read_only = if memory.is_read_only(&address) {
+                ", read only"
+            } else {
+                ""
+            };
+
+            lines.push(format!("{:#x}: {} bytes{}", address, length, read_only));
+        }
+
+        if lines.is_empty() {
+            return String::from("Nothing is allocated.");
+        }
+
+        return lines.join("\n");
+    }
+
+    /// Loads a module after the instructions entered so far, without running it. The module is
+    /// verified by `VM::load_module` as it is for the `LOAD_MODULE` system call.
+    fn load(&mut self, path: &str) -> Result<String, String> {
+        let module = load_file(path, self.options)?;
+        let handle = self.machine.load_module(&module).map_err(describe_error)?;
+
+        self.machine.set_ip(self.machine.instructions().len());
+
+        let mut lines = vec![format!("Loaded {} as module {}.", path, handle)];
+
+        for export in module.link_table().exports() {
+            if let Some(index) = self.machine.resolve_export(handle, export.name()) {
+                lines.push(format!("    {} at {}", export.name(), index));
+            }
+        }
+
+        return Ok(lines.join("\n"));
+    }
+}
+
+fn describe_register(register: Register, value: u64) -> String {
+    return format!(
+        "{} = {}",
+        register_name(register),
+        register_value(register, value)
+    );
+}
+
+/// Formats a value as a signed integer and in hex, or the flags set in `rfl`.
+pub fn register_value(register: Register, value: u64) -> String {
+    if register == Register::RFL {
+        let flags: Vec<&str> = [(0b001, "equal"), (0b010, "less"), (0b100, "greater")]
+            .iter()
+            .filter(|(mask, _)| value & mask != 0)
+            .map(|(_, name)| *name)
+            .collect();
+
+        return format!(
+            "{:#05b} ({})",
+            value,
+            if flags.is_empty() {
+                String::from("no comparison")
+            } else {
+                flags.join(", ")
+            }
+        );
+    }
+
+    return format!("{} ({:#x})", value as i64, value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use vxl_iset::instruction_arguments::Immediate;
+    use vxlvm::error::{AssemblyError, AssemblyErrorKind, LinkError, ProgramError, VMError};
+    use vxlvm::linker::Module;
+    use vxlvm::loader::ChecksumAlgorithm;
+    use vxlvm::writer::encode_instruction;
+
+    use std::io::Cursor;
+
+    /// Runs the lines in a new REPL, returning what was printed for each of them.
+    fn run_script(lines: &[&str]) -> Vec<String> {
+        let options = LoadOptions::new(false, None).unwrap();
+        let mut output = Vec::new();
+
+        Repl::new(&options)
+            .run(Cursor::new(lines.join("\n")), &mut output)
+            .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let mut responses: Vec<String> = output
+            .split("> ")
+            .skip(1)
+            .map(|response| response.trim_end().to_string())
+            .collect();
+
+        // The prompt for the line that was never entered.
+        assert_eq!(responses.pop().as_deref(), Some(""));
+
+        return responses;
+    }
+
+    #[test]
+    fn test_changed_registers() {
+        let responses = run_script(&["ldi 5, $r0", "addi $r1, $r0, $r0", "cmp $r0, $r1", "nop"]);
+
+        assert_eq!(
+            responses,
+            vec!["$r0 = 5 (0x5)", "$r1 = 10 (0xa)", "$rfl = 0b010 (less)", "",]
+        );
+    }
+
+    #[test]
+    fn test_hex_split_across_lines() {
+        let mut bytes = Vec::new();
+        encode_instruction(
+            Instruction::Ldi(Immediate::from(7u64), Register::R2),
+            &mut bytes,
+        );
+        let hex = hex::encode(&bytes);
+
+        let first = format!("0x{}", &hex[..4]);
+        let second = format!("0x{}", &hex[4..]);
+        let responses = run_script(&[&first, &second]);
+
+        assert_eq!(
+            responses,
+            vec![
+                "2 byte(s) waiting for the rest of the instruction.",
+                "$r2 = 7 (0x7)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_error_then_continue() {
+        let responses = run_script(&["bogus $r0", "pop $r0", "ldi 1, $r1", ":nothing"]);
+
+        assert_eq!(
+            responses[0],
+            describe_error(AssemblyError::new(1, 1, AssemblyErrorKind::UnknownMnemonic))
+        );
+        assert_eq!(
+            responses[1],
+            format!(
+                "{}\n    at instruction 0",
+                describe_error(VMError::AccessBeyondStackBounds)
+            )
+        );
+        assert_eq!(responses[2], "$r1 = 1 (0x1)");
+        assert_eq!(responses[3], "Unknown command :nothing, see :help.");
+    }
+
+    #[test]
+    fn test_reset() {
+        let responses = run_script(&["ldi 3, $r4", "halt", "ldi 1, $r0", ":reset", ":regs"]);
+
+        assert_eq!(responses[0], "$r4 = 3 (0x3)");
+        assert_eq!(
+            responses[1],
+            "The machine has halted, use :reset to start again."
+        );
+        assert_eq!(
+            responses[2],
+            "The machine has halted, use :reset to start again."
+        );
+        assert_eq!(responses[3], "Started a new machine.");
+        assert!(responses[4].contains("$r4 = 0 (0x0)"));
+    }
+
+    /// Writes a module to a file in the temporary directory, returning its path.
+    fn write_module(module: &Module, name: &str) -> String {
+        let path =
+            std::env::temp_dir().join(format!("vxlvm-repl-{}-{}.xvl", name, std::process::id()));
+        std::fs::write(
+            &path,
+            module.to_writer(ChecksumAlgorithm::Sha3_224).to_bytes(),
+        )
+        .unwrap();
+
+        return path.to_string_lossy().into_owned();
+    }
+
+    #[test]
+    fn test_load() {
+        // square:
+        // muli $rou, $r0, $r0
+        // ret
+        let mut module = Module::new(
+            vec![
+                Instruction::Muli(Register::ROU, Register::R0, Register::R0),
+                Instruction::Ret,
+            ],
+            0,
+        );
+        module
+            .link_table_mut()
+            .add_export(String::from("square"), 0);
+
+        // The exported nop can continue past the end of the module.
+        // ret
+        // nop
+        let mut invalid = Module::new(vec![Instruction::Ret, Instruction::Nop], 0);
+        invalid.link_table_mut().add_export(String::from("nop"), 1);
+
+        let path = write_module(&module, "square");
+        let invalid_path = write_module(&invalid, "invalid");
+
+        let load = format!(":load {}", path);
+        let load_invalid = format!(":load {}", invalid_path);
+        let responses = run_script(&["ldi 4, $r0", &load, &load_invalid, "call 1"]);
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&invalid_path).unwrap();
+
+        assert_eq!(responses[0], "$r0 = 4 (0x4)");
+        assert_eq!(
+            responses[1],
+            format!("Loaded {} as module 1.\n    square at 1", path)
+        );
+        assert_eq!(
+            responses[2],
+            describe_error(LinkError::InvalidProgram(2, ProgramError::FallsOffEnd(4)))
+        );
+        assert_eq!(responses[3], "$rou = 16 (0x10)");
+    }
+}
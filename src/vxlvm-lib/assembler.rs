@@ -0,0 +1,848 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::disassembler::{mnemonic, register_name};
+use crate::effects::branch_target;
+use crate::error::{AssemblyError, AssemblyErrorKind};
+use crate::linker::Module;
+use crate::symbols::SymbolTable;
+use vxl_iset::instruction::Instruction;
+use vxl_iset::instruction_arguments::{Address, Immediate, Register};
+
+/*
+Assembly source has a statement on each line, anything after a `;` is a comment.
+
+name:               A label for the next instruction, or the data directive that follows
+ldi 3, $r0          An instruction, written as the disassembler writes it
+jmp name            A label can be used in place of any integer operand
+
+Directives
+
+.entry name         The program starts at the label rather than the first instruction
+.export name        The label can be linked against by other modules
+.import name        A jump or call may target a label from another module
+.byte 1, 0xff       Bytes of data
+.u64 1, -2          Little endian u64s of data
+.f64 1.5            Little endian f64s of data
+.string "text\n"    UTF-8 data, with the escapes \n, \t, \0, \\ and \"
+.zero 16            Zeroed bytes of data, at most `MAX_ZERO_LENGTH`
+
+Data directives that follow one another make up a data section, a label before them holds the
+index of the section. Code labels become functions in the symbols, unless they start with a `.`.
+*/
+
+/// The most bytes a single `.zero` directive can add.
+pub const MAX_ZERO_LENGTH: u64 = 1 << 24;
+
+/// Part of a line and where it starts.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+    column: usize,
+}
+
+/// An instruction waiting for every label to be known.
+struct Statement<'a> {
+    mnemonic: Token<'a>,
+    operands: Vec<Token<'a>>,
+}
+
+/// The values of labels, and the names imported from other modules.
+#[derive(Default)]
+struct Labels<'a> {
+    values: BTreeMap<&'a str, u64>,
+    imports: Vec<&'a str>,
+}
+
+/// Everything found by the first pass over the source.
+#[derive(Default)]
+struct Assembly<'a> {
+    labels: Labels<'a>,
+    /// Labels waiting for the next instruction or data section.
+    pending_labels: Vec<Token<'a>>,
+    /// The labels that name functions, with their first instruction.
+    functions: Vec<(u64, &'a str)>,
+    statements: Vec<Statement<'a>>,
+    data_sections: Vec<Vec<u8>>,
+    /// Whether the last statement was a data directive, which the next one adds to.
+    in_data: bool,
+    entry: Option<Token<'a>>,
+    exports: Vec<Token<'a>>,
+    /// Refuse every directive, for source that must assemble to nothing but instructions.
+    instructions_only: bool,
+}
+
+impl Token<'_> {
+    fn error(&self, kind: AssemblyErrorKind) -> AssemblyError {
+        return AssemblyError::new(self.line, self.column, kind);
+    }
+}
+
+impl<'a> Labels<'a> {
+    /// The value of an integer or a label. Imported labels are 0 until the module is linked and
+    /// are only allowed when `imports` is true.
+    fn value(
+        &self,
+        token: Token<'_>,
+        invalid: AssemblyErrorKind,
+        imports: bool,
+    ) -> Result<u64, AssemblyError> {
+        if let Some(value) = parse_integer(token.text) {
+            return Ok(value);
+        }
+
+        if let Some(value) = self.values.get(token.text) {
+            return Ok(*value);
+        }
+
+        if imports && self.imports.contains(&token.text) {
+            return Ok(0);
+        }
+
+        if is_identifier(token.text) {
+            return Err(token.error(AssemblyErrorKind::UnknownLabel));
+        }
+
+        return Err(token.error(invalid));
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        return self.values.contains_key(name) || self.imports.contains(&name);
+    }
+}
+
+impl<'a> Assembly<'a> {
+    fn add_line(&mut self, code: &'a str, line: usize) -> Result<(), AssemblyError> {
+        let mut offset = 0;
+
+        while let Some((label, end)) = split_label(code, offset, line) {
+            if self.labels.contains(label.text)
+                || self.pending_labels.iter().any(|l| l.text == label.text)
+            {
+                return Err(label.error(AssemblyErrorKind::DuplicateLabel));
+            }
+
+            self.pending_labels.push(label);
+            self.in_data = false;
+            offset = end;
+        }
+
+        if code[offset..].trim().is_empty() {
+            return Ok(());
+        }
+
+        let (word, end) = split_word(code, offset, line);
+
+        if word.text.starts_with('.') {
+            return self.add_directive(word, code, end);
+        }
+
+        self.bind_labels(self.statements.len() as u64, true);
+        self.in_data = false;
+        self.statements.push(Statement {
+            mnemonic: word,
+            operands: split_operands(code, end, line),
+        });
+
+        return Ok(());
+    }
+
+    /// Sets the value of each pending label.
+    fn bind_labels(&mut self, value: u64, code: bool) {
+        for label in self.pending_labels.drain(..) {
+            self.labels.values.insert(label.text, value);
+
+            if code && !label.text.starts_with('.') {
+                self.functions.push((value, label.text));
+            }
+        }
+    }
+
+    fn add_directive(
+        &mut self,
+        directive: Token<'a>,
+        code: &'a str,
+        end: usize,
+    ) -> Result<(), AssemblyError> {
+        if self.instructions_only {
+            return Err(directive.error(AssemblyErrorKind::DirectiveNotAllowed));
+        }
+
+        if directive.text == ".string" {
+            let bytes = parse_string(code, end, directive.line)?;
+            self.add_data(bytes);
+
+            return Ok(());
+        }
+
+        let operands = split_operands(code, end, directive.line);
+        let single = || match operands.as_slice() {
+            [operand] => Ok(*operand),
+            _ => Err(directive.error(AssemblyErrorKind::WrongOperandCount(1))),
+        };
+
+        match directive.text {
+            ".entry" => self.entry = Some(single()?),
+            ".export" => self.exports.extend(operands.iter().copied()),
+            ".import" => {
+                for operand in &operands {
+                    if !is_identifier(operand.text) {
+                        return Err(operand.error(AssemblyErrorKind::InvalidData));
+                    }
+
+                    if self.labels.contains(operand.text)
+                        || self.pending_labels.iter().any(|l| l.text == operand.text)
+                    {
+                        return Err(operand.error(AssemblyErrorKind::DuplicateLabel));
+                    }
+
+                    self.labels.imports.push(operand.text);
+                }
+            }
+            ".byte" => {
+                let mut bytes = Vec::new();
+
+                for operand in &operands {
+                    let value = parse_integer(operand.text)
+                        .filter(|value| (-128..=255).contains(&(*value as i64)))
+                        .ok_or_else(|| operand.error(AssemblyErrorKind::InvalidData))?;
+
+                    bytes.push(value as u8);
+                }
+
+                self.add_data(bytes);
+            }
+            ".u64" | ".f64" => {
+                let mut bytes = Vec::new();
+
+                for operand in &operands {
+                    let value = if directive.text == ".f64" {
+                        operand.text.parse::<f64>().ok().map(f64::to_bits)
+                    } else {
+                        parse_integer(operand.text)
+                    };
+
+                    let value =
+                        value.ok_or_else(|| operand.error(AssemblyErrorKind::InvalidData))?;
+                    bytes.extend_from_slice(&value.to_le_bytes());
+                }
+
+                self.add_data(bytes);
+            }
+            ".zero" => {
+                let operand = single()?;
+                let length = parse_integer(operand.text)
+                    .filter(|length| !operand.text.starts_with('-') && *length <= MAX_ZERO_LENGTH)
+                    .ok_or_else(|| operand.error(AssemblyErrorKind::InvalidData))?;
+
+                self.add_data(vec![0; length as usize]);
+            }
+            _ => return Err(directive.error(AssemblyErrorKind::UnknownDirective)),
+        }
+
+        return Ok(());
+    }
+
+    /// Adds to the current data section, or starts a new one.
+    fn add_data(&mut self, bytes: Vec<u8>) {
+        if !self.in_data {
+            self.data_sections.push(Vec::new());
+            self.bind_labels(self.data_sections.len() as u64 - 1, false);
+            self.in_data = true;
+        }
+
+        self.data_sections.last_mut().unwrap().extend(bytes);
+    }
+
+    fn assemble(mut self, file_name: &str, source: &'a str) -> Result<Module, AssemblyError> {
+        for (number, text) in source.lines().enumerate() {
+            self.add_line(strip_comment(text), number + 1)?;
+        }
+
+        return self.finish(file_name);
+    }
+
+    /// Resolves the labels of every instruction, recording the line of each against `file_name`
+    /// in the symbols.
+    fn finish(mut self, file_name: &str) -> Result<Module, AssemblyError> {
+        // Labels at the end refer to the index after the last instruction.
+        self.bind_labels(self.statements.len() as u64, true);
+
+        let mut symbols = SymbolTable::new();
+        let file = symbols.add_file(String::from(file_name));
+        let mut instructions = Vec::new();
+        let mut imports = Vec::new();
+
+        for (index, statement) in self.statements.iter().enumerate() {
+            let instruction =
+                build_instruction(statement.mnemonic, &statement.operands, &self.labels)?;
+
+            // The linker only fills in the target of a jump or call, which is its only operand.
+            let is_branch = branch_target(&instruction).is_some() && statement.operands.len() == 1;
+
+            for operand in &statement.operands {
+                if self.labels.imports.contains(&operand.text) {
+                    if !is_branch {
+                        return Err(operand.error(AssemblyErrorKind::MisplacedImport));
+                    }
+
+                    imports.push((operand.text, index as u64));
+                }
+            }
+
+            instructions.push(instruction);
+
+            symbols.add_line(index as u64, file, statement.mnemonic.line as u64);
+        }
+
+        for (start, name) in &self.functions {
+            symbols.add_function(*start, String::from(*name));
+        }
+
+        let entry = match self.entry {
+            Some(token) => self
+                .labels
+                .value(token, AssemblyErrorKind::InvalidAddress, false)?,
+            None => 0,
+        };
+
+        let mut module = Module::new(instructions, entry);
+
+        for export in &self.exports {
+            let index = self
+                .labels
+                .values
+                .get(export.text)
+                .ok_or_else(|| export.error(AssemblyErrorKind::UnknownLabel))?;
+
+            module
+                .link_table_mut()
+                .add_export(String::from(export.text), *index);
+        }
+
+        for (name, site) in imports {
+            module.link_table_mut().add_import(String::from(name), site);
+        }
+
+        for section in self.data_sections {
+            module.add_data_section(section);
+        }
+
+        module.set_symbols(symbols);
+
+        return Ok(module);
+    }
+}
+
+/// Assembles source, described at the top of this file, into a module that can be run, linked
+/// or written with `Module::to_writer`.
+///
+/// The line of each instruction is recorded against `file_name` in the symbols.
+pub fn assemble(file_name: &str, source: &str) -> Result<Module, AssemblyError> {
+    return Assembly::default().assemble(file_name, source);
+}
+
+/// Assembles source that has no directives, returning only its instructions. Data, the entry
+/// point, imports and exports can't be returned, so any directive is an error.
+pub fn assemble_instructions(source: &str) -> Result<Vec<Instruction>, AssemblyError> {
+    let assembly = Assembly {
+        instructions_only: true,
+        ..Assembly::default()
+    };
+
+    return Ok(assembly.assemble("", source)?.instructions().to_vec());
+}
+
+/// Parses one instruction written as the disassembler writes it, such as `ldi 3, $r0`.
+///
+/// Immediates are written first, then addresses and then registers. Integers may be negative,
+/// hexadecimal with `0x` or binary with `0b`, the immediate of `ldf` is a float. Anything after a
+/// `;` is a comment.
+pub fn parse_instruction(text: &str) -> Result<Instruction, AssemblyError> {
+    let code = strip_comment(text);
+    let (mnemonic, end) = split_word(code, 0, 1);
+    let operands = split_operands(code, end, 1);
+
+    return build_instruction(mnemonic, &operands, &Labels::default());
+}
+
+/// Removes a comment, which may not start inside a string.
+fn strip_comment(text: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            ';' if !in_string => return &text[..i],
+            _ => (),
+        }
+    }
+
+    return text;
+}
+
+/// A label such as `loop:` from `start`, and the offset after its colon.
+fn split_label(code: &str, start: usize, line: usize) -> Option<(Token<'_>, usize)> {
+    let rest = &code[start..];
+    let label_start = start + rest.len() - rest.trim_start().len();
+    let length = code[label_start..].find(':')?;
+    let text = &code[label_start..label_start + length];
+
+    if !is_identifier(text) {
+        return None;
+    }
+
+    let token = Token {
+        text,
+        line,
+        column: column(code, label_start),
+    };
+
+    return Some((token, label_start + length + 1));
+}
+
+/// The first word from `start`, and the offset after it.
+fn split_word(code: &str, start: usize, line: usize) -> (Token<'_>, usize) {
+    let rest = &code[start..];
+    let word_start = start + rest.len() - rest.trim_start().len();
+    let length = code[word_start..]
+        .find(char::is_whitespace)
+        .unwrap_or(code.len() - word_start);
+
+    let token = Token {
+        text: &code[word_start..word_start + length],
+        line,
+        column: column(code, word_start),
+    };
+
+    return (token, word_start + length);
+}
+
+/// The comma separated operands from `start`.
+fn split_operands(code: &str, start: usize, line: usize) -> Vec<Token<'_>> {
+    if code[start..].trim().is_empty() {
+        return Vec::new();
+    }
+
+    let mut operands = Vec::new();
+    let mut offset = start;
+
+    for part in code[start..].split(',') {
+        let leading = part.len() - part.trim_start().len();
+
+        operands.push(Token {
+            text: part.trim(),
+            line,
+            column: column(code, offset + leading),
+        });
+
+        offset += part.len() + 1;
+    }
+
+    return operands;
+}
+
+/// The column of a byte offset, counting characters from 1.
+fn column(code: &str, offset: usize) -> usize {
+    return code[..offset].chars().count() + 1;
+}
+
+/// Labels start with a letter, `_` or `.`, followed by letters, digits, `_` or `.`.
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+
+    return chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+}
+
+/// Parses the quoted string from `start` to the end of the line.
+fn parse_string(code: &str, start: usize, line: usize) -> Result<Vec<u8>, AssemblyError> {
+    let rest = &code[start..];
+    let quote = start + rest.len() - rest.trim_start().len();
+    let error =
+        |offset| AssemblyError::new(line, column(code, offset), AssemblyErrorKind::InvalidData);
+
+    if !code[quote..].starts_with('"') {
+        return Err(error(quote));
+    }
+
+    let mut text = String::new();
+    let mut chars = code[quote + 1..].char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => {
+                let rest = &code[quote + i + 2..];
+
+                if !rest.trim().is_empty() {
+                    return Err(error(code.len() - rest.trim_start().len()));
+                }
+
+                return Ok(text.into_bytes());
+            }
+            '\\' => {
+                let escaped = match chars.next() {
+                    Some((_, 'n')) => '\n',
+                    Some((_, 't')) => '\t',
+                    Some((_, '0')) => '\0',
+                    Some((_, '\\')) => '\\',
+                    Some((_, '"')) => '"',
+                    _ => return Err(error(quote + i + 1)),
+                };
+
+                text.push(escaped);
+            }
+            c => text.push(c),
+        }
+    }
+
+    // The string has no closing quote.
+    return Err(error(quote));
+}
+
+fn build_instruction(
+    mnemonic: Token<'_>,
+    operands: &[Token<'_>],
+    labels: &Labels<'_>,
+) -> Result<Instruction, AssemblyError> {
+    let opcode = opcode_for(mnemonic.text)
+        .ok_or_else(|| mnemonic.error(AssemblyErrorKind::UnknownMnemonic))?;
+
+    // The counts are known for every opcode returned by `opcode_for`.
+    let register_count = Instruction::register_count(opcode).unwrap();
+    let address_count = Instruction::address_count(opcode).unwrap();
+    let immediate_count = Instruction::immediate_count(opcode).unwrap();
+    let operand_count = register_count + address_count + immediate_count;
+
+    if operands.len() != operand_count {
+        return Err(mnemonic.error(AssemblyErrorKind::WrongOperandCount(operand_count)));
+    }
+
+    let (immediate_tokens, rest) = operands.split_at(immediate_count);
+    let (address_tokens, register_tokens) = rest.split_at(address_count);
+
+    let mut immediates = Vec::new();
+
+    for token in immediate_tokens {
+        let value = if opcode == 0x04 {
+            token
+                .text
+                .parse::<f64>()
+                .map(f64::to_bits)
+                .map_err(|_| token.error(AssemblyErrorKind::InvalidImmediate))?
+        } else {
+            labels.value(*token, AssemblyErrorKind::InvalidImmediate, false)?
+        };
+
+        immediates.push(Immediate::from(value));
+    }
+
+    let mut addresses = Vec::new();
+
+    for token in address_tokens {
+        if token.text.starts_with('-') {
+            return Err(token.error(AssemblyErrorKind::InvalidAddress));
+        }
+
+        let value = labels.value(*token, AssemblyErrorKind::InvalidAddress, true)?;
+        addresses.push(Address::from(value));
+    }
+
+    let mut registers = Vec::new();
+
+    for token in register_tokens {
+        registers.push(
+            parse_register(token.text)
+                .ok_or_else(|| token.error(AssemblyErrorKind::InvalidRegister))?,
+        );
+    }
+
+    return Ok(Instruction::new(opcode, registers, addresses, immediates)
+        .expect("The operands match the counts for the opcode."));
+}
+
+/// Finds the opcode of a mnemonic, ignoring case.
+fn opcode_for(name: &str) -> Option<u8> {
+    let name = name.to_lowercase();
+
+    return (0..=u8::MAX).find(|opcode| {
+        example_instruction(*opcode).is_some_and(|instruction| mnemonic(instruction) == name)
+    });
+}
+
+/// An instruction with the opcode and zeroed operands.
+fn example_instruction(opcode: u8) -> Option<Instruction> {
+    let registers = vec![Register::R0; Instruction::register_count(opcode)?];
+    let addresses = vec![Address::from(0u64); Instruction::address_count(opcode)?];
+    let immediates = vec![Immediate::from(0u64); Instruction::immediate_count(opcode)?];
+
+    return Instruction::new(opcode, registers, addresses, immediates);
+}
+
+/// Parses a decimal, `0x` hexadecimal or `0b` binary integer. Negative values are stored as
+/// two's complement.
+fn parse_integer(text: &str) -> Option<u64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+
+    let magnitude = if let Some(hex) = digits.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        u64::from_str_radix(binary, 2).ok()?
+    } else {
+        digits.parse::<u64>().ok()?
+    };
+
+    if !negative {
+        return Some(magnitude);
+    }
+
+    if magnitude > 1 << 63 {
+        return None;
+    }
+
+    return Some(magnitude.wrapping_neg());
+}
+
+/// Parses a register name such as `$r0` or `$rfp`, ignoring case.
+fn parse_register(text: &str) -> Option<Register> {
+    let text = text.to_lowercase();
+
+    return (0..16)
+        .map(Register::from_bits)
+        .find(|register| register_name(*register) == text);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassembler::format_instruction;
+
+    #[test]
+    fn test_disassembly_round_trip() {
+        let instructions = vec![
+            Instruction::Nop,
+            Instruction::Syscall(Immediate::from(0x105u64)),
+            Instruction::Ldi(Immediate::from(-2i64), Register::R9),
+            Instruction::Ldf(Immediate::from(63.24f64), Register::R0),
+            Instruction::Mov(Register::RFP, Register::RSP),
+            Instruction::Iseti(Immediate::from(3u64), Register::R1, Register::R2),
+            Instruction::Copyi(
+                Immediate::from(1u64),
+                Immediate::from(2u64),
+                Immediate::from(3u64),
+                Register::R3,
+                Register::R4,
+            ),
+            Instruction::Swpa(Address::from(1u64), Address::from(2u64)),
+            Instruction::Jmp(Address::from(7u64)),
+            Instruction::Ret,
+        ];
+
+        for instruction in instructions {
+            let text = format_instruction(instruction, 0, None);
+
+            assert_eq!(parse_instruction(&text), Ok(instruction), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_syntax() {
+        assert_eq!(
+            parse_instruction("  LDI 0x10,$R1 ; sixteen"),
+            Ok(Instruction::Ldi(Immediate::from(16u64), Register::R1))
+        );
+        assert_eq!(
+            parse_instruction("ldb 0b101, $rou"),
+            Ok(Instruction::Ldb(Immediate::from(5u64), Register::ROU))
+        );
+        assert_eq!(
+            parse_instruction("ldf 2, $r0"),
+            Ok(Instruction::Ldf(Immediate::from(2.0f64), Register::R0))
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |column, kind| Err(AssemblyError::new(1, column, kind));
+
+        assert_eq!(
+            parse_instruction("  load 1, $r0"),
+            error(3, AssemblyErrorKind::UnknownMnemonic)
+        );
+        assert_eq!(
+            parse_instruction("mov $r0"),
+            error(1, AssemblyErrorKind::WrongOperandCount(2))
+        );
+        assert_eq!(
+            parse_instruction("ldi 1x, $r0"),
+            error(5, AssemblyErrorKind::InvalidImmediate)
+        );
+        assert_eq!(
+            parse_instruction("jmp -1"),
+            error(5, AssemblyErrorKind::InvalidAddress)
+        );
+        assert_eq!(
+            parse_instruction("mov $r0,  $r10"),
+            error(11, AssemblyErrorKind::InvalidRegister)
+        );
+        assert_eq!(
+            parse_instruction(""),
+            error(1, AssemblyErrorKind::UnknownMnemonic)
+        );
+        assert_eq!(
+            parse_instruction("jmp start"),
+            error(5, AssemblyErrorKind::UnknownLabel)
+        );
+    }
+
+    #[test]
+    fn test_labels_and_data() {
+        let source = "\
+            .entry main
+            .export square
+            .import print
+
+            greeting: .string \"Hi; there\\n\"
+                      .byte 1, -1
+            table:    .u64 0x10
+                      .f64 0.5
+            buffer:
+                .zero 3
+
+            square: mulu $rou, $r0, $r0
+                    ret
+            main:   ldi table, $r0  ; the index of the section
+            .loop:  call square
+                    call print
+                    jmp .loop
+            end:
+        ";
+
+        let module = assemble("square.vsm", source).unwrap();
+
+        assert_eq!(module.entry(), 2);
+        assert_eq!(
+            module.instructions(),
+            &[
+                Instruction::Mulu(Register::ROU, Register::R0, Register::R0),
+                Instruction::Ret,
+                Instruction::Ldi(Immediate::from(1u64), Register::R0),
+                Instruction::Call(Address::from(0u64)),
+                Instruction::Call(Address::from(0u64)),
+                Instruction::Jmp(Address::from(3u64)),
+            ]
+        );
+
+        let mut table = 0x10u64.to_le_bytes().to_vec();
+        table.extend_from_slice(&0.5f64.to_bits().to_le_bytes());
+
+        assert_eq!(
+            module.data_sections(),
+            &[b"Hi; there\n\x01\xff".to_vec(), table, vec![0; 3]]
+        );
+
+        let exports = module.link_table().exports();
+        assert_eq!((exports[0].name(), exports[0].index()), ("square", 0));
+
+        let imports = module.link_table().imports();
+        assert_eq!(
+            (imports[0].name(), imports[0].sites()),
+            ("print", &[4u64][..])
+        );
+
+        let symbols = module.symbols().unwrap();
+        let names: Vec<(u64, &str)> = symbols
+            .functions()
+            .iter()
+            .map(|function| (function.start(), function.name()))
+            .collect();
+
+        assert_eq!(names, vec![(0, "square"), (2, "main"), (6, "end")]);
+        assert_eq!(symbols.location_at(3).unwrap().line, 15);
+        assert_eq!(symbols.location_at(3).unwrap().file, "square.vsm");
+    }
+
+    #[test]
+    fn test_source_errors() {
+        let error = |source, line, column, kind| {
+            assert_eq!(
+                assemble("", source).err(),
+                Some(AssemblyError::new(line, column, kind)),
+                "{}",
+                source
+            );
+        };
+
+        error("a:\nnop\na: nop", 3, 1, AssemblyErrorKind::DuplicateLabel);
+        error(".import a\na: nop", 2, 1, AssemblyErrorKind::DuplicateLabel);
+        error("nop\n  .bytes 1", 2, 3, AssemblyErrorKind::UnknownDirective);
+        error(".byte 1, 256", 1, 10, AssemblyErrorKind::InvalidData);
+        error(".string \"a\" b", 1, 13, AssemblyErrorKind::InvalidData);
+        error(".string \"a\\q\"", 1, 11, AssemblyErrorKind::InvalidData);
+        error(".string \"a", 1, 9, AssemblyErrorKind::InvalidData);
+        error(".entry a, b", 1, 1, AssemblyErrorKind::WrongOperandCount(1));
+        error(".entry main\nnop", 1, 8, AssemblyErrorKind::UnknownLabel);
+        error(".zero 16777217", 1, 7, AssemblyErrorKind::InvalidData);
+        error(".zero -1", 1, 7, AssemblyErrorKind::InvalidData);
+        error("nop\n\njmp $r0", 3, 5, AssemblyErrorKind::InvalidAddress);
+        error(
+            ".import f\nldi f, $r0",
+            2,
+            5,
+            AssemblyErrorKind::UnknownLabel,
+        );
+        error(
+            ".import f\nswpa f, 2",
+            2,
+            6,
+            AssemblyErrorKind::MisplacedImport,
+        );
+        error(
+            ".import f\n.import g\nswpa 2, g",
+            3,
+            9,
+            AssemblyErrorKind::MisplacedImport,
+        );
+    }
+
+    #[test]
+    fn test_instructions_only() {
+        assert_eq!(
+            assemble_instructions("a: ldi 1, $r0\njmp a"),
+            Ok(vec![
+                Instruction::Ldi(Immediate::from(1u64), Register::R0),
+                Instruction::Jmp(Address::from(0u64)),
+            ])
+        );
+
+        for source in [
+            "nop\n.byte 1",
+            "nop\n.zero 4",
+            "nop\n.entry a\na: nop",
+            "nop\n.export a\na: nop",
+            "nop\n.import f",
+        ] {
+            assert_eq!(
+                assemble_instructions(source),
+                Err(AssemblyError::new(
+                    2,
+                    1,
+                    AssemblyErrorKind::DirectiveNotAllowed
+                )),
+                "{}",
+                source
+            );
+        }
+    }
+}
@@ -0,0 +1,485 @@
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::ops::Range;
+
+use crate::disassembler::{format_instruction, label};
+use crate::effects::branch_target;
+use crate::symbols::SymbolTable;
+use vxl_iset::instruction::Instruction;
+
+/// The basic blocks of a program and the ways control can move between them.
+///
+/// Jumps and calls to instructions outside the program are left without an edge, see
+/// `validator::verify_program` for reporting them.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ControlFlowGraph {
+    entry: usize,
+    /// Sorted by their first instruction, together they cover every instruction.
+    blocks: Vec<BasicBlock>,
+    /// Sorted by the block they leave.
+    edges: Vec<Edge>,
+    /// Sorted by their first instruction.
+    functions: Vec<Function>,
+}
+
+/// A run of instructions that is only entered at its first instruction and only left after its
+/// last.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct BasicBlock {
+    start: usize,
+    end: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub enum EdgeKind {
+    /// Continuing with the next instruction, including after a call returns.
+    FallThrough,
+    /// A jump being taken.
+    Branch,
+    /// A call to the start of a function.
+    Call,
+    /// A `ret` going back to the instruction after a call of its function.
+    Return,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct Edge {
+    from: usize,
+    to: usize,
+    kind: EdgeKind,
+}
+
+/// The entry point or the target of a call, with the blocks that can run before it returns.
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Function {
+    start: usize,
+    /// Sorted block indices, blocks can be shared between functions.
+    blocks: Vec<usize>,
+}
+
+impl BasicBlock {
+    /// The index of the first instruction.
+    pub fn start(&self) -> usize {
+        return self.start;
+    }
+
+    /// The index after the last instruction.
+    pub fn end(&self) -> usize {
+        return self.end;
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        return self.start..self.end;
+    }
+
+    pub fn last(&self) -> usize {
+        return self.end - 1;
+    }
+}
+
+impl Edge {
+    fn new(from: usize, to: usize, kind: EdgeKind) -> Self {
+        return Self { from, to, kind };
+    }
+
+    pub fn from(&self) -> usize {
+        return self.from;
+    }
+
+    pub fn to(&self) -> usize {
+        return self.to;
+    }
+
+    pub fn kind(&self) -> EdgeKind {
+        return self.kind;
+    }
+}
+
+impl Function {
+    /// The index of the first instruction.
+    pub fn start(&self) -> usize {
+        return self.start;
+    }
+
+    pub fn blocks(&self) -> &[usize] {
+        return &self.blocks;
+    }
+}
+
+impl ControlFlowGraph {
+    pub fn new(instructions: &[Instruction], entry: u64) -> Self {
+        let blocks = find_blocks(instructions, entry);
+        let mut graph = Self {
+            entry: entry as usize,
+            blocks,
+            edges: Vec::new(),
+            functions: Vec::new(),
+        };
+
+        for (block, range) in graph.blocks.iter().enumerate() {
+            let last = range.last();
+            let next = block + 1;
+            let has_next = next < graph.blocks.len();
+            let target = branch_target(&instructions[last])
+                .and_then(|target| graph.block_starting_at(target as usize));
+
+            match instructions[last] {
+                Instruction::Ret | Instruction::Halt => (),
+                Instruction::Jmp(_) => {
+                    if let Some(target) = target {
+                        graph.edges.push(Edge::new(block, target, EdgeKind::Branch));
+                    }
+                }
+                Instruction::Call(_) => {
+                    if let Some(target) = target {
+                        graph.edges.push(Edge::new(block, target, EdgeKind::Call));
+                    }
+
+                    if has_next {
+                        graph
+                            .edges
+                            .push(Edge::new(block, next, EdgeKind::FallThrough));
+                    }
+                }
+                _ => {
+                    if let Some(target) = target {
+                        graph.edges.push(Edge::new(block, target, EdgeKind::Branch));
+                    }
+
+                    if has_next {
+                        graph
+                            .edges
+                            .push(Edge::new(block, next, EdgeKind::FallThrough));
+                    }
+                }
+            }
+        }
+
+        let mut starts: BTreeSet<usize> = instructions
+            .iter()
+            .filter(|instruction| matches!(instruction, Instruction::Call(_)))
+            .filter_map(branch_target)
+            .map(|target| target as usize)
+            .filter(|target| *target < instructions.len())
+            .collect();
+
+        if graph.entry < instructions.len() {
+            starts.insert(graph.entry);
+        }
+
+        for start in starts {
+            let function = graph.find_function(start);
+            graph.add_return_edges(instructions, &function);
+            graph.functions.push(function);
+        }
+
+        graph.edges.sort();
+        graph.edges.dedup();
+
+        return graph;
+    }
+
+    pub fn blocks(&self) -> &[BasicBlock] {
+        return &self.blocks;
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        return &self.edges;
+    }
+
+    pub fn functions(&self) -> &[Function] {
+        return &self.functions;
+    }
+
+    /// The index of the block holding the instruction.
+    pub fn block_containing(&self, index: usize) -> Option<usize> {
+        let end = self.blocks.partition_point(|block| block.start <= index);
+        let block = end.checked_sub(1)?;
+
+        return Some(block).filter(|block| index < self.blocks[*block].end);
+    }
+
+    pub fn block_starting_at(&self, index: usize) -> Option<usize> {
+        return self
+            .block_containing(index)
+            .filter(|block| self.blocks[*block].start == index);
+    }
+
+    /// The edges leaving a block.
+    pub fn successors(&self, block: usize) -> &[Edge] {
+        let start = self.edges.partition_point(|edge| edge.from < block);
+        let end = self.edges.partition_point(|edge| edge.from <= block);
+
+        return &self.edges[start..end];
+    }
+
+    pub fn function_starting_at(&self, index: usize) -> Option<&Function> {
+        return self
+            .functions
+            .binary_search_by_key(&index, |function| function.start)
+            .ok()
+            .map(|i| &self.functions[i]);
+    }
+
+    /// Writes the graph in the Graphviz DOT language, each node lists the assembly of a block.
+    ///
+    /// Branches are drawn in blue, calls dashed and returns dotted.
+    pub fn to_dot(&self, instructions: &[Instruction], symbols: Option<&SymbolTable>) -> String {
+        let mut output = String::from("digraph cfg {\n");
+        output.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+
+        for (i, block) in self.blocks.iter().enumerate() {
+            let mut lines = Vec::new();
+
+            if block.start == self.entry {
+                lines.push(String::from("entry:"));
+            }
+
+            lines.push(format!("{}:", label(block.start as u64, symbols)));
+
+            for index in block.range() {
+                lines.push(format!(
+                    "{:>4}  {}",
+                    index,
+                    format_instruction(instructions[index], instructions.len() as u64, symbols)
+                ));
+            }
+
+            let label: String = lines.iter().map(|line| escape(line) + "\\l").collect();
+
+            let _ = writeln!(output, "    block_{} [label=\"{}\"];", i, label);
+        }
+
+        for edge in &self.edges {
+            let attributes = match edge.kind {
+                EdgeKind::FallThrough => "",
+                EdgeKind::Branch => " [color=blue]",
+                EdgeKind::Call => " [style=dashed]",
+                EdgeKind::Return => " [style=dotted]",
+            };
+
+            let _ = writeln!(
+                output,
+                "    block_{} -> block_{}{};",
+                edge.from, edge.to, attributes
+            );
+        }
+
+        output.push_str("}\n");
+
+        return output;
+    }
+
+    /// Collects the blocks reachable from the start without following calls or returns.
+    fn find_function(&self, start: usize) -> Function {
+        let mut seen = vec![false; self.blocks.len()];
+        let mut pending: Vec<usize> = self.block_starting_at(start).into_iter().collect();
+
+        while let Some(block) = pending.pop() {
+            if seen[block] {
+                continue;
+            }
+
+            seen[block] = true;
+
+            for edge in self.successors(block) {
+                if matches!(edge.kind, EdgeKind::FallThrough | EdgeKind::Branch) {
+                    pending.push(edge.to);
+                }
+            }
+        }
+
+        let blocks = (0..self.blocks.len())
+            .filter(|block| seen[*block])
+            .collect();
+
+        return Function { start, blocks };
+    }
+
+    /// Links every `ret` of a function to the instruction after each call of it, keeping the
+    /// edges sorted.
+    fn add_return_edges(&mut self, instructions: &[Instruction], function: &Function) {
+        let returns: Vec<usize> = function
+            .blocks
+            .iter()
+            .copied()
+            .filter(|block| instructions[self.blocks[*block].last()] == Instruction::Ret)
+            .collect();
+
+        for (block, range) in self.blocks.iter().enumerate() {
+            let instruction = &instructions[range.last()];
+            let calls_function = matches!(instruction, Instruction::Call(_))
+                && branch_target(instruction) == Some(function.start as u64);
+
+            if !calls_function || block + 1 == self.blocks.len() {
+                continue;
+            }
+
+            for from in &returns {
+                self.edges
+                    .push(Edge::new(*from, block + 1, EdgeKind::Return));
+            }
+        }
+
+        self.edges.sort();
+    }
+}
+
+/// Splits the program before every jump target and after every jump, call, `ret` and `halt`.
+fn find_blocks(instructions: &[Instruction], entry: u64) -> Vec<BasicBlock> {
+    let mut leaders = BTreeSet::new();
+
+    if !instructions.is_empty() {
+        leaders.insert(0);
+    }
+
+    if entry < instructions.len() as u64 {
+        leaders.insert(entry as usize);
+    }
+
+    for (index, instruction) in instructions.iter().enumerate() {
+        if let Some(target) = branch_target(instruction) {
+            if target < instructions.len() as u64 {
+                leaders.insert(target as usize);
+            }
+        }
+
+        let ends_block = branch_target(instruction).is_some()
+            || matches!(instruction, Instruction::Ret | Instruction::Halt);
+
+        if ends_block && index + 1 < instructions.len() {
+            leaders.insert(index + 1);
+        }
+    }
+
+    let starts: Vec<usize> = leaders.into_iter().collect();
+
+    return starts
+        .iter()
+        .enumerate()
+        .map(|(i, start)| BasicBlock {
+            start: *start,
+            end: starts.get(i + 1).copied().unwrap_or(instructions.len()),
+        })
+        .collect();
+}
+
+/// Escapes text for a quoted DOT string.
+fn escape(text: &str) -> String {
+    return text.replace('\\', "\\\\").replace('"', "\\\"");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use vxl_iset::instruction_arguments::{Address, Immediate, Register};
+
+    fn power() -> Vec<Instruction> {
+        // The pow.vsm example from the vxasm repository.
+        return vec![
+            Instruction::Ldi(Immediate::from(3i64), Register::R0),
+            Instruction::Ldi(Immediate::from(4i64), Register::R1),
+            Instruction::Call(Address::from(4u64)),
+            Instruction::Halt,
+            Instruction::Ldb(Immediate::from(1u8), Register::ROU),
+            Instruction::Mov(Register::R2, Register::R0),
+            Instruction::Cmp(Register::R1, Register::R0),
+            Instruction::Jge(Address::from(8u64)),
+            Instruction::Ret,
+        ];
+    }
+
+    #[test]
+    fn test_power_graph() {
+        let graph = ControlFlowGraph::new(&power(), 0);
+
+        let ranges: Vec<Range<usize>> = graph.blocks().iter().map(BasicBlock::range).collect();
+        assert_eq!(ranges, vec![0..3, 3..4, 4..8, 8..9]);
+
+        assert_eq!(
+            graph.edges(),
+            &[
+                Edge::new(0, 1, EdgeKind::FallThrough),
+                Edge::new(0, 2, EdgeKind::Call),
+                Edge::new(2, 3, EdgeKind::FallThrough),
+                Edge::new(2, 3, EdgeKind::Branch),
+                Edge::new(3, 1, EdgeKind::Return),
+            ]
+        );
+
+        assert_eq!(
+            graph.functions(),
+            &[
+                Function {
+                    start: 0,
+                    blocks: vec![0, 1]
+                },
+                Function {
+                    start: 4,
+                    blocks: vec![2, 3]
+                },
+            ]
+        );
+
+        assert_eq!(graph.block_containing(6), Some(2));
+        assert_eq!(graph.block_starting_at(6), None);
+        assert_eq!(graph.block_containing(9), None);
+        assert_eq!(graph.successors(1), &[]);
+    }
+
+    #[test]
+    fn test_invalid_targets() {
+        // 0: jmp 2
+        // 1: call 7
+        // 2: jlt 0
+        let instructions = vec![
+            Instruction::Jmp(Address::from(2u64)),
+            Instruction::Call(Address::from(7u64)),
+            Instruction::Jlt(Address::from(0u64)),
+        ];
+
+        let graph = ControlFlowGraph::new(&instructions, 5);
+
+        assert_eq!(graph.blocks().len(), 3);
+        assert_eq!(
+            graph.edges(),
+            &[
+                Edge::new(0, 2, EdgeKind::Branch),
+                Edge::new(1, 2, EdgeKind::FallThrough),
+                Edge::new(2, 0, EdgeKind::Branch),
+            ]
+        );
+        assert!(graph.functions().is_empty());
+        assert!(ControlFlowGraph::new(&[], 0).blocks().is_empty());
+    }
+
+    #[test]
+    fn test_dot() {
+        let instructions = vec![
+            Instruction::Call(Address::from(2u64)),
+            Instruction::Halt,
+            Instruction::Ret,
+        ];
+
+        let mut symbols = SymbolTable::new();
+        symbols.add_function(2, String::from("\"quoted\""));
+
+        assert_eq!(
+            ControlFlowGraph::new(&instructions, 0).to_dot(&instructions, Some(&symbols)),
+            "digraph cfg {\n\
+            \x20   node [shape=box, fontname=\"monospace\"];\n\
+            \x20   block_0 [label=\"entry:\\llabel_0:\\l   0  call \\\"quoted\\\"\\l\"];\n\
+            \x20   block_1 [label=\"label_1:\\l   1  halt\\l\"];\n\
+            \x20   block_2 [label=\"\\\"quoted\\\":\\l   2  ret\\l\"];\n\
+            \x20   block_0 -> block_1;\n\
+            \x20   block_0 -> block_2 [style=dashed];\n\
+            \x20   block_2 -> block_1 [style=dotted];\n\
+            }\n"
+        );
+    }
+}
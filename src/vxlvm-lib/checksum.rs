@@ -0,0 +1,58 @@
+use digest::consts::{U0, U4};
+use digest::{FixedOutput, HashMarker, Output, OutputSizeUser, Update};
+
+/// CRC32 as a `Digest`, much faster than the cryptographic hashes but only suitable for catching
+/// accidental corruption. The output is little endian.
+#[derive(Clone, Default)]
+pub struct Crc32 {
+    hasher: crc32fast::Hasher,
+}
+
+impl HashMarker for Crc32 {}
+
+impl OutputSizeUser for Crc32 {
+    type OutputSize = U4;
+}
+
+impl Update for Crc32 {
+    fn update(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+    }
+}
+
+impl FixedOutput for Crc32 {
+    fn finalize_into(self, out: &mut Output<Self>) {
+        out.copy_from_slice(&self.hasher.finalize().to_le_bytes());
+    }
+}
+
+/// A `Digest` with no output, for files built without a checksum. An empty checksum is stored as
+/// zeros so any file with a zeroed checksum field passes.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct NoChecksum;
+
+impl HashMarker for NoChecksum {}
+
+impl OutputSizeUser for NoChecksum {
+    type OutputSize = U0;
+}
+
+impl Update for NoChecksum {
+    fn update(&mut self, _data: &[u8]) {}
+}
+
+impl FixedOutput for NoChecksum {
+    fn finalize_into(self, _out: &mut Output<Self>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use digest::Digest;
+
+    #[test]
+    fn test_crc32() {
+        // The standard check value for CRC32.
+        assert_eq!(Crc32::digest(b"123456789")[..], 0xcbf43926u32.to_le_bytes());
+    }
+}
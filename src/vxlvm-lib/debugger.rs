@@ -0,0 +1,249 @@
+use alloc::collections::BTreeSet;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::error::VMError;
+use crate::vm::VM;
+use vxl_iset::instruction::Instruction;
+use vxl_iset::instruction_arguments::Register;
+use vxl_iset::syscall_handler::SyscallHandler;
+
+/// The number of bytes `call` pushes below the callee's frame: `rfp`, `rsp` and the return
+/// address.
+const CALL_FRAME_SIZE: u64 = 24;
+
+/// Runs a machine an instruction at a time, stopping at breakpoints or when a step finishes.
+pub struct Debugger {
+    machine: VM,
+    /// Instruction indices.
+    breakpoints: BTreeSet<usize>,
+    target: Target,
+}
+
+/// Where `Debugger::run` stops if there is no breakpoint in the way.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum Resume {
+    /// Only stop at a breakpoint.
+    Continue,
+    /// Stop after one instruction.
+    Step,
+    /// Stop after one instruction, or after a `call` once the callee returns.
+    StepOver,
+    /// Stop once the current function returns.
+    StepOut,
+}
+
+/// Why `Debugger::run` stopped.
+#[derive(Clone, PartialEq, Debug)]
+pub enum StopReason {
+    /// The next instruction has a breakpoint.
+    Breakpoint,
+    /// The step asked for by `resume` is complete.
+    Step,
+    /// The limit passed to `run` was reached first.
+    Limit,
+    /// The machine ran past its last instruction.
+    Finished,
+    Halted,
+    Error(VMError),
+}
+
+/// A function call that hasn't returned, found from the chain of saved `rsp` values.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct Frame {
+    index: usize,
+    base: u64,
+    top: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+enum Target {
+    Continue,
+    Instruction,
+    /// The instruction after a call, once the frame has the same `rsp` again.
+    ReturnTo(usize, u64),
+    /// `rsp` below the frame's.
+    Return(u64),
+}
+
+impl Frame {
+    /// The instruction the frame is running, which is a `call` for every frame but the
+    /// innermost.
+    pub fn index(&self) -> usize {
+        return self.index;
+    }
+
+    /// The first byte of the stack that belongs to the frame, its `rsp`.
+    pub fn base(&self) -> u64 {
+        return self.base;
+    }
+
+    /// The byte after the last value the frame has pushed.
+    pub fn top(&self) -> u64 {
+        return self.top;
+    }
+}
+
+impl Debugger {
+    /// Superinstructions are unfused so that every instruction can be stepped to.
+    pub fn new(mut machine: VM) -> Self {
+        machine.unfuse();
+
+        return Self {
+            machine,
+            breakpoints: BTreeSet::new(),
+            target: Target::Continue,
+        };
+    }
+
+    pub fn machine(&self) -> &VM {
+        return &self.machine;
+    }
+
+    pub fn machine_mut(&mut self) -> &mut VM {
+        return &mut self.machine;
+    }
+
+    pub fn into_machine(self) -> VM {
+        return self.machine;
+    }
+
+    /// Returns false if there is no instruction at the index.
+    pub fn add_breakpoint(&mut self, index: usize) -> bool {
+        if index >= self.machine.instructions().len() {
+            return false;
+        }
+
+        self.breakpoints.insert(index);
+
+        return true;
+    }
+
+    pub fn remove_breakpoint(&mut self, index: usize) -> bool {
+        return self.breakpoints.remove(&index);
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        return self.breakpoints.iter().copied();
+    }
+
+    /// Sets where the next call to `run` stops, based on the current instruction.
+    pub fn resume(&mut self, resume: Resume) {
+        let rsp = self.machine.registers().get_value(Register::RSP as u8);
+        let ip = self.machine.ip();
+
+        self.target = match resume {
+            Resume::Continue => Target::Continue,
+            Resume::Step => Target::Instruction,
+            Resume::StepOver => match self.machine.instructions().get(ip) {
+                Some(Instruction::Call(_)) => Target::ReturnTo(ip + 1, rsp),
+                _ => Target::Instruction,
+            },
+            Resume::StepOut => Target::Return(rsp),
+        };
+    }
+
+    /// Runs at most `limit` instructions towards the target set by `resume`. The current
+    /// instruction always runs, even if it has a breakpoint.
+    pub fn run<H: SyscallHandler<VM>>(&mut self, handler: &mut H, limit: u64) -> StopReason {
+        if let Some(reason) = self.finished() {
+            return reason;
+        }
+
+        for _ in 0..limit {
+            if let Err(error) = self.machine.run_next(handler) {
+                return StopReason::Error(error);
+            }
+
+            if let Some(reason) = self.finished() {
+                return reason;
+            }
+
+            if self.reached_target() {
+                return StopReason::Step;
+            }
+
+            if self.breakpoints.contains(&self.machine.ip()) {
+                return StopReason::Breakpoint;
+            }
+        }
+
+        return StopReason::Limit;
+    }
+
+    fn finished(&self) -> Option<StopReason> {
+        if self.machine.is_halted() {
+            return Some(StopReason::Halted);
+        }
+
+        if self.machine.ip() >= self.machine.instructions().len() {
+            return Some(StopReason::Finished);
+        }
+
+        return None;
+    }
+
+    fn reached_target(&self) -> bool {
+        let rsp = self.machine.registers().get_value(Register::RSP as u8);
+
+        return match self.target {
+            Target::Continue => false,
+            Target::Instruction => true,
+            Target::ReturnTo(ip, frame) => self.machine.ip() == ip && rsp == frame,
+            Target::Return(frame) => rsp < frame,
+        };
+    }
+
+    /// Returns the active frames, innermost first. The chain stops early if the saved values
+    /// have been overwritten.
+    pub fn frames(&self) -> Vec<Frame> {
+        let registers = self.machine.registers();
+        let stack = self.machine.stack();
+
+        let mut frame = Frame {
+            index: self.machine.ip(),
+            base: registers.get_value(Register::RSP as u8),
+            top: registers.get_value(Register::RFP as u8),
+        };
+        let mut frames = vec![frame];
+
+        while frame.base != 0 {
+            let (return_ip, caller_rsp) = match (
+                stack.get_top_u64(frame.base),
+                frame
+                    .base
+                    .checked_sub(8)
+                    .and_then(|top| stack.get_top_u64(top)),
+            ) {
+                (Some(return_ip), Some(caller_rsp)) if return_ip > 0 => (return_ip, caller_rsp),
+                _ => break,
+            };
+
+            // Frames always grow upwards, anything else is a corrupted stack.
+            if caller_rsp >= frame.base || frame.base < CALL_FRAME_SIZE {
+                break;
+            }
+
+            frame = Frame {
+                index: return_ip as usize - 1,
+                base: caller_rsp,
+                top: frame.base - CALL_FRAME_SIZE,
+            };
+            frames.push(frame);
+        }
+
+        return frames;
+    }
+
+    /// The values the frame has pushed, from the first.
+    pub fn frame_values(&self, frame: &Frame) -> Vec<u64> {
+        return (frame.base..frame.top)
+            .step_by(8)
+            .filter_map(|offset| self.machine.stack().get_top_u64(offset + 8))
+            .collect();
+    }
+}
@@ -0,0 +1,293 @@
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Write;
+use either::Either;
+
+use crate::effects::branch_target;
+use crate::error::{LoaderError, ValidatorError};
+use crate::loader::Loader;
+use crate::symbols::SymbolTable;
+use crate::validator::BulkValidator;
+use crate::writer::instruction_parts;
+use vxl_iset::instruction::Instruction;
+use vxl_iset::instruction_arguments::{Immediate, Register};
+
+/// Decodes a `.xvl` file and disassembles its program, see `disassemble`.
+///
+/// The checksum is not verified so that damaged files can still be inspected.
+pub fn disassemble_bytes(bytes: &[u8]) -> Result<String, Either<LoaderError, ValidatorError>> {
+    let loader = Loader::load_bytes(bytes).map_err(Either::Left)?;
+    let symbols = loader.symbols().cloned();
+
+    let (header, instructions) = loader
+        .to_instructions(BulkValidator::new())
+        .map_err(Either::Right)?;
+
+    return Ok(disassemble_with_symbols(
+        &instructions,
+        header.starting_offset(),
+        symbols.as_ref(),
+    ));
+}
+
+/// Produces one line per instruction containing its index, byte offset within the program and
+/// its assembly.
+///
+/// The entry point is labelled `entry` and every jump or call target is given a label that is
+/// used in place of the target's index.
+pub fn disassemble(instructions: &[Instruction], entry: u64) -> String {
+    return disassemble_with_symbols(instructions, entry, None);
+}
+
+/// Disassembles as `disassemble` does, except that functions are labelled with their names and
+/// each change of source line is noted in a comment.
+pub fn disassemble_with_symbols(
+    instructions: &[Instruction],
+    entry: u64,
+    symbols: Option<&SymbolTable>,
+) -> String {
+    let mut targets: BTreeSet<u64> = instructions.iter().filter_map(branch_target).collect();
+
+    if let Some(symbols) = symbols {
+        targets.extend(symbols.functions().iter().map(|function| function.start()));
+    }
+
+    let mut output = String::new();
+    let mut offset = 0;
+    let mut previous_location = None;
+
+    for (index, instruction) in instructions.iter().enumerate() {
+        let index = index as u64;
+
+        if index == entry {
+            output.push_str("entry:\n");
+        }
+
+        if targets.contains(&index) {
+            let _ = writeln!(output, "{}:", label(index, symbols));
+        }
+
+        let _ = write!(
+            output,
+            "{:>8}  {:#08x}  {}",
+            index,
+            offset,
+            format_instruction(*instruction, instructions.len() as u64, symbols)
+        );
+
+        let location = symbols.and_then(|symbols| symbols.location_at(index));
+
+        if location != previous_location {
+            if let Some(location) = location {
+                let _ = write!(output, "  ; {}:{}", location.file, location.line);
+            }
+        }
+
+        output.push('\n');
+
+        previous_location = location;
+        offset += encoded_size(*instruction);
+    }
+
+    return output;
+}
+
+pub fn register_name(register: Register) -> String {
+    return format!("${:?}", register).to_lowercase();
+}
+
+pub fn mnemonic(instruction: Instruction) -> String {
+    let name = format!("{:?}", instruction);
+
+    return match name.split_once('(') {
+        Some((name, _)) => name.to_lowercase(),
+        None => name.to_lowercase(),
+    };
+}
+
+/// Formats an instruction as assembly, jump and call targets are written as labels.
+pub(crate) fn format_instruction(
+    instruction: Instruction,
+    instruction_count: u64,
+    symbols: Option<&SymbolTable>,
+) -> String {
+    let (_opcode, registers, addresses, immediates) = instruction_parts(instruction);
+    let mut operands = Vec::new();
+
+    for immediate in immediates {
+        if let Instruction::Ldf(_, _) = instruction {
+            operands.push(format!("{:?}", f64::from_bits(u64::from(immediate))));
+        } else {
+            operands.push(format_immediate(immediate));
+        }
+    }
+
+    for address in addresses {
+        let address = u64::from(address);
+
+        match branch_target(&instruction) {
+            Some(_) if address < instruction_count => operands.push(label(address, symbols)),
+            _ => operands.push(format!("{:#x}", address)),
+        }
+    }
+
+    for register in registers {
+        operands.push(register_name(register));
+    }
+
+    if operands.is_empty() {
+        return mnemonic(instruction);
+    }
+
+    return format!("{} {}", mnemonic(instruction), operands.join(", "));
+}
+
+/// Small values are written as signed integers and anything else in hexadecimal.
+fn format_immediate(immediate: Immediate) -> String {
+    let value = u64::from(immediate);
+
+    if (value as i64).unsigned_abs() <= i32::MAX as u64 {
+        return (value as i64).to_string();
+    }
+
+    return format!("{:#x}", value);
+}
+
+/// Functions are labelled by name and any other target by its index.
+pub(crate) fn label(index: u64, symbols: Option<&SymbolTable>) -> String {
+    return match symbols.and_then(|symbols| symbols.function_starting_at(index)) {
+        Some(function) => String::from(function.name()),
+        None => format!("label_{}", index),
+    };
+}
+
+fn encoded_size(instruction: Instruction) -> usize {
+    let (_opcode, registers, addresses, immediates) = instruction_parts(instruction);
+
+    return 1 + (addresses.len() + immediates.len()) * 8 + registers.len().div_ceil(2);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::loader::ChecksumAlgorithm;
+    use crate::writer::Writer;
+    use alloc::vec;
+    use vxl_iset::instruction_arguments::Address;
+
+    #[test]
+    fn test_disassemble_power() {
+        // The pow.vsm example from the vxasm repository.
+        let instructions = vec![
+            Instruction::Ldi(Immediate::from(3i64), Register::R0),
+            Instruction::Ldi(Immediate::from(4i64), Register::R1),
+            Instruction::Call(Address::from(4u64)),
+            Instruction::Halt,
+            Instruction::Ldb(Immediate::from(1u8), Register::ROU),
+            Instruction::Mov(Register::R2, Register::R0),
+            Instruction::Cmp(Register::R1, Register::R0),
+            Instruction::Jge(Address::from(8u64)),
+            Instruction::Ret,
+        ];
+
+        let bytes = Writer::new(instructions, 0, ChecksumAlgorithm::Sha3_224).to_bytes();
+
+        assert_eq!(
+            disassemble_bytes(&bytes).unwrap(),
+            "entry:\n\
+            \x20      0  0x000000  ldi 3, $r0\n\
+            \x20      1  0x00000a  ldi 4, $r1\n\
+            \x20      2  0x000014  call label_4\n\
+            \x20      3  0x00001d  halt\n\
+            label_4:\n\
+            \x20      4  0x00001e  ldb 1, $rou\n\
+            \x20      5  0x000028  mov $r2, $r0\n\
+            \x20      6  0x00002a  cmp $r1, $r0\n\
+            \x20      7  0x00002c  jge label_8\n\
+            label_8:\n\
+            \x20      8  0x000035  ret\n"
+        );
+    }
+
+    #[test]
+    fn test_format_operands() {
+        assert_eq!(
+            format_instruction(
+                Instruction::Ldf(Immediate::from(63.24f64), Register::R0),
+                1,
+                None
+            ),
+            "ldf 63.24, $r0"
+        );
+        assert_eq!(
+            format_instruction(
+                Instruction::Ldi(Immediate::from(-2i64), Register::R9),
+                1,
+                None
+            ),
+            "ldi -2, $r9"
+        );
+        assert_eq!(
+            format_instruction(
+                Instruction::Syscall(Immediate::from(0xdead_beef_0000u64)),
+                1,
+                None
+            ),
+            "syscall 0xdeadbeef0000"
+        );
+        assert_eq!(
+            format_instruction(Instruction::Jmp(Address::from(7u64)), 3, None),
+            "jmp 0x7"
+        );
+        assert_eq!(
+            format_instruction(Instruction::Freea(Address::from(2u64)), 3, None),
+            "freea 0x2"
+        );
+    }
+
+    #[test]
+    fn test_entry_label() {
+        let instructions = vec![Instruction::Nop, Instruction::Jmp(Address::from(1u64))];
+
+        assert_eq!(
+            disassemble(&instructions, 1),
+            "       0  0x000000  nop\n\
+            entry:\n\
+            label_1:\n\
+            \x20      1  0x000001  jmp label_1\n"
+        );
+    }
+
+    #[test]
+    fn test_symbols() {
+        let instructions = vec![
+            Instruction::Call(Address::from(2u64)),
+            Instruction::Halt,
+            Instruction::Jmp(Address::from(3u64)),
+            Instruction::Ret,
+        ];
+
+        let mut symbols = SymbolTable::new();
+        symbols.add_function(0, String::from("main"));
+        symbols.add_function(2, String::from("exit"));
+
+        let file = symbols.add_file(String::from("exit.vsm"));
+        symbols.add_line(0, file, 1);
+        symbols.add_line(2, file, 4);
+
+        assert_eq!(
+            disassemble_with_symbols(&instructions, 0, Some(&symbols)),
+            "entry:\n\
+            main:\n\
+            \x20      0  0x000000  call exit  ; exit.vsm:1\n\
+            \x20      1  0x000009  halt\n\
+            exit:\n\
+            \x20      2  0x00000a  jmp label_3  ; exit.vsm:4\n\
+            label_3:\n\
+            \x20      3  0x000013  ret\n"
+        );
+    }
+}
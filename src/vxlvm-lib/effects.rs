@@ -0,0 +1,111 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::writer::instruction_parts;
+use vxl_iset::instruction::Instruction;
+use vxl_iset::instruction_arguments::Register;
+
+/// Returns the instruction index that a jump or call transfers control to.
+pub fn branch_target(instruction: &Instruction) -> Option<u64> {
+    return match instruction {
+        Instruction::Jmp(a)
+        | Instruction::Jeq(a)
+        | Instruction::Jne(a)
+        | Instruction::Jge(a)
+        | Instruction::Jgt(a)
+        | Instruction::Jle(a)
+        | Instruction::Jlt(a)
+        | Instruction::Call(a) => Some(u64::from(*a)),
+        _ => None,
+    };
+}
+
+/// Returns the registers that an instruction stores a result in. Flags, overflow and the changes
+/// to `rfp` made by `push`, `pop`, `call` and `ret` are not included.
+pub fn written_registers(instruction: &Instruction) -> Vec<Register> {
+    let (_opcode, registers, _addresses, _immediates) = instruction_parts(*instruction);
+
+    return match instruction {
+        Instruction::Swpr(a, b) => vec![*a, *b],
+        Instruction::Ldb(_, _)
+        | Instruction::Ldi(_, _)
+        | Instruction::Ldf(_, _)
+        | Instruction::Mov(_, _)
+        | Instruction::Pop(_)
+        | Instruction::Sget(_, _)
+        | Instruction::Malloc(_, _)
+        | Instruction::Malloci(_, _)
+        | Instruction::Getb(_, _, _)
+        | Instruction::Geti(_, _, _)
+        | Instruction::Igetb(_, _, _)
+        | Instruction::Igeti(_, _, _)
+        | Instruction::Last(_, _)
+        | Instruction::Length(_, _)
+        | Instruction::Clone(_, _)
+        | Instruction::Addi(_, _, _)
+        | Instruction::Subi(_, _, _)
+        | Instruction::Muli(_, _, _)
+        | Instruction::Divi(_, _, _)
+        | Instruction::Modi(_, _, _)
+        | Instruction::Addu(_, _, _)
+        | Instruction::Subu(_, _, _)
+        | Instruction::Mulu(_, _, _)
+        | Instruction::Divu(_, _, _)
+        | Instruction::Modu(_, _, _)
+        | Instruction::Addf(_, _, _)
+        | Instruction::Subf(_, _, _)
+        | Instruction::Mulf(_, _, _)
+        | Instruction::Divf(_, _, _)
+        | Instruction::Rotl(_, _)
+        | Instruction::Rotli(_, _)
+        | Instruction::Rotr(_, _)
+        | Instruction::Rotri(_, _)
+        | Instruction::Sll(_, _)
+        | Instruction::Slli(_, _)
+        | Instruction::Srl(_, _)
+        | Instruction::Srli(_, _)
+        | Instruction::Not(_)
+        | Instruction::And(_, _, _)
+        | Instruction::Or(_, _, _)
+        | Instruction::Xor(_, _, _)
+        | Instruction::I2f(_)
+        | Instruction::F2i(_) => registers.into_iter().take(1).collect(),
+        _ => Vec::new(),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vxl_iset::instruction_arguments::{Address, Immediate};
+
+    #[test]
+    fn test_branch_target() {
+        assert_eq!(
+            branch_target(&Instruction::Jne(Address::from(4u64))),
+            Some(4)
+        );
+        assert_eq!(
+            branch_target(&Instruction::Call(Address::from(7u64))),
+            Some(7)
+        );
+        assert_eq!(branch_target(&Instruction::Ret), None);
+    }
+
+    #[test]
+    fn test_written_registers() {
+        assert_eq!(
+            written_registers(&Instruction::Addi(Register::R0, Register::R1, Register::R2)),
+            vec![Register::R0]
+        );
+        assert_eq!(
+            written_registers(&Instruction::Swpr(Register::R3, Register::R4)),
+            vec![Register::R3, Register::R4]
+        );
+        assert_eq!(
+            written_registers(&Instruction::Ldi(Immediate::from(1u64), Register::R5)),
+            vec![Register::R5]
+        );
+        assert_eq!(written_registers(&Instruction::Push(Register::R0)), vec![]);
+    }
+}
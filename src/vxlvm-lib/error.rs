@@ -9,6 +9,22 @@ pub enum LoaderError {
     NonMatchingFileSize,
     InvalidMagic,
     InvalidEndHeaderMarker,
+    InvalidSectionTable,
+    UnknownSectionKind(u8),
+    InvalidSymbolTable,
+    InvalidLinkTable,
+    /// The `ByteSource` the file was being loaded from failed.
+    ReadFailed,
+    /// The header flags, given in full, select an unknown checksum algorithm.
+    UnknownChecksumAlgorithm(u8),
+    /// A signature was required but the file is not signed.
+    MissingSignature,
+    /// The file is signed by a trusted key but the signature does not match its contents.
+    InvalidSignature,
+    /// The file is signed by a key that is not trusted.
+    UntrustedSignature,
+    /// The file has no checksum and the `LoadPolicy` doesn't allow unchecked files.
+    UncheckedNotAllowed,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
@@ -20,6 +36,81 @@ pub enum ValidatorError {
     InvalidInstructionFormat,
 }
 
+/// A problem with the control flow of a decoded program, see `validator::verify_program`,
+/// `stack_depth::StackUsage` and `VM::patch_instructions`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum ProgramError {
+    /// The jump or call at the instruction index targets an index with no instruction.
+    TargetOutOfBounds(usize, u64),
+    /// The entry offset is not the index of an instruction.
+    EntryOutOfBounds(u64),
+    /// Execution can continue past the last instruction, which is at the index.
+    FallsOffEnd(usize),
+    /// The `pop` at the instruction index can run with nothing pushed by its function.
+    StackUnderflow(usize),
+    /// Replacing instructions from the index would go past the last instruction.
+    PatchOutOfBounds(usize),
+}
+
+/// A problem found when checking a whole file, see `Loader::verify_bytes`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum VerificationError {
+    Loader(LoaderError),
+    /// The program could not be decoded from the byte offset onwards.
+    Validator(usize, ValidatorError),
+    Program(ProgramError),
+}
+
+/// A problem with assembly source at a line and column, both counted from 1.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct AssemblyError {
+    line: usize,
+    column: usize,
+    kind: AssemblyErrorKind,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum AssemblyErrorKind {
+    UnknownMnemonic,
+    /// The instruction takes this many operands.
+    WrongOperandCount(usize),
+    InvalidImmediate,
+    InvalidAddress,
+    InvalidRegister,
+    UnknownLabel,
+    DuplicateLabel,
+    UnknownDirective,
+    /// A value of a data directive, or a missing or unterminated string.
+    InvalidData,
+    /// A directive in source that may only contain instructions.
+    DirectiveNotAllowed,
+    /// An imported label used other than as the only operand of a jump or call.
+    MisplacedImport,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub enum LinkError {
+    /// More than one module exports the symbol.
+    DuplicateSymbol(String),
+    /// No module exports a symbol that is imported.
+    UnresolvedSymbol(String),
+    /// An import refers to an instruction, identified by module and index, that isn't a jump or
+    /// call.
+    InvalidImportSite(usize, u64),
+    /// Only the first module may contain data sections, the module is identified by its index.
+    DataSectionInLibrary(usize),
+    /// The data sections of a module, identified by its index, could not be placed in memory.
+    FailedDataSectionAllocation(usize),
+    /// An export of a module, identified by module and index, is not an instruction of the
+    /// module.
+    InvalidExport(usize, u64),
+    /// Moving the target of a jump or call, identified by module and index, overflows.
+    RelocationOverflow(usize, u64),
+    /// A module, identified by its index, fails `validator::verify_module` with the problem,
+    /// which is the first one found.
+    InvalidProgram(usize, ProgramError),
+}
+
 #[derive(Debug, Clone, PartialEq, Hash)]
 pub enum VMError {
     SystemHalted,
@@ -35,6 +126,7 @@ pub enum VMError {
     FloatOverflowError,
     AttemptedModuloZeroOperation,
     UnknownSystemCall(u64),
+    WriteToReadOnlyMemoryError(u64),
     Unknown(String),
 }
 
@@ -53,6 +145,16 @@ impl LoaderError {
             LoaderError::NonMatchingFileSize => 3,
             LoaderError::InvalidMagic => 4,
             LoaderError::InvalidEndHeaderMarker => 5,
+            LoaderError::InvalidSectionTable => 6,
+            LoaderError::UnknownSectionKind(_) => 7,
+            LoaderError::InvalidSymbolTable => 8,
+            LoaderError::InvalidLinkTable => 9,
+            LoaderError::ReadFailed => 10,
+            LoaderError::UnknownChecksumAlgorithm(_) => 11,
+            LoaderError::MissingSignature => 12,
+            LoaderError::InvalidSignature => 13,
+            LoaderError::UntrustedSignature => 14,
+            LoaderError::UncheckedNotAllowed => 15,
         };
     }
 }
@@ -69,6 +171,80 @@ impl ValidatorError {
     }
 }
 
+impl AssemblyError {
+    pub fn new(line: usize, column: usize, kind: AssemblyErrorKind) -> Self {
+        return Self { line, column, kind };
+    }
+
+    pub fn line(&self) -> usize {
+        return self.line;
+    }
+
+    pub fn column(&self) -> usize {
+        return self.column;
+    }
+
+    pub fn kind(&self) -> AssemblyErrorKind {
+        return self.kind;
+    }
+}
+
+impl AssemblyErrorKind {
+    pub fn as_u8(&self) -> u8 {
+        return match self {
+            AssemblyErrorKind::UnknownMnemonic => 0,
+            AssemblyErrorKind::WrongOperandCount(_) => 1,
+            AssemblyErrorKind::InvalidImmediate => 2,
+            AssemblyErrorKind::InvalidAddress => 3,
+            AssemblyErrorKind::InvalidRegister => 4,
+            AssemblyErrorKind::UnknownLabel => 5,
+            AssemblyErrorKind::DuplicateLabel => 6,
+            AssemblyErrorKind::UnknownDirective => 7,
+            AssemblyErrorKind::InvalidData => 8,
+            AssemblyErrorKind::DirectiveNotAllowed => 9,
+            AssemblyErrorKind::MisplacedImport => 10,
+        };
+    }
+}
+
+impl LinkError {
+    pub fn as_u8(&self) -> u8 {
+        return match self {
+            LinkError::DuplicateSymbol(_) => 0,
+            LinkError::UnresolvedSymbol(_) => 1,
+            LinkError::InvalidImportSite(_, _) => 2,
+            LinkError::DataSectionInLibrary(_) => 3,
+            LinkError::FailedDataSectionAllocation(_) => 4,
+            LinkError::InvalidExport(_, _) => 5,
+            LinkError::RelocationOverflow(_, _) => 6,
+            LinkError::InvalidProgram(_, _) => 7,
+        };
+    }
+}
+
+impl ProgramError {
+    pub fn as_u8(&self) -> u8 {
+        return match self {
+            ProgramError::TargetOutOfBounds(_, _) => 0,
+            ProgramError::EntryOutOfBounds(_) => 1,
+            ProgramError::FallsOffEnd(_) => 2,
+            ProgramError::StackUnderflow(_) => 3,
+            ProgramError::PatchOutOfBounds(_) => 4,
+        };
+    }
+
+    /// The index of the instruction with the problem, if there is one.
+    pub fn instruction(&self) -> Option<usize> {
+        return match self {
+            ProgramError::TargetOutOfBounds(index, _)
+            | ProgramError::FallsOffEnd(index)
+            | ProgramError::StackUnderflow(index)
+            | ProgramError::PatchOutOfBounds(index) => Some(*index),
+            ProgramError::EntryOutOfBounds(_) => None,
+        };
+    }
+}
+
 impl VMError {
     pub fn as_u8(&self) -> u8 {
         return match self {
@@ -85,6 +261,7 @@ impl VMError {
             VMError::FloatOverflowError => 10,
             VMError::AttemptedModuloZeroOperation => 11,
             VMError::UnknownSystemCall(_) => 12,
+            VMError::WriteToReadOnlyMemoryError(_) => 13,
             VMError::Unknown(_) => u8::MAX,
         };
     }
@@ -105,6 +282,27 @@ impl VXLVMError for LoaderError {
             }
             LoaderError::InvalidMagic => "This file is not of the executable-voxeol format.",
             LoaderError::InvalidEndHeaderMarker => "Could not find the end-header marker.",
+            LoaderError::InvalidSectionTable => {
+                "The section table does not describe the contents of the file."
+            }
+            LoaderError::InvalidSymbolTable => "The symbols section could not be read.",
+            LoaderError::InvalidLinkTable => "The imports and exports could not be read.",
+            LoaderError::ReadFailed => "The file could not be read.",
+            LoaderError::MissingSignature => "This file is not signed.",
+            LoaderError::InvalidSignature => "This file's signature is invalid.",
+            LoaderError::UntrustedSignature => "This file is not signed by a trusted key.",
+            LoaderError::UncheckedNotAllowed => {
+                "This file has no checksum and unchecked files are not allowed."
+            }
+            LoaderError::UnknownSectionKind(k) => {
+                return format!("This file contains a section of unknown kind {}.", k);
+            }
+            LoaderError::UnknownChecksumAlgorithm(flags) => {
+                return format!(
+                    "The flags {:#010b} select an unknown checksum algorithm.",
+                    flags
+                );
+            }
         }
         .to_string();
     }
@@ -131,6 +329,141 @@ impl VXLVMError for ValidatorError {
     }
 }
 
+impl VXLVMError for AssemblyError {
+    fn specific_description(&self) -> String {
+        let description = match self.kind {
+            AssemblyErrorKind::UnknownMnemonic => String::from("Unknown instruction."),
+            AssemblyErrorKind::WrongOperandCount(count) => {
+                format!("The instruction takes {} operand(s).", count)
+            }
+            AssemblyErrorKind::InvalidImmediate => String::from("Invalid immediate value."),
+            AssemblyErrorKind::InvalidAddress => String::from("Invalid address."),
+            AssemblyErrorKind::InvalidRegister => String::from("Invalid register."),
+            AssemblyErrorKind::UnknownLabel => String::from("Unknown label."),
+            AssemblyErrorKind::DuplicateLabel => String::from("The label is already defined."),
+            AssemblyErrorKind::UnknownDirective => String::from("Unknown directive."),
+            AssemblyErrorKind::InvalidData => String::from("Invalid data."),
+            AssemblyErrorKind::DirectiveNotAllowed => {
+                String::from("Only instructions are allowed here, not directives.")
+            }
+            AssemblyErrorKind::MisplacedImport => {
+                String::from("An imported label can only be the target of a jump or call.")
+            }
+        };
+
+        return format!(
+            "{} At line {}, column {}.",
+            description, self.line, self.column
+        );
+    }
+
+    fn short_description(&self) -> String {
+        return format!(
+            "Assembly Error: {} At line {}, column {}.",
+            self.kind.as_u8(),
+            self.line,
+            self.column
+        );
+    }
+}
+
+impl VXLVMError for LinkError {
+    fn specific_description(&self) -> String {
+        return match self {
+            LinkError::DuplicateSymbol(name) => {
+                format!("The symbol {} is exported by more than one module.", name)
+            }
+            LinkError::UnresolvedSymbol(name) => {
+                format!("No module exports the symbol {}.", name)
+            }
+            LinkError::InvalidImportSite(module, index) => format!(
+                "Instruction {} of module {} imports a symbol but is not a jump or call.",
+                index, module
+            ),
+            LinkError::DataSectionInLibrary(module) => format!(
+                "Module {} contains data sections, only the first module may.",
+                module
+            ),
+            LinkError::FailedDataSectionAllocation(module) => format!(
+                "The data sections of module {} could not be placed in memory.",
+                module
+            ),
+            LinkError::InvalidExport(module, index) => format!(
+                "Module {} exports instruction {}, which it does not have.",
+                module, index
+            ),
+            LinkError::RelocationOverflow(module, index) => format!(
+                "The target of instruction {} of module {} is too large to relocate.",
+                index, module
+            ),
+            LinkError::InvalidProgram(module, problem) => format!(
+                "Module {} can't be loaded. {}",
+                module,
+                problem.specific_description()
+            ),
+        };
+    }
+
+    fn short_description(&self) -> String {
+        return format!("Link Error: {}", self.as_u8());
+    }
+}
+
+impl VXLVMError for ProgramError {
+    fn specific_description(&self) -> String {
+        return match self {
+            ProgramError::TargetOutOfBounds(index, target) => format!(
+                "Instruction {} jumps to {}, which is not an instruction.",
+                index, target
+            ),
+            ProgramError::EntryOutOfBounds(entry) => {
+                format!("The entry offset {} is not an instruction.", entry)
+            }
+            ProgramError::FallsOffEnd(index) => format!(
+                "Execution can continue past the last instruction, {}, without halting.",
+                index
+            ),
+            ProgramError::StackUnderflow(index) => format!(
+                "Instruction {} can pop more values than its function has pushed.",
+                index
+            ),
+            ProgramError::PatchOutOfBounds(index) => format!(
+                "Replacing instructions from {} would go past the last instruction.",
+                index
+            ),
+        };
+    }
+
+    fn short_description(&self) -> String {
+        return match self.instruction() {
+            Some(index) => format!("Program Error: {} At instruction {}.", self.as_u8(), index),
+            None => format!("Program Error: {}", self.as_u8()),
+        };
+    }
+}
+
+impl VXLVMError for VerificationError {
+    fn specific_description(&self) -> String {
+        return match self {
+            VerificationError::Loader(e) => e.specific_description(),
+            VerificationError::Program(e) => e.specific_description(),
+            VerificationError::Validator(offset, e) => {
+                format!("{} At program byte {}.", e.specific_description(), offset)
+            }
+        };
+    }
+
+    fn short_description(&self) -> String {
+        return match self {
+            VerificationError::Loader(e) => e.short_description(),
+            VerificationError::Program(e) => e.short_description(),
+            VerificationError::Validator(offset, e) => {
+                format!("{} At program byte {}.", e.short_description(), offset)
+            }
+        };
+    }
+}
+
 impl VXLVMError for VMError {
     fn specific_description(&self) -> String {
         return match self {
@@ -155,6 +488,9 @@ impl VXLVMError for VMError {
                 "Attempted a divide by 0 operation".to_string()
             }
             VMError::UnknownSystemCall(c) => format!("Unknown system call {}", c),
+            VMError::WriteToReadOnlyMemoryError(a) => {
+                format!("Failed to modify memory. Address {} is read only.", a)
+            }
             VMError::Unknown(s) => s.clone(),
         };
     }
@@ -1,7 +1,23 @@
 #![no_std]
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 
+pub mod assembler;
+pub mod cfg;
+pub mod checksum;
+pub mod debugger;
+pub mod disassembler;
+pub mod effects;
 pub mod error;
+pub mod linker;
+pub mod lint;
 pub mod loader;
+pub mod signature;
+pub mod source;
+pub mod stack_depth;
+pub mod stdlib;
+pub mod symbols;
 pub mod validator;
 pub mod vm;
+pub mod writer;
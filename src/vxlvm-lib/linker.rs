@@ -0,0 +1,517 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::error::{LinkError, LoaderError, ValidatorError};
+use crate::loader::{ChecksumAlgorithm, Loader};
+use crate::symbols::{write_string, Reader, SymbolTable};
+use crate::validator::BulkValidator;
+use crate::writer::Writer;
+use vxl_iset::instruction::Instruction;
+use vxl_iset::instruction_arguments::Address;
+
+/*
+The contents of a link section, integers and strings are encoded as in the symbols section.
+
+Number of exports, then for each export its name and instruction index
+Number of imports, then for each import its name, the number of sites and the instruction index
+of each site
+*/
+
+/// The symbols that a module provides to, and requires from, other modules.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct LinkTable {
+    exports: Vec<Export>,
+    imports: Vec<Import>,
+}
+
+/// A function that other modules can call, identified by the index of its first instruction.
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Export {
+    name: String,
+    index: u64,
+}
+
+/// A symbol from another module and the jumps or calls that target it.
+///
+/// The address operand of each site is replaced by the linker, so its value is ignored.
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Import {
+    name: String,
+    sites: Vec<u64>,
+}
+
+impl Export {
+    pub fn name(&self) -> &str {
+        return &self.name;
+    }
+
+    pub fn index(&self) -> u64 {
+        return self.index;
+    }
+}
+
+impl Import {
+    pub fn name(&self) -> &str {
+        return &self.name;
+    }
+
+    pub fn sites(&self) -> &[u64] {
+        return &self.sites;
+    }
+}
+
+impl LinkTable {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    pub fn add_export(&mut self, name: String, index: u64) {
+        self.exports.push(Export { name, index });
+    }
+
+    /// Records that the jump or call at `site` targets the symbol `name` from another module.
+    pub fn add_import(&mut self, name: String, site: u64) {
+        match self.imports.iter_mut().find(|import| import.name == name) {
+            Some(import) => import.sites.push(site),
+            None => self.imports.push(Import {
+                name,
+                sites: vec![site],
+            }),
+        }
+    }
+
+    pub fn exports(&self) -> &[Export] {
+        return &self.exports;
+    }
+
+    pub fn imports(&self) -> &[Import] {
+        return &self.imports;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.exports.is_empty() && self.imports.is_empty();
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        bytes.extend_from_slice(&(self.exports.len() as u64).to_le_bytes());
+
+        for export in &self.exports {
+            write_string(&export.name, &mut bytes);
+            bytes.extend_from_slice(&export.index.to_le_bytes());
+        }
+
+        bytes.extend_from_slice(&(self.imports.len() as u64).to_le_bytes());
+
+        for import in &self.imports {
+            write_string(&import.name, &mut bytes);
+            bytes.extend_from_slice(&(import.sites.len() as u64).to_le_bytes());
+
+            for site in &import.sites {
+                bytes.extend_from_slice(&site.to_le_bytes());
+            }
+        }
+
+        return bytes;
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, LoaderError> {
+        let mut reader = Reader::new(bytes, LoaderError::InvalidLinkTable);
+        let mut table = Self::new();
+
+        for _ in 0..reader.read_u64()? {
+            let name = reader.read_string()?;
+            let index = reader.read_u64()?;
+
+            table.add_export(name, index);
+        }
+
+        for _ in 0..reader.read_u64()? {
+            let name = reader.read_string()?;
+
+            for _ in 0..reader.read_u64()? {
+                let site = reader.read_u64()?;
+
+                table.add_import(name.clone(), site);
+            }
+        }
+
+        reader.finish()?;
+
+        return Ok(table);
+    }
+}
+
+/// A decoded program along with everything needed to link or write it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Module {
+    instructions: Vec<Instruction>,
+    entry: u64,
+    data_sections: Vec<Vec<u8>>,
+    symbols: Option<SymbolTable>,
+    link_table: LinkTable,
+}
+
+impl Module {
+    pub fn new(instructions: Vec<Instruction>, entry: u64) -> Self {
+        return Self {
+            instructions,
+            entry,
+            data_sections: Vec::new(),
+            symbols: None,
+            link_table: LinkTable::new(),
+        };
+    }
+
+    /// Decodes the instructions of a loaded file. The checksum is not checked.
+    pub fn from_loader(loader: Loader) -> Result<Self, ValidatorError> {
+        let data_sections = loader
+            .data_sections()
+            .into_iter()
+            .map(|section| section.to_vec())
+            .collect();
+        let symbols = loader.symbols().cloned();
+        let link_table = loader.link_table().cloned().unwrap_or_default();
+
+        let (header, instructions) = loader.to_instructions(BulkValidator::new())?;
+
+        return Ok(Self {
+            instructions,
+            entry: header.starting_offset(),
+            data_sections,
+            symbols,
+            link_table,
+        });
+    }
+
+    pub fn add_data_section(&mut self, bytes: Vec<u8>) -> u64 {
+        self.data_sections.push(bytes);
+
+        return self.data_sections.len() as u64 - 1;
+    }
+
+    pub fn set_symbols(&mut self, symbols: SymbolTable) {
+        self.symbols = Some(symbols);
+    }
+
+    pub fn link_table_mut(&mut self) -> &mut LinkTable {
+        return &mut self.link_table;
+    }
+
+    pub fn instructions(&self) -> &[Instruction] {
+        return &self.instructions;
+    }
+
+    pub fn entry(&self) -> u64 {
+        return self.entry;
+    }
+
+    pub fn data_sections(&self) -> &[Vec<u8>] {
+        return &self.data_sections;
+    }
+
+    pub fn symbols(&self) -> Option<&SymbolTable> {
+        return self.symbols.as_ref();
+    }
+
+    pub fn link_table(&self) -> &LinkTable {
+        return &self.link_table;
+    }
+
+    pub fn to_writer(&self, algorithm: ChecksumAlgorithm) -> Writer {
+        let mut writer = Writer::new(self.instructions.clone(), self.entry, algorithm);
+
+        for section in &self.data_sections {
+            writer.add_data_section(section.clone());
+        }
+
+        if let Some(symbols) = &self.symbols {
+            writer.set_symbols(symbols.clone());
+        }
+
+        if !self.link_table.is_empty() {
+            writer.set_link_table(self.link_table.clone());
+        }
+
+        return writer;
+    }
+}
+
+/// Combines modules into a single program that has no imports.
+///
+/// The instructions of each module follow those of the previous module, with every jump and
+/// call moved to match and each import replaced by the index of the export it names. The first
+/// module provides the entry point and is the only module that may have data sections, as the
+/// guest refers to them by index. Exports are kept and added to the symbols so that the program
+/// can be linked again.
+pub fn link(modules: &[Module]) -> Result<Module, LinkError> {
+    let mut bases = Vec::with_capacity(modules.len());
+    let mut exports = BTreeMap::new();
+    let mut instruction_count = 0;
+
+    for (i, module) in modules.iter().enumerate() {
+        if i > 0 && !module.data_sections.is_empty() {
+            return Err(LinkError::DataSectionInLibrary(i));
+        }
+
+        check_exports(module, i)?;
+
+        for export in &module.link_table.exports {
+            let index = export.index + instruction_count;
+
+            if exports.insert(export.name.clone(), index).is_some() {
+                return Err(LinkError::DuplicateSymbol(export.name.clone()));
+            }
+        }
+
+        bases.push(instruction_count);
+        instruction_count += module.instructions.len() as u64;
+    }
+
+    let mut linked = Module::new(Vec::with_capacity(instruction_count as usize), 0);
+    let mut symbols = SymbolTable::new();
+
+    for (i, (module, base)) in modules.iter().zip(bases).enumerate() {
+        linked
+            .instructions
+            .extend(place_module(module, i, base, &exports)?);
+
+        if let Some(module_symbols) = &module.symbols {
+            symbols.append(module_symbols, base);
+        }
+    }
+
+    let named: BTreeSet<u64> = symbols.functions().iter().map(|f| f.start()).collect();
+
+    for (name, index) in exports {
+        if !named.contains(&index) {
+            symbols.add_function(index, name.clone());
+        }
+
+        linked.link_table.add_export(name, index);
+    }
+
+    if let Some(first) = modules.first() {
+        linked.entry = first.entry;
+        linked.data_sections = first.data_sections.clone();
+    }
+
+    if !symbols.is_empty() {
+        linked.symbols = Some(symbols);
+    }
+
+    return Ok(linked);
+}
+
+/// Returns the instructions of a module that will begin at the instruction `base`, with its
+/// imports resolved using `exports`. The module is identified by `module_index` in errors.
+pub(crate) fn place_module(
+    module: &Module,
+    module_index: usize,
+    base: u64,
+    exports: &BTreeMap<String, u64>,
+) -> Result<Vec<Instruction>, LinkError> {
+    let mut resolved = BTreeMap::new();
+
+    for import in &module.link_table.imports {
+        let target = *exports
+            .get(&import.name)
+            .ok_or_else(|| LinkError::UnresolvedSymbol(import.name.clone()))?;
+
+        for site in &import.sites {
+            if *site >= module.instructions.len() as u64 {
+                return Err(LinkError::InvalidImportSite(module_index, *site));
+            }
+
+            resolved.insert(*site, target);
+        }
+    }
+
+    let mut instructions = Vec::with_capacity(module.instructions.len());
+
+    for (index, instruction) in module.instructions.iter().enumerate() {
+        let index = index as u64;
+
+        let instruction = match resolved.get(&index) {
+            Some(target) => with_target(*instruction, *target)
+                .ok_or(LinkError::InvalidImportSite(module_index, index))?,
+            None => relocate(*instruction, base)
+                .ok_or(LinkError::RelocationOverflow(module_index, index))?,
+        };
+
+        instructions.push(instruction);
+    }
+
+    return Ok(instructions);
+}
+
+/// Checks that every export of a module, identified by `module_index` in errors, is one of its
+/// instructions.
+pub(crate) fn check_exports(module: &Module, module_index: usize) -> Result<(), LinkError> {
+    for export in &module.link_table.exports {
+        if export.index >= module.instructions.len() as u64 {
+            return Err(LinkError::InvalidExport(module_index, export.index));
+        }
+    }
+
+    return Ok(());
+}
+
+/// Moves the target of a jump or call forward by `base`, returning `None` if it overflows.
+fn relocate(instruction: Instruction, base: u64) -> Option<Instruction> {
+    return match crate::effects::branch_target(&instruction) {
+        Some(target) => with_target(instruction, target.checked_add(base)?),
+        None => Some(instruction),
+    };
+}
+
+/// Replaces the target of a jump or call, returning `None` for any other instruction.
+fn with_target(instruction: Instruction, target: u64) -> Option<Instruction> {
+    let a = Address::from(target);
+
+    return match instruction {
+        Instruction::Jmp(_) => Some(Instruction::Jmp(a)),
+        Instruction::Jeq(_) => Some(Instruction::Jeq(a)),
+        Instruction::Jne(_) => Some(Instruction::Jne(a)),
+        Instruction::Jge(_) => Some(Instruction::Jge(a)),
+        Instruction::Jgt(_) => Some(Instruction::Jgt(a)),
+        Instruction::Jle(_) => Some(Instruction::Jle(a)),
+        Instruction::Jlt(_) => Some(Instruction::Jlt(a)),
+        Instruction::Call(_) => Some(Instruction::Call(a)),
+        _ => None,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use vxl_iset::instruction_arguments::Register;
+
+    fn call(target: u64) -> Instruction {
+        return Instruction::Call(Address::from(target));
+    }
+
+    /// main calls `square` from the library twice and jumps within itself.
+    fn modules() -> (Module, Module) {
+        let mut main = Module::new(
+            vec![
+                call(0),
+                Instruction::Jmp(Address::from(3u64)),
+                call(0),
+                Instruction::Halt,
+            ],
+            0,
+        );
+        main.link_table_mut().add_import(String::from("square"), 0);
+        main.link_table_mut().add_import(String::from("square"), 2);
+        main.link_table_mut().add_export(String::from("main"), 0);
+
+        let mut library = Module::new(
+            vec![
+                Instruction::Jmp(Address::from(1u64)),
+                Instruction::Muli(Register::ROU, Register::R0, Register::R0),
+                Instruction::Ret,
+            ],
+            0,
+        );
+        library
+            .link_table_mut()
+            .add_export(String::from("square"), 0);
+
+        return (main, library);
+    }
+
+    #[test]
+    fn test_link() {
+        let (main, library) = modules();
+        let linked = link(&[main, library]).unwrap();
+
+        assert_eq!(
+            linked.instructions(),
+            &[
+                call(4),
+                Instruction::Jmp(Address::from(3u64)),
+                call(4),
+                Instruction::Halt,
+                Instruction::Jmp(Address::from(5u64)),
+                Instruction::Muli(Register::ROU, Register::R0, Register::R0),
+                Instruction::Ret,
+            ]
+        );
+        assert!(linked.link_table().imports().is_empty());
+        assert_eq!(linked.link_table().exports().len(), 2);
+        assert_eq!(
+            linked.symbols().unwrap().function_at(5).unwrap().name(),
+            "square"
+        );
+    }
+
+    #[test]
+    fn test_link_errors() {
+        let (main, library) = modules();
+
+        assert_eq!(
+            link(core::slice::from_ref(&main)),
+            Err(LinkError::UnresolvedSymbol(String::from("square")))
+        );
+        assert_eq!(
+            link(&[main.clone(), library.clone(), library.clone()]),
+            Err(LinkError::DuplicateSymbol(String::from("square")))
+        );
+
+        let mut data_library = library.clone();
+        data_library.add_data_section(vec![1]);
+        assert_eq!(
+            link(&[main.clone(), data_library]),
+            Err(LinkError::DataSectionInLibrary(1))
+        );
+
+        let mut invalid_site = main.clone();
+        invalid_site
+            .link_table_mut()
+            .add_import(String::from("square"), 3);
+        assert_eq!(
+            link(&[invalid_site, library.clone()]),
+            Err(LinkError::InvalidImportSite(0, 3))
+        );
+
+        let mut missing_site = main.clone();
+        missing_site
+            .link_table_mut()
+            .add_import(String::from("square"), 9);
+        assert_eq!(
+            link(&[missing_site, library.clone()]),
+            Err(LinkError::InvalidImportSite(0, 9))
+        );
+
+        let mut invalid_export = library.clone();
+        invalid_export
+            .link_table_mut()
+            .add_export(String::from("cube"), u64::MAX);
+        assert_eq!(
+            link(&[main.clone(), invalid_export]),
+            Err(LinkError::InvalidExport(1, u64::MAX))
+        );
+
+        // The jump in the library can't be moved after main.
+        let overflowing = Module::new(vec![Instruction::Jmp(Address::from(u64::MAX))], 0);
+        assert_eq!(
+            link(&[main, library, overflowing]),
+            Err(LinkError::RelocationOverflow(2, 0))
+        );
+    }
+
+    #[test]
+    fn test_write_module() {
+        let (main, _library) = modules();
+        let bytes = main.to_writer(ChecksumAlgorithm::Sha3_224).to_bytes();
+
+        let loader = Loader::load_bytes(&bytes).unwrap();
+        assert_eq!(loader.link_table(), Some(main.link_table()));
+        assert_eq!(Module::from_loader(loader).unwrap(), main);
+    }
+}
@@ -0,0 +1,385 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::cfg::{ControlFlowGraph, EdgeKind};
+use crate::disassembler::{mnemonic, register_name};
+use crate::effects::written_registers;
+use crate::stdlib::StandardLibrary;
+use vxl_iset::instruction::Instruction;
+use vxl_iset::instruction_arguments::Register;
+
+const REGISTER_COUNT: usize = 16;
+
+/// What each register is known to hold, `None` if it could hold anything.
+type RegisterTypes = [Option<ValueType>; REGISTER_COUNT];
+
+/// The kinds of value that registers hold, which the VM doesn't distinguish.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum ValueType {
+    Int,
+    Float,
+    /// The address of a block of heap memory.
+    Handle,
+}
+
+/// An instruction using a register that holds the wrong type of value on some path to it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TypeMismatch {
+    index: usize,
+    instruction: Instruction,
+    register: Register,
+    expected: ValueType,
+    found: ValueType,
+}
+
+impl ValueType {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            ValueType::Int => "an int",
+            ValueType::Float => "a float",
+            ValueType::Handle => "a heap handle",
+        };
+    }
+}
+
+impl TypeMismatch {
+    /// The index of the instruction.
+    pub fn index(&self) -> usize {
+        return self.index;
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        return self.instruction;
+    }
+
+    pub fn register(&self) -> Register {
+        return self.register;
+    }
+
+    pub fn expected(&self) -> ValueType {
+        return self.expected;
+    }
+
+    pub fn found(&self) -> ValueType {
+        return self.found;
+    }
+
+    pub fn describe(&self) -> String {
+        return format!(
+            "Instruction {} ({}) uses {} as {} but it holds {}.",
+            self.index,
+            mnemonic(self.instruction),
+            register_name(self.register),
+            self.expected.name(),
+            self.found.name()
+        );
+    }
+}
+
+/// Tracks whether each register holds an int, float or heap handle through every function of a
+/// program, reporting each use of a register that holds the wrong type.
+///
+/// A register is only given a type where every path to it agrees. Functions start with nothing
+/// known and calls may change any register, as may `rou` during a syscall.
+pub fn check_types(instructions: &[Instruction], entry: u64) -> Vec<TypeMismatch> {
+    let graph = ControlFlowGraph::new(instructions, entry);
+    let mut states: Vec<Option<RegisterTypes>> = vec![None; graph.blocks().len()];
+    let mut pending: Vec<(usize, RegisterTypes)> = graph
+        .functions()
+        .iter()
+        .filter_map(|function| graph.block_starting_at(function.start()))
+        .map(|block| (block, [None; REGISTER_COUNT]))
+        .collect();
+
+    while let Some((block, types)) = pending.pop() {
+        let merged = match states[block] {
+            Some(previous) => merge(&previous, &types),
+            None => types,
+        };
+
+        if states[block] == Some(merged) {
+            continue;
+        }
+
+        states[block] = Some(merged);
+
+        let mut types = merged;
+
+        for index in graph.blocks()[block].range() {
+            apply(&instructions[index], &mut types);
+        }
+
+        for edge in graph.successors(block) {
+            if matches!(edge.kind(), EdgeKind::FallThrough | EdgeKind::Branch) {
+                pending.push((edge.to(), types));
+            }
+        }
+    }
+
+    let mut mismatches = Vec::new();
+
+    for (block, types) in graph.blocks().iter().zip(states) {
+        // Blocks that can't be reached are skipped.
+        let mut types = match types {
+            Some(types) => types,
+            None => continue,
+        };
+
+        for index in block.range() {
+            let instruction = instructions[index];
+
+            for (register, expected) in uses(&instruction) {
+                match types[register as usize] {
+                    Some(found) if found != expected => mismatches.push(TypeMismatch {
+                        index,
+                        instruction,
+                        register,
+                        expected,
+                        found,
+                    }),
+                    _ => (),
+                }
+            }
+
+            apply(&instruction, &mut types);
+        }
+    }
+
+    return mismatches;
+}
+
+/// Forgets the type of any register that differs between the two.
+fn merge(a: &RegisterTypes, b: &RegisterTypes) -> RegisterTypes {
+    let mut merged = *a;
+
+    for (merged, b) in merged.iter_mut().zip(b) {
+        if *merged != *b {
+            *merged = None;
+        }
+    }
+
+    return merged;
+}
+
+/// The registers an instruction reads and the type it expects each to hold. Registers that can
+/// reasonably hold any type, such as the values stored in memory, are not included.
+fn uses(instruction: &Instruction) -> Vec<(Register, ValueType)> {
+    use ValueType::{Float, Handle, Int};
+
+    return match *instruction {
+        Instruction::Malloc(_, r1) => vec![(r1, Int)],
+        Instruction::Free(r) => vec![(r, Handle)],
+        Instruction::Setb(r, r1, _) | Instruction::Seti(r, r1, _) => {
+            vec![(r, Handle), (r1, Int)]
+        }
+        Instruction::Isetb(_, r, _) | Instruction::Iseti(_, r, _) => vec![(r, Handle)],
+        Instruction::Getb(_, r1, r2) | Instruction::Geti(_, r1, r2) => {
+            vec![(r1, Handle), (r2, Int)]
+        }
+        Instruction::Igetb(_, _, r1)
+        | Instruction::Igeti(_, _, r1)
+        | Instruction::Last(_, r1)
+        | Instruction::Length(_, r1)
+        | Instruction::Clone(_, r1) => vec![(r1, Handle)],
+        Instruction::Copy(r, r1, r2, r3, r4) => {
+            vec![(r, Handle), (r1, Int), (r2, Handle), (r3, Int), (r4, Int)]
+        }
+        Instruction::Copyi(_, _, _, r, r1) | Instruction::Swpar(r, r1) => {
+            vec![(r, Handle), (r1, Handle)]
+        }
+        Instruction::Addi(_, r1, r2)
+        | Instruction::Subi(_, r1, r2)
+        | Instruction::Muli(_, r1, r2)
+        | Instruction::Divi(_, r1, r2)
+        | Instruction::Modi(_, r1, r2)
+        | Instruction::Addu(_, r1, r2)
+        | Instruction::Subu(_, r1, r2)
+        | Instruction::Mulu(_, r1, r2)
+        | Instruction::Divu(_, r1, r2)
+        | Instruction::Modu(_, r1, r2)
+        | Instruction::And(_, r1, r2)
+        | Instruction::Or(_, r1, r2)
+        | Instruction::Xor(_, r1, r2) => vec![(r1, Int), (r2, Int)],
+        Instruction::Addf(_, r1, r2)
+        | Instruction::Subf(_, r1, r2)
+        | Instruction::Mulf(_, r1, r2)
+        | Instruction::Divf(_, r1, r2) => vec![(r1, Float), (r2, Float)],
+        Instruction::Rotl(r, r1)
+        | Instruction::Rotr(r, r1)
+        | Instruction::Sll(r, r1)
+        | Instruction::Srl(r, r1)
+        | Instruction::Cmpi(r, r1) => vec![(r, Int), (r1, Int)],
+        Instruction::Rotli(_, r)
+        | Instruction::Rotri(_, r)
+        | Instruction::Slli(_, r)
+        | Instruction::Srli(_, r)
+        | Instruction::Not(r)
+        | Instruction::I2f(r) => vec![(r, Int)],
+        Instruction::Cmpf(r, r1) => vec![(r, Float), (r1, Float)],
+        Instruction::F2i(r) => vec![(r, Float)],
+        _ => Vec::new(),
+    };
+}
+
+/// Updates the types of the registers that an instruction writes.
+fn apply(instruction: &Instruction, types: &mut RegisterTypes) {
+    let result = match *instruction {
+        Instruction::Swpr(r, r1) => {
+            types.swap(r as usize, r1 as usize);
+            return;
+        }
+        Instruction::Call(_) => {
+            *types = [None; REGISTER_COUNT];
+            return;
+        }
+        // Calls code that isn't known until it runs, which may write any register.
+        Instruction::Syscall(i) if u64::from(i) == StandardLibrary::CALL => {
+            *types = [None; REGISTER_COUNT];
+            return;
+        }
+        Instruction::Syscall(_) => {
+            types[Register::ROU as usize] = None;
+            return;
+        }
+        Instruction::Mov(_, r1) => types[r1 as usize],
+        Instruction::Ldf(_, _)
+        | Instruction::Addf(_, _, _)
+        | Instruction::Subf(_, _, _)
+        | Instruction::Mulf(_, _, _)
+        | Instruction::Divf(_, _, _)
+        | Instruction::I2f(_) => Some(ValueType::Float),
+        Instruction::Malloc(_, _) | Instruction::Malloci(_, _) | Instruction::Clone(_, _) => {
+            Some(ValueType::Handle)
+        }
+        // Whole values loaded from the stack or memory could be anything.
+        Instruction::Pop(_)
+        | Instruction::Sget(_, _)
+        | Instruction::Geti(_, _, _)
+        | Instruction::Igeti(_, _, _)
+        | Instruction::Last(_, _) => None,
+        _ => Some(ValueType::Int),
+    };
+
+    for register in written_registers(instruction) {
+        types[register as usize] = result;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use vxl_iset::instruction_arguments::{Address, Immediate};
+
+    #[test]
+    fn test_mismatches() {
+        // 0: ldi 2, $r0
+        // 1: ldf 1.5, $r1
+        // 2: addf $r2, $r0, $r1
+        // 3: cmpi $r1, $r0
+        // 4: getb $r3, $r0, $r0
+        // 5: i2f $r1
+        // 6: halt
+        let instructions = vec![
+            Instruction::Ldi(Immediate::from(2i64), Register::R0),
+            Instruction::Ldf(Immediate::from(1.5f64), Register::R1),
+            Instruction::Addf(Register::R2, Register::R0, Register::R1),
+            Instruction::Cmpi(Register::R1, Register::R0),
+            Instruction::Getb(Register::R3, Register::R0, Register::R0),
+            Instruction::I2f(Register::R1),
+            Instruction::Halt,
+        ];
+
+        let mismatches = check_types(&instructions, 0);
+        let found: Vec<(usize, Register, ValueType)> = mismatches
+            .iter()
+            .map(|m| (m.index(), m.register(), m.expected()))
+            .collect();
+
+        assert_eq!(
+            found,
+            vec![
+                (2, Register::R0, ValueType::Float),
+                (3, Register::R1, ValueType::Int),
+                (4, Register::R0, ValueType::Handle),
+                (5, Register::R1, ValueType::Int),
+            ]
+        );
+        assert_eq!(
+            mismatches[0].describe(),
+            "Instruction 2 (addf) uses $r0 as a float but it holds an int."
+        );
+    }
+
+    #[test]
+    fn test_paths() {
+        // 0: malloci 8, $r0
+        // 1: jeq 3
+        // 2: ldi 0, $r0
+        // 3: free $r0
+        // 4: ldf 2.0, $r1
+        // 5: call 8
+        // 6: addu $r1, $r1, $r1
+        // 7: halt
+        // 8: ret
+        let instructions = vec![
+            Instruction::Malloci(Immediate::from(8u64), Register::R0),
+            Instruction::Jeq(Address::from(3u64)),
+            Instruction::Ldi(Immediate::from(0u64), Register::R0),
+            Instruction::Free(Register::R0),
+            Instruction::Ldf(Immediate::from(2.0f64), Register::R1),
+            Instruction::Call(Address::from(8u64)),
+            Instruction::Addu(Register::R1, Register::R1, Register::R1),
+            Instruction::Halt,
+            Instruction::Ret,
+        ];
+
+        // The paths to the free disagree and the call may have changed $r1.
+        assert_eq!(check_types(&instructions, 0), vec![]);
+
+        // 0: ldf 1.0, $r0
+        // 1: mov $r1, $r0
+        // 2: swpr $r1, $r2
+        // 3: f2i $r2
+        // 4: not $r2
+        // 5: jmp 1
+        let looping = vec![
+            Instruction::Ldf(Immediate::from(1.0f64), Register::R0),
+            Instruction::Mov(Register::R1, Register::R0),
+            Instruction::Swpr(Register::R1, Register::R2),
+            Instruction::F2i(Register::R2),
+            Instruction::Not(Register::R2),
+            Instruction::Jmp(Address::from(1u64)),
+        ];
+
+        assert_eq!(check_types(&looping, 0), vec![]);
+    }
+
+    #[test]
+    fn test_syscall() {
+        // 0: ldf 2.0, $r1
+        // 1: syscall 0x104
+        // 2: addu $r2, $r1, $r1
+        // 3: syscall 0x107
+        // 4: addu $r2, $r1, $r1
+        // 5: halt
+        let instructions = vec![
+            Instruction::Ldf(Immediate::from(2.0f64), Register::R1),
+            Instruction::Syscall(Immediate::from(StandardLibrary::DATA_SECTION)),
+            Instruction::Addu(Register::R2, Register::R1, Register::R1),
+            Instruction::Syscall(Immediate::from(StandardLibrary::CALL)),
+            Instruction::Addu(Register::R2, Register::R1, Register::R1),
+            Instruction::Halt,
+        ];
+
+        // Only the call can have changed $r1.
+        let found: Vec<usize> = check_types(&instructions, 0)
+            .iter()
+            .map(|m| m.index())
+            .collect();
+
+        assert_eq!(found, vec![2, 2]);
+    }
+}
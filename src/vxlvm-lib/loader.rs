@@ -1,27 +1,442 @@
+use alloc::vec;
 use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::ops::Range;
+use digest::consts::U28;
 use digest::Digest;
+use sha2::Sha512;
 
-use crate::error::{LoaderError, ValidatorError};
-use crate::validator::Validator;
+use crate::checksum::{Crc32, NoChecksum};
+use crate::error::{LoaderError, ValidatorError, VerificationError};
+use crate::linker::{LinkTable, Module};
+use crate::signature::{SignatureTrailer, TrustedKeys};
+use crate::source::ByteSource;
+use crate::symbols::SymbolTable;
+use crate::validator::{verify_program, BulkValidator, Validator};
+use crate::writer::{encode_header, encoded_size};
 use vxl_iset::instruction::Instruction;
 use vxl_iset::vxl_file::VXLHeader;
 
+/// The hash algorithm used for the checksum of a file, selected by the lowest 3 bits of the
+/// header flags.
+///
+/// Hashes longer than the checksum field are truncated and shorter ones are followed by zeros.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum ChecksumAlgorithm {
+    Sha2_224,
+    Sha3_224,
+    /// SHA-256 truncated to 224 bits.
+    Sha256,
+    Blake2b224,
+    /// Fast to compute but only detects accidental corruption, intended for development builds.
+    Crc32,
+    /// No checksum at all, the checksum field must be zero. Intended for development builds.
+    Unchecked,
+}
+
+impl ChecksumAlgorithm {
+    /// The bits of the header flags that select the algorithm. Earlier versions of the format
+    /// only used the lowest bit, so `Sha2_224` and `Sha3_224` keep their original flags.
+    pub const FLAGS_MASK: u8 = 0b0000_0111;
+
+    pub const ALL: [ChecksumAlgorithm; 6] = [
+        ChecksumAlgorithm::Sha2_224,
+        ChecksumAlgorithm::Sha3_224,
+        ChecksumAlgorithm::Sha256,
+        ChecksumAlgorithm::Blake2b224,
+        ChecksumAlgorithm::Crc32,
+        ChecksumAlgorithm::Unchecked,
+    ];
+
+    /// Returns `None` if the flags select an unknown algorithm.
+    pub fn from_flags(flags: u8) -> Option<Self> {
+        return Self::ALL
+            .iter()
+            .copied()
+            .find(|algorithm| algorithm.flags() == flags & Self::FLAGS_MASK);
+    }
+
+    /// The header flags that select this algorithm.
+    pub fn flags(&self) -> u8 {
+        return match self {
+            ChecksumAlgorithm::Sha2_224 => 0,
+            ChecksumAlgorithm::Sha3_224 => VXLHeader::CHECKSUM_MASK,
+            ChecksumAlgorithm::Sha256 => 2,
+            ChecksumAlgorithm::Blake2b224 => 3,
+            ChecksumAlgorithm::Crc32 => 4,
+            ChecksumAlgorithm::Unchecked => 5,
+        };
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            ChecksumAlgorithm::Sha2_224 => "SHA2-224",
+            ChecksumAlgorithm::Sha3_224 => "SHA3-224",
+            ChecksumAlgorithm::Sha256 => "SHA-256",
+            ChecksumAlgorithm::Blake2b224 => "BLAKE2b-224",
+            ChecksumAlgorithm::Crc32 => "CRC32",
+            ChecksumAlgorithm::Unchecked => "none",
+        };
+    }
+
+    /// Finds an algorithm by its name, ignoring case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        return Self::ALL
+            .iter()
+            .copied()
+            .find(|algorithm| algorithm.name().eq_ignore_ascii_case(name));
+    }
+
+    pub fn checksum(&self, bytes: &[u8]) -> [u8; VXLHeader::HEADER_CHECKSUM_SIZE] {
+        return match self {
+            ChecksumAlgorithm::Sha2_224 => compute_checksum(sha2::Sha224::new(), bytes),
+            ChecksumAlgorithm::Sha3_224 => compute_checksum(sha3::Sha3_224::new(), bytes),
+            ChecksumAlgorithm::Sha256 => compute_checksum(sha2::Sha256::new(), bytes),
+            ChecksumAlgorithm::Blake2b224 => compute_checksum(Blake2b224::new(), bytes),
+            ChecksumAlgorithm::Crc32 => compute_checksum(Crc32::new(), bytes),
+            ChecksumAlgorithm::Unchecked => compute_checksum(NoChecksum::new(), bytes),
+        };
+    }
+}
+
+type Blake2b224 = blake2::Blake2b<U28>;
+
+fn compute_checksum<D: Digest>(
+    mut digest: D,
+    bytes: &[u8],
+) -> [u8; VXLHeader::HEADER_CHECKSUM_SIZE] {
+    digest.update(bytes);
+
+    return fit_checksum(&digest.finalize());
+}
+
+/// Fits the output of a hash to the checksum field of the header.
+fn fit_checksum(output: &[u8]) -> [u8; VXLHeader::HEADER_CHECKSUM_SIZE] {
+    let mut checksum = [0u8; VXLHeader::HEADER_CHECKSUM_SIZE];
+    let length = output.len().min(checksum.len());
+
+    checksum[..length].copy_from_slice(&output[..length]);
+
+    return checksum;
+}
+
+/// The kind of each entry in the section table of a sectioned file.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum SectionKind {
+    /// The instructions, exactly one code section is required.
+    Code,
+    /// A blob that is preloaded into a read only block of memory.
+    Data,
+    /// An optional `SymbolTable`, at most one is allowed.
+    Symbols,
+    /// An optional `LinkTable`, at most one is allowed.
+    Link,
+}
+
+impl SectionKind {
+    pub fn from_u8(kind: u8) -> Option<Self> {
+        return match kind {
+            0 => Some(SectionKind::Code),
+            1 => Some(SectionKind::Data),
+            2 => Some(SectionKind::Symbols),
+            3 => Some(SectionKind::Link),
+            _ => None,
+        };
+    }
+
+    pub fn as_u8(&self) -> u8 {
+        return match self {
+            SectionKind::Code => 0,
+            SectionKind::Data => 1,
+            SectionKind::Symbols => 2,
+            SectionKind::Link => 3,
+        };
+    }
+}
+
+/*
+In a sectioned file the program bytes begin with a section table.
+
+Byte Offset - Value
+
+0x0 - Number of sections (Little endian)
+0x8 - For each section, the kind (1 byte) followed by its length in bytes (Little endian)
+
+The contents of each section follow the table in the same order and fill the rest of the file.
+*/
+/// The ranges of the program bytes occupied by each section.
+struct SectionRanges {
+    code: Range<usize>,
+    data: Vec<Range<usize>>,
+    symbols: Option<Range<usize>>,
+    link: Option<Range<usize>>,
+}
+
+/// How files are checked when they are loaded to be run, including modules loaded by a running
+/// program through `StandardLibrary::LOAD_MODULE`.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct LoadPolicy {
+    /// Accept files built without a checksum.
+    allow_unchecked: bool,
+    /// Refuse files that are not signed by one of these keys.
+    trusted_keys: Option<TrustedKeys>,
+}
+
+impl LoadPolicy {
+    pub fn new(allow_unchecked: bool, trusted_keys: Option<TrustedKeys>) -> Self {
+        return Self {
+            allow_unchecked,
+            trusted_keys,
+        };
+    }
+
+    pub fn allow_unchecked(&self) -> bool {
+        return self.allow_unchecked;
+    }
+
+    pub fn trusted_keys(&self) -> Option<&TrustedKeys> {
+        return self.trusted_keys.as_ref();
+    }
+
+    /// Loads a file as `Loader::load_trusted` or `Loader::load_bytes` does and checks its
+    /// checksum, refusing files that are not accepted by the policy.
+    pub fn load_bytes(&self, bytes: &[u8]) -> Result<Loader, LoaderError> {
+        let loader = match &self.trusted_keys {
+            Some(keys) => Loader::load_trusted(bytes, keys)?,
+            None => Loader::load_bytes(bytes)?,
+        };
+
+        self.check_flags(loader.header.flags())?;
+        loader.validate()?;
+
+        return Ok(loader);
+    }
+
+    /// Loads a file from a `ByteSource` as `Loader::load_from_trusted` or `Loader::load_from`
+    /// does, refusing files that are not accepted by the policy.
+    pub fn load_from<S: ByteSource, V: Validator>(
+        &self,
+        source: S,
+        validator: V,
+    ) -> Result<(VXLHeader, Module), VerificationError> {
+        let (header, module) = match &self.trusted_keys {
+            Some(keys) => Loader::load_from_trusted(source, validator, keys),
+            None => Loader::load_from(source, validator),
+        }?;
+
+        self.check_flags(header.flags())
+            .map_err(VerificationError::Loader)?;
+
+        return Ok((header, module));
+    }
+
+    fn check_flags(&self, flags: u8) -> Result<(), LoaderError> {
+        if !self.allow_unchecked
+            && ChecksumAlgorithm::from_flags(flags) == Some(ChecksumAlgorithm::Unchecked)
+        {
+            return Err(LoaderError::UncheckedNotAllowed);
+        }
+
+        return Ok(());
+    }
+}
+
 #[derive(Clone, PartialEq, Debug)]
 pub struct Loader {
     header: VXLHeader,
     program_bytes: Vec<u8>,
+    /// The range of `program_bytes` containing instructions.
+    code: Range<usize>,
+    data: Vec<Range<usize>>,
+    symbols: Option<SymbolTable>,
+    link_table: Option<LinkTable>,
+    signature: Option<SignatureTrailer>,
 }
 
 impl Loader {
+    /// The first version with a section table, earlier versions contain only instructions.
+    pub const SECTIONED_VERSION: u8 = 1;
+    pub const SUPPORTED_VERSIONS: [u8; 2] = [0, Self::SECTIONED_VERSION];
+    /// Set in the header flags of files followed by a `SignatureTrailer`.
+    pub const SIGNED_FLAG: u8 = 0b0000_1000;
+
+    const SECTION_ENTRY_SIZE: usize = 9;
+
     pub fn load_bytes(bytes: &[u8]) -> Result<Self, LoaderError> {
         if bytes.len() < VXLHeader::HEADER_SIZE {
             return Err(LoaderError::NotEnoughBytesForHeader);
         }
 
+        let (loader, problems) = Self::parse(bytes);
+
+        if let Some(problem) = problems.first() {
+            return Err(*problem);
+        }
+
+        return Ok(loader);
+    }
+
+    /// Loads a file as `load_bytes` does, refusing files that are not signed by a trusted key.
+    pub fn load_trusted(bytes: &[u8], keys: &TrustedKeys) -> Result<Self, LoaderError> {
+        let loader = Self::load_bytes(bytes)?;
+        loader.verify_signature(keys)?;
+
+        return Ok(loader);
+    }
+
+    /// Reads the header fields without checking them, so that files with an invalid header can
+    /// still be inspected.
+    pub fn read_bytes(bytes: &[u8]) -> Result<Self, LoaderError> {
+        if bytes.len() < VXLHeader::HEADER_SIZE {
+            return Err(LoaderError::NotEnoughBytesForHeader);
+        }
+
+        return Ok(Self::parse(bytes).0);
+    }
+
+    /// Checks every part of a file, reporting each problem found rather than stopping at the
+    /// first.
+    ///
+    /// The header fields and checksum are checked even if other header fields are invalid. The
+    /// program can't be decoded beyond an invalid instruction so at most one decoding problem is
+    /// reported. Once the whole program is decoded it is checked with `verify_program`.
+    pub fn verify_bytes(bytes: &[u8]) -> Vec<VerificationError> {
+        if bytes.len() < VXLHeader::HEADER_SIZE {
+            return vec![VerificationError::Loader(
+                LoaderError::NotEnoughBytesForHeader,
+            )];
+        }
+
+        let (loader, problems) = Self::parse(bytes);
+        let mut problems: Vec<VerificationError> = problems
+            .into_iter()
+            .map(VerificationError::Loader)
+            .collect();
+
+        // An unknown algorithm has already been reported.
+        if loader.checksum_algorithm().is_some() {
+            if let Err(e) = loader.validate() {
+                problems.push(VerificationError::Loader(e));
+            }
+        }
+
+        let code_start = loader.code.start;
+        let mut validator = BulkValidator::with_bytes(loader.code_bytes().to_vec());
+        let mut instructions = Vec::new();
+
+        while validator.has_next_byte() {
+            let offset = code_start + validator.position();
+
+            match validator.take_next_instruction() {
+                Ok(instruction) => instructions.push(instruction),
+                Err(e) => {
+                    problems.push(VerificationError::Validator(offset, e));
+                    return problems;
+                }
+            }
+        }
+
+        // Without a section table the code can't be found.
+        let missing_code = problems.iter().any(|problem| {
+            matches!(
+                problem,
+                VerificationError::Loader(
+                    LoaderError::InvalidSectionTable | LoaderError::UnknownSectionKind(_)
+                )
+            )
+        });
+
+        if !missing_code {
+            let entry = loader.header.starting_offset();
+
+            problems.extend(
+                verify_program(&instructions, entry)
+                    .into_iter()
+                    .map(VerificationError::Program),
+            );
+        }
+
+        return problems;
+    }
+
+    /// Reads each header field, collecting any problems in the order that `load_bytes` reports
+    /// them. `bytes` must be at least as long as the header.
+    fn parse(bytes: &[u8]) -> (Self, Vec<LoaderError>) {
+        let (header, mut problems) = Self::parse_header(bytes);
+        let mut program_end = bytes.len();
+        let mut signature = None;
+
+        if header.flags() & Self::SIGNED_FLAG != 0 {
+            if bytes.len() >= VXLHeader::HEADER_SIZE + SignatureTrailer::SIZE {
+                program_end -= SignatureTrailer::SIZE;
+
+                let trailer = bytes[program_end..].try_into().unwrap();
+                signature = Some(SignatureTrailer::from_bytes(trailer));
+            } else {
+                problems.push(LoaderError::MissingSignature);
+            }
+        }
+
+        let program_bytes = bytes[VXLHeader::HEADER_SIZE..program_end].to_vec();
+
+        if program_bytes.len() as u64 != header.file_size() {
+            problems.push(LoaderError::NonMatchingFileSize);
+        }
+
+        let mut code = 0..program_bytes.len();
+        let mut data = Vec::new();
+        let mut symbols = None;
+        let mut link_table = None;
+
+        if header.version() >= Self::SECTIONED_VERSION {
+            match Self::parse_sections(&program_bytes) {
+                Ok(sections) => {
+                    code = sections.code;
+                    data = sections.data;
+
+                    if let Some(range) = sections.symbols {
+                        match SymbolTable::from_bytes(&program_bytes[range]) {
+                            Ok(table) => symbols = Some(table),
+                            Err(e) => problems.push(e),
+                        }
+                    }
+
+                    if let Some(range) = sections.link {
+                        match LinkTable::from_bytes(&program_bytes[range]) {
+                            Ok(table) => link_table = Some(table),
+                            Err(e) => problems.push(e),
+                        }
+                    }
+                }
+                Err(e) => {
+                    problems.push(e);
+                    code = 0..0;
+                }
+            }
+        }
+
+        let ldr = Self {
+            header,
+            program_bytes,
+            code,
+            data,
+            symbols,
+            link_table,
+            signature,
+        };
+
+        return (ldr, problems);
+    }
+
+    /// Reads the header from the start of `bytes`, which must be at least as long as the header.
+    fn parse_header(bytes: &[u8]) -> (VXLHeader, Vec<LoaderError>) {
+        let mut problems = Vec::new();
+
         // Check the magic
         for i in 0..VXLHeader::MAGIC.len() {
             if bytes[i] != VXLHeader::MAGIC[i] {
-                return Err(LoaderError::InvalidMagic);
+                problems.push(LoaderError::InvalidMagic);
+                break;
             }
         }
 
@@ -33,8 +448,8 @@ impl Loader {
         let flags;
         let mut checksum = [0u8; VXLHeader::HEADER_CHECKSUM_SIZE];
 
-        if !VXLHeader::SUPPORTED_VERSIONS.contains(&version) {
-            return Err(LoaderError::UnsupportedVersion);
+        if !Self::SUPPORTED_VERSIONS.contains(&version) {
+            problems.push(LoaderError::UnsupportedVersion);
         }
 
         current_index += 1;
@@ -58,6 +473,10 @@ impl Loader {
         flags = bytes[current_index];
         current_index += 1;
 
+        if ChecksumAlgorithm::from_flags(flags).is_none() {
+            problems.push(LoaderError::UnknownChecksumAlgorithm(flags));
+        }
+
         for i in 0..VXLHeader::HEADER_CHECKSUM_SIZE {
             checksum[i] = bytes[current_index];
             current_index += 1;
@@ -65,50 +484,156 @@ impl Loader {
 
         // Check the trailing byte
         if bytes[current_index] != VXLHeader::END_HEADER_BYTE {
-            return Err(LoaderError::InvalidEndHeaderMarker);
+            problems.push(LoaderError::InvalidEndHeaderMarker);
         }
 
-        current_index += 1;
+        let header = VXLHeader::new(version, file_size, starting_offset, flags, checksum);
 
-        let program_bytes = bytes[current_index..].to_vec();
+        return (header, problems);
+    }
 
-        if program_bytes.len() as u64 != file_size {
-            return Err(LoaderError::NonMatchingFileSize);
+    fn parse_sections(program_bytes: &[u8]) -> Result<SectionRanges, LoaderError> {
+        let count = program_bytes
+            .get(0..8)
+            .ok_or(LoaderError::InvalidSectionTable)?;
+        let table_end =
+            Self::section_table_end(count.try_into().unwrap(), program_bytes.len() as u64)?;
+
+        return Self::parse_section_table(&program_bytes[8..table_end], program_bytes.len() as u64);
+    }
+
+    /// Returns the size of a section table from the number of sections, checking that it fits in
+    /// the program bytes.
+    fn section_table_end(count: [u8; 8], program_size: u64) -> Result<usize, LoaderError> {
+        return Ok(u64::from_le_bytes(count)
+            .checked_mul(Self::SECTION_ENTRY_SIZE as u64)
+            .and_then(|size| size.checked_add(8))
+            .filter(|end| *end <= program_size)
+            .ok_or(LoaderError::InvalidSectionTable)? as usize);
+    }
+
+    /// Finds the range of each section from the entries of the section table, which must describe
+    /// every remaining program byte.
+    fn parse_section_table(
+        entries: &[u8],
+        program_size: u64,
+    ) -> Result<SectionRanges, LoaderError> {
+        let mut code = None;
+        let mut data = Vec::new();
+        let mut symbols = None;
+        let mut link = None;
+        let mut start = 8 + entries.len();
+
+        for entry in entries.chunks(Self::SECTION_ENTRY_SIZE) {
+            let kind =
+                SectionKind::from_u8(entry[0]).ok_or(LoaderError::UnknownSectionKind(entry[0]))?;
+            let length = u64::from_le_bytes(entry[1..].try_into().unwrap());
+
+            let end = (start as u64)
+                .checked_add(length)
+                .filter(|end| *end <= program_size)
+                .ok_or(LoaderError::InvalidSectionTable)? as usize;
+
+            match kind {
+                SectionKind::Code if code.is_none() => code = Some(start..end),
+                SectionKind::Code => return Err(LoaderError::InvalidSectionTable),
+                SectionKind::Data => data.push(start..end),
+                SectionKind::Symbols if symbols.is_none() => symbols = Some(start..end),
+                SectionKind::Symbols => return Err(LoaderError::InvalidSectionTable),
+                SectionKind::Link if link.is_none() => link = Some(start..end),
+                SectionKind::Link => return Err(LoaderError::InvalidSectionTable),
+            }
+
+            start = end;
         }
 
-        let ldr = Self {
-            header: VXLHeader::new(version, file_size, starting_offset, flags, checksum),
-            program_bytes,
-        };
+        if start as u64 != program_size {
+            return Err(LoaderError::InvalidSectionTable);
+        }
 
-        return Ok(ldr);
+        return Ok(SectionRanges {
+            code: code.ok_or(LoaderError::InvalidSectionTable)?,
+            data,
+            symbols,
+            link,
+        });
     }
 
     pub fn validate(&self) -> Result<(), LoaderError> {
-        if self.header.flags() & VXLHeader::CHECKSUM_MASK == 0 {
-            return self.verify_checksum(sha2::Sha224::new());
-        } else {
-            return self.verify_checksum(sha3::Sha3_224::new());
-        }
+        return match self.checksum_algorithm() {
+            Some(ChecksumAlgorithm::Sha2_224) => self.verify_checksum(sha2::Sha224::new()),
+            Some(ChecksumAlgorithm::Sha3_224) => self.verify_checksum(sha3::Sha3_224::new()),
+            Some(ChecksumAlgorithm::Sha256) => self.verify_checksum(sha2::Sha256::new()),
+            Some(ChecksumAlgorithm::Blake2b224) => self.verify_checksum(Blake2b224::new()),
+            Some(ChecksumAlgorithm::Crc32) => self.verify_checksum(Crc32::new()),
+            Some(ChecksumAlgorithm::Unchecked) => self.verify_checksum(NoChecksum::new()),
+            None => Err(LoaderError::UnknownChecksumAlgorithm(self.header.flags())),
+        };
+    }
+
+    /// Checks that the file is signed by one of the trusted keys.
+    pub fn verify_signature(&self, keys: &TrustedKeys) -> Result<(), LoaderError> {
+        let trailer = self.signature.ok_or(LoaderError::MissingSignature)?;
+        let prehash = header_prehash(&self.header).chain_update(&self.program_bytes);
+
+        return keys.verify(&trailer, prehash);
+    }
+
+    /// The signature trailer, if the file is signed.
+    pub fn signature(&self) -> Option<&SignatureTrailer> {
+        return self.signature.as_ref();
     }
 
     fn verify_checksum<D: Digest>(&self, mut digest: D) -> Result<(), LoaderError> {
-        digest.update(self.program_bytes.clone());
-        let output = digest.finalize();
+        digest.update(&self.program_bytes);
 
-        if output.len() != self.header.checksum().len() {
+        if fit_checksum(&digest.finalize()) != self.header.checksum() {
             return Err(LoaderError::InvalidChecksum);
         }
 
-        for i in 0..output.len() {
-            if output[i] != self.header.checksum()[i] {
-                return Err(LoaderError::InvalidChecksum);
-            }
-        }
-
         return Ok(());
     }
 
+    /// Returns `None` if the header flags select an unknown algorithm.
+    pub fn checksum_algorithm(&self) -> Option<ChecksumAlgorithm> {
+        return ChecksumAlgorithm::from_flags(self.header.flags());
+    }
+
+    /// The checksum of the program bytes, which should match the checksum in the header.
+    pub fn compute_checksum(&self) -> Option<[u8; VXLHeader::HEADER_CHECKSUM_SIZE]> {
+        return self
+            .checksum_algorithm()
+            .map(|algorithm| algorithm.checksum(&self.program_bytes));
+    }
+
+    pub fn program_bytes(&self) -> &[u8] {
+        return &self.program_bytes;
+    }
+
+    /// The encoded instructions, which is every program byte in unsectioned files.
+    pub fn code_bytes(&self) -> &[u8] {
+        return &self.program_bytes[self.code.clone()];
+    }
+
+    /// The contents of each data section in the order they appear in the section table.
+    pub fn data_sections(&self) -> Vec<&[u8]> {
+        return self
+            .data
+            .iter()
+            .map(|range| &self.program_bytes[range.clone()])
+            .collect();
+    }
+
+    /// The symbol table, if the file has a symbols section.
+    pub fn symbols(&self) -> Option<&SymbolTable> {
+        return self.symbols.as_ref();
+    }
+
+    /// The imports and exports, if the file has a link section.
+    pub fn link_table(&self) -> Option<&LinkTable> {
+        return self.link_table.as_ref();
+    }
+
     pub fn get_header(&self) -> VXLHeader {
         return self.header;
     }
@@ -121,7 +646,11 @@ impl Loader {
         self,
         mut validator: V,
     ) -> Result<(VXLHeader, Vec<Instruction>), ValidatorError> {
-        validator.append_bytes(self.program_bytes);
+        let mut code_bytes = self.program_bytes;
+        code_bytes.truncate(self.code.end);
+        code_bytes.drain(..self.code.start);
+
+        validator.append_bytes(code_bytes);
 
         let instructions = validator.process_all_instructions()?;
 
@@ -129,12 +658,285 @@ impl Loader {
     }
 }
 
+impl Loader {
+    /// Loads a file from a `ByteSource` without holding the whole file in memory at once.
+    ///
+    /// The program bytes are hashed as they are read and the code is passed straight to
+    /// `validator`, which holds the only copy of it. Unlike `load_bytes` the checksum is checked
+    /// and the instructions are decoded, stopping at the first problem found.
+    pub fn load_from<S: ByteSource, V: Validator>(
+        source: S,
+        validator: V,
+    ) -> Result<(VXLHeader, Module), VerificationError> {
+        return Self::load_stream(source, validator, None);
+    }
+
+    /// Loads a file from a `ByteSource` as `load_from` does, refusing files that are not signed
+    /// by one of the trusted keys.
+    pub fn load_from_trusted<S: ByteSource, V: Validator>(
+        source: S,
+        validator: V,
+        keys: &TrustedKeys,
+    ) -> Result<(VXLHeader, Module), VerificationError> {
+        return Self::load_stream(source, validator, Some(keys));
+    }
+
+    fn load_stream<S: ByteSource, V: Validator>(
+        mut source: S,
+        validator: V,
+        keys: Option<&TrustedKeys>,
+    ) -> Result<(VXLHeader, Module), VerificationError> {
+        let mut header_bytes = [0u8; VXLHeader::HEADER_SIZE];
+
+        if !source
+            .fill(&mut header_bytes)
+            .map_err(VerificationError::Loader)?
+        {
+            return Err(VerificationError::Loader(
+                LoaderError::NotEnoughBytesForHeader,
+            ));
+        }
+
+        let (header, problems) = Self::parse_header(&header_bytes);
+
+        if let Some(problem) = problems.first() {
+            return Err(VerificationError::Loader(*problem));
+        }
+
+        if keys.is_some() && header.flags() & Self::SIGNED_FLAG == 0 {
+            return Err(VerificationError::Loader(LoaderError::MissingSignature));
+        }
+
+        let source = &mut source;
+
+        // The header has been checked so the algorithm is known.
+        return match ChecksumAlgorithm::from_flags(header.flags()).unwrap() {
+            ChecksumAlgorithm::Sha2_224 => {
+                Self::stream_program(header, source, validator, sha2::Sha224::new(), keys)
+            }
+            ChecksumAlgorithm::Sha3_224 => {
+                Self::stream_program(header, source, validator, sha3::Sha3_224::new(), keys)
+            }
+            ChecksumAlgorithm::Sha256 => {
+                Self::stream_program(header, source, validator, sha2::Sha256::new(), keys)
+            }
+            ChecksumAlgorithm::Blake2b224 => {
+                Self::stream_program(header, source, validator, Blake2b224::new(), keys)
+            }
+            ChecksumAlgorithm::Crc32 => {
+                Self::stream_program(header, source, validator, Crc32::new(), keys)
+            }
+            ChecksumAlgorithm::Unchecked => {
+                Self::stream_program(header, source, validator, NoChecksum::new(), keys)
+            }
+        };
+    }
+
+    fn stream_program<S: ByteSource, V: Validator, D: Digest>(
+        header: VXLHeader,
+        source: &mut S,
+        mut validator: V,
+        digest: D,
+        keys: Option<&TrustedKeys>,
+    ) -> Result<(VXLHeader, Module), VerificationError> {
+        let mut reader = ProgramReader {
+            source,
+            digest,
+            prehash: keys.map(|_| header_prehash(&header)),
+        };
+        let mut data_sections = Vec::new();
+        let mut symbols = None;
+        let mut link_table = None;
+        let code_start;
+
+        if header.version() < Self::SECTIONED_VERSION {
+            code_start = 0;
+
+            reader
+                .read_chunks(header.file_size(), |chunk| {
+                    validator.append_bytes(chunk.to_vec())
+                })
+                .map_err(VerificationError::Loader)?;
+        } else {
+            if header.file_size() < 8 {
+                return Err(VerificationError::Loader(LoaderError::InvalidSectionTable));
+            }
+
+            let count = reader.read_vec(8).map_err(VerificationError::Loader)?;
+            let table_end = Self::section_table_end(count.try_into().unwrap(), header.file_size())
+                .map_err(VerificationError::Loader)?;
+            let entries = reader
+                .read_vec(table_end as u64 - 8)
+                .map_err(VerificationError::Loader)?;
+            let ranges = Self::parse_section_table(&entries, header.file_size())
+                .map_err(VerificationError::Loader)?;
+
+            code_start = ranges.code.start;
+
+            // The sections must be read in the order they appear in the file.
+            let mut sections = vec![(SectionKind::Code, ranges.code)];
+            sections.extend(ranges.data.into_iter().map(|r| (SectionKind::Data, r)));
+            sections.extend(ranges.symbols.map(|r| (SectionKind::Symbols, r)));
+            sections.extend(ranges.link.map(|r| (SectionKind::Link, r)));
+            sections.sort_by_key(|(_, range)| range.start);
+
+            for (kind, range) in sections {
+                let length = range.len() as u64;
+
+                let result = match kind {
+                    SectionKind::Code => {
+                        reader.read_chunks(length, |chunk| validator.append_bytes(chunk.to_vec()))
+                    }
+                    SectionKind::Data => reader
+                        .read_vec(length)
+                        .map(|bytes| data_sections.push(bytes)),
+                    SectionKind::Symbols => reader
+                        .read_vec(length)
+                        .and_then(|bytes| SymbolTable::from_bytes(&bytes))
+                        .map(|table| symbols = Some(table)),
+                    SectionKind::Link => reader
+                        .read_vec(length)
+                        .and_then(|bytes| LinkTable::from_bytes(&bytes))
+                        .map(|table| link_table = Some(table)),
+                };
+
+                result.map_err(VerificationError::Loader)?;
+            }
+        }
+
+        reader
+            .finish(&header, keys)
+            .map_err(VerificationError::Loader)?;
+
+        let mut instructions = Vec::new();
+        let mut offset = code_start;
+
+        while validator.has_next_byte() {
+            let instruction = validator
+                .take_next_instruction()
+                .map_err(|e| VerificationError::Validator(offset, e))?;
+
+            offset += encoded_size(instruction);
+            instructions.push(instruction);
+        }
+
+        let mut module = Module::new(instructions, header.starting_offset());
+
+        for section in data_sections {
+            module.add_data_section(section);
+        }
+
+        if let Some(symbols) = symbols {
+            module.set_symbols(symbols);
+        }
+
+        if let Some(link_table) = link_table {
+            *module.link_table_mut() = link_table;
+        }
+
+        return Ok((header, module));
+    }
+}
+
+/// Starts the hash that a signature is made over with the header.
+fn header_prehash(header: &VXLHeader) -> Sha512 {
+    let mut bytes = Vec::with_capacity(VXLHeader::HEADER_SIZE);
+    encode_header(header, &mut bytes);
+
+    return Sha512::new().chain_update(&bytes);
+}
+
+/// The most bytes requested from a `ByteSource` at once.
+const CHUNK_SIZE: usize = 4096;
+
+/// Reads the program bytes of a file from a `ByteSource`, hashing them as they are read.
+struct ProgramReader<'a, S, D> {
+    source: &'a mut S,
+    digest: D,
+    /// The hash for checking the signature, when there are trusted keys.
+    prehash: Option<Sha512>,
+}
+
+impl<'a, S: ByteSource, D: Digest> ProgramReader<'a, S, D> {
+    /// Passes the next `length` program bytes to `f` a chunk at a time.
+    fn read_chunks<F: FnMut(&[u8])>(
+        &mut self,
+        mut length: u64,
+        mut f: F,
+    ) -> Result<(), LoaderError> {
+        let mut buffer = [0u8; CHUNK_SIZE];
+
+        while length > 0 {
+            let size = length.min(CHUNK_SIZE as u64) as usize;
+            let read = self.source.read_chunk(&mut buffer[..size])?;
+
+            if read == 0 {
+                return Err(LoaderError::NonMatchingFileSize);
+            }
+
+            self.digest.update(&buffer[..read]);
+
+            if let Some(prehash) = &mut self.prehash {
+                prehash.update(&buffer[..read]);
+            }
+
+            f(&buffer[..read]);
+
+            length -= read as u64;
+        }
+
+        return Ok(());
+    }
+
+    fn read_vec(&mut self, length: u64) -> Result<Vec<u8>, LoaderError> {
+        let mut bytes = Vec::new();
+
+        self.read_chunks(length, |chunk| bytes.extend_from_slice(chunk))?;
+
+        return Ok(bytes);
+    }
+
+    /// Reads the signature trailer of signed files, then checks that the source has ended, that
+    /// the checksum matches, and that the signature is trusted if there are trusted keys.
+    fn finish(self, header: &VXLHeader, keys: Option<&TrustedKeys>) -> Result<(), LoaderError> {
+        let mut trailer = None;
+
+        if header.flags() & Loader::SIGNED_FLAG != 0 {
+            let mut bytes = [0u8; SignatureTrailer::SIZE];
+
+            if !self.source.fill(&mut bytes)? {
+                return Err(LoaderError::MissingSignature);
+            }
+
+            trailer = Some(SignatureTrailer::from_bytes(&bytes));
+        }
+
+        if self.source.read_chunk(&mut [0u8; 1])? != 0 {
+            return Err(LoaderError::NonMatchingFileSize);
+        }
+
+        if fit_checksum(&self.digest.finalize()) != header.checksum() {
+            return Err(LoaderError::InvalidChecksum);
+        }
+
+        if let (Some(keys), Some(trailer), Some(prehash)) = (keys, trailer, self.prehash) {
+            keys.verify(&trailer, prehash)?;
+        }
+
+        return Ok(());
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use alloc::vec;
     use paste::paste;
 
     use super::*;
+    use crate::error::ProgramError;
+    use crate::signature::{sign_file, SigningKey};
+    use crate::source::ChunkedSource;
+    use crate::writer::Writer;
 
     macro_rules! test_validation {
         ($reference:expr, $program_bytes:expr, $flags:literal, $out:expr, $name:ident) => {
@@ -156,7 +958,12 @@ mod tests {
 
                     let loader = Loader {
                         header: VXLHeader::new(0x0, $program_bytes.len() as u64, 0, $flags, checksum),
+                        code: 0..$program_bytes.len(),
                         program_bytes: $program_bytes,
+                        data: Vec::new(),
+                        symbols: None,
+                        link_table: None,
+                        signature: None,
                     };
 
                     assert_eq!(loader.validate(), $out);
@@ -398,4 +1205,291 @@ mod tests {
         out Err(LoaderError::InvalidEndHeaderMarker),
         load_invalid_header_terminator
     );
+
+    fn header_bytes(magic: [u8; 4], file_size: u64, end_header: u8) -> Vec<u8> {
+        let mut bytes = magic.to_vec();
+
+        bytes.push(0x0);
+        bytes.extend_from_slice(&file_size.to_le_bytes());
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes.push(0b0000_0001);
+        bytes.extend_from_slice(&[0u8; 28]);
+        bytes.push(end_header);
+
+        return bytes;
+    }
+
+    #[test]
+    fn test_verify_reports_every_problem() {
+        let mut bytes = header_bytes([0x65, 0x58, 0x56, 0x4b], 12, 0xab);
+        // nop, then an ldi missing most of its immediate.
+        bytes.extend_from_slice(&[0x00, 0x03, 0x01, 0x02]);
+
+        assert_eq!(
+            Loader::verify_bytes(&bytes),
+            vec![
+                VerificationError::Loader(LoaderError::InvalidMagic),
+                VerificationError::Loader(LoaderError::InvalidEndHeaderMarker),
+                VerificationError::Loader(LoaderError::NonMatchingFileSize),
+                VerificationError::Loader(LoaderError::InvalidChecksum),
+                VerificationError::Validator(1, ValidatorError::UnexpectedEndOfBytes),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_verify_valid_file() {
+        let program = [0x00, 0x45];
+        let mut bytes = header_bytes([0x65, 0x58, 0x56, 0x4c], 2, 0xaa);
+        bytes.extend_from_slice(&program);
+
+        let checksum = ChecksumAlgorithm::Sha3_224.checksum(&program);
+        bytes[0x16..0x32].copy_from_slice(&checksum);
+
+        assert_eq!(Loader::verify_bytes(&bytes), vec![]);
+
+        // The entry offset is beyond the instructions.
+        bytes[0xd] = 2;
+        assert_eq!(
+            Loader::verify_bytes(&bytes),
+            vec![VerificationError::Program(ProgramError::EntryOutOfBounds(
+                2
+            ))]
+        );
+
+        assert_eq!(
+            Loader::verify_bytes(&bytes[..10]),
+            vec![VerificationError::Loader(
+                LoaderError::NotEnoughBytesForHeader
+            )]
+        );
+    }
+
+    #[test]
+    fn test_read_invalid_header() {
+        let bytes = header_bytes([0x0, 0x0, 0x0, 0x0], 8, 0xaa);
+        let loader = Loader::read_bytes(&bytes).unwrap();
+
+        assert_eq!(loader.get_header().flags(), 0b0000_0001);
+        assert_eq!(
+            loader.checksum_algorithm(),
+            Some(ChecksumAlgorithm::Sha3_224)
+        );
+        assert_eq!(
+            loader.compute_checksum(),
+            Some(ChecksumAlgorithm::Sha3_224.checksum(&[]))
+        );
+        assert_eq!(Loader::load_bytes(&bytes), Err(LoaderError::InvalidMagic));
+    }
+
+    fn sectioned_bytes(program: &[u8]) -> Vec<u8> {
+        let mut bytes = header_bytes([0x65, 0x58, 0x56, 0x4c], program.len() as u64, 0xaa);
+        bytes[4] = Loader::SECTIONED_VERSION;
+        bytes.extend_from_slice(program);
+
+        return bytes;
+    }
+
+    fn section_table(entries: &[(u8, u64)]) -> Vec<u8> {
+        let mut table = (entries.len() as u64).to_le_bytes().to_vec();
+
+        for (kind, length) in entries {
+            table.push(*kind);
+            table.extend_from_slice(&length.to_le_bytes());
+        }
+
+        return table;
+    }
+
+    #[test]
+    fn test_checksum_algorithms() {
+        let mut program = section_table(&[(0, 1)]);
+        program.push(0x45);
+
+        let mut unchecked = sectioned_bytes(&program);
+        unchecked[0x15] = ChecksumAlgorithm::Unchecked.flags();
+
+        let loader = Loader::load_bytes(&unchecked).unwrap();
+        assert_eq!(loader.validate(), Ok(()));
+
+        // Without a checksum the field must be zero.
+        unchecked[0x16] = 1;
+        assert_eq!(
+            Loader::load_bytes(&unchecked).unwrap().validate(),
+            Err(LoaderError::InvalidChecksum)
+        );
+
+        unchecked[0x15] = 0b1000_0110;
+        assert_eq!(
+            Loader::load_bytes(&unchecked),
+            Err(LoaderError::UnknownChecksumAlgorithm(0b1000_0110))
+        );
+        assert_eq!(
+            Loader::read_bytes(&unchecked).unwrap().compute_checksum(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_load_sections() {
+        let mut program = section_table(&[(1, 2), (0, 1), (1, 3)]);
+        program.extend_from_slice(&[0xa, 0xb, 0x45, 0xc, 0xd, 0xe]);
+
+        let loader = Loader::load_bytes(&sectioned_bytes(&program)).unwrap();
+
+        assert_eq!(loader.code_bytes(), &[0x45]);
+        assert_eq!(
+            loader.data_sections(),
+            vec![&[0xa, 0xb][..], &[0xc, 0xd, 0xe][..]]
+        );
+        assert_eq!(
+            loader.to_instructions(BulkValidator::new()).unwrap().1,
+            vec![Instruction::Halt]
+        );
+    }
+
+    #[test]
+    fn test_load_invalid_sections() {
+        let invalid_tables = [
+            // The contents are shorter than the table describes.
+            (section_table(&[(0, 4)]), LoaderError::InvalidSectionTable),
+            // The contents are longer than the table describes.
+            (section_table(&[(0, 0)]), LoaderError::InvalidSectionTable),
+            // There is no code section.
+            (section_table(&[(1, 1)]), LoaderError::InvalidSectionTable),
+            (
+                section_table(&[(0, 0), (0, 1)]),
+                LoaderError::InvalidSectionTable,
+            ),
+            (
+                section_table(&[(0, 0), (7, 1)]),
+                LoaderError::UnknownSectionKind(7),
+            ),
+            (vec![0xff; 8], LoaderError::InvalidSectionTable),
+            (vec![], LoaderError::InvalidSectionTable),
+        ];
+
+        for (mut program, error) in invalid_tables {
+            program.push(0x45);
+
+            assert_eq!(Loader::load_bytes(&sectioned_bytes(&program)), Err(error));
+        }
+    }
+
+    fn load_in_chunks(bytes: &[u8], size: usize) -> Result<(VXLHeader, Module), VerificationError> {
+        let chunks: Vec<Vec<u8>> = bytes.chunks(size).map(|chunk| chunk.to_vec()).collect();
+
+        return Loader::load_from(ChunkedSource::new(chunks.into_iter()), BulkValidator::new());
+    }
+
+    fn with_checksum(program: &[u8]) -> Vec<u8> {
+        let mut bytes = sectioned_bytes(program);
+        bytes[0x16..0x32].copy_from_slice(&ChecksumAlgorithm::Sha3_224.checksum(program));
+
+        return bytes;
+    }
+
+    #[test]
+    fn test_load_policy() {
+        let module = Module::new(vec![Instruction::Halt], 0);
+        let checked = module.to_writer(ChecksumAlgorithm::Sha3_224).to_bytes();
+        let unchecked = module.to_writer(ChecksumAlgorithm::Unchecked).to_bytes();
+
+        let policy = LoadPolicy::default();
+        assert!(policy.load_bytes(&checked).is_ok());
+        assert_eq!(
+            policy.load_bytes(&unchecked),
+            Err(LoaderError::UncheckedNotAllowed)
+        );
+        assert_eq!(
+            policy.load_from(&unchecked[..], BulkValidator::new()),
+            Err(VerificationError::Loader(LoaderError::UncheckedNotAllowed))
+        );
+
+        let policy = LoadPolicy::new(true, None);
+        assert!(policy.load_bytes(&unchecked).is_ok());
+        assert!(policy
+            .load_from(&unchecked[..], BulkValidator::new())
+            .is_ok());
+
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let mut keys = TrustedKeys::new();
+        keys.add_key(&key.verifying_key().to_bytes());
+        let signed = sign_file(&checked, &key).unwrap();
+
+        let policy = LoadPolicy::new(false, Some(keys));
+        assert!(policy.load_bytes(&signed).is_ok());
+        assert!(policy.load_from(&signed[..], BulkValidator::new()).is_ok());
+        assert_eq!(
+            policy.load_bytes(&checked),
+            Err(LoaderError::MissingSignature)
+        );
+        assert_eq!(
+            policy.load_from(&checked[..], BulkValidator::new()),
+            Err(VerificationError::Loader(LoaderError::MissingSignature))
+        );
+    }
+
+    #[test]
+    fn test_load_from() {
+        // The data sections are on either side of the code.
+        let mut program = section_table(&[(1, 2), (0, 1), (1, 3)]);
+        program.extend_from_slice(&[0xa, 0xb, 0x45, 0xc, 0xd, 0xe]);
+
+        let unsectioned = Writer::new(vec![Instruction::Halt], 0, ChecksumAlgorithm::Sha3_224);
+
+        for bytes in [with_checksum(&program), unsectioned.to_bytes()] {
+            let expected = Module::from_loader(Loader::load_bytes(&bytes).unwrap()).unwrap();
+
+            for size in [1, 7, bytes.len()] {
+                let (header, module) = load_in_chunks(&bytes, size).unwrap();
+
+                assert_eq!(header.file_size(), bytes.len() as u64 - 51);
+                assert_eq!(module, expected);
+            }
+
+            let (_header, module) = Loader::load_from(&bytes[..], BulkValidator::new()).unwrap();
+            assert_eq!(module, expected);
+        }
+    }
+
+    #[test]
+    fn test_load_from_invalid() {
+        let mut program = section_table(&[(0, 1), (1, 1)]);
+        program.extend_from_slice(&[0x45, 0xa]);
+        let bytes = with_checksum(&program);
+
+        let mut longer = bytes.clone();
+        longer.push(0);
+
+        let mut modified = bytes.clone();
+        *modified.last_mut().unwrap() = 0xb;
+
+        let mut invalid_code = section_table(&[(0, 1)]);
+        invalid_code.push(0xff);
+
+        let files = [
+            (&bytes[..10], LoaderError::NotEnoughBytesForHeader),
+            (&bytes[..bytes.len() - 1], LoaderError::NonMatchingFileSize),
+            (&longer, LoaderError::NonMatchingFileSize),
+            (&modified, LoaderError::InvalidChecksum),
+            (&sectioned_bytes(&[0x45]), LoaderError::InvalidSectionTable),
+        ];
+
+        for (file, error) in files {
+            assert_eq!(
+                load_in_chunks(file, 4),
+                Err(VerificationError::Loader(error))
+            );
+        }
+
+        // The offset of the instruction is after the section table.
+        assert_eq!(
+            load_in_chunks(&with_checksum(&invalid_code), 4),
+            Err(VerificationError::Validator(
+                17,
+                ValidatorError::UnknownRegisterCountForOpcode
+            ))
+        );
+    }
 }
@@ -0,0 +1,227 @@
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use ed25519_dalek::{Signature, VerifyingKey};
+use sha2::{Digest, Sha512};
+
+use crate::error::LoaderError;
+use crate::loader::Loader;
+use vxl_iset::vxl_file::VXLHeader;
+
+pub use ed25519_dalek::SigningKey;
+
+/*
+A signed file has `Loader::SIGNED_FLAG` set in its header and ends with a trailer, which is not
+included in the file size.
+
+Byte Offset - Value
+
+0x0 - The public key of the signer (32 bytes)
+0x20 - The Ed25519ph signature of the header and program bytes (64 bytes)
+*/
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct SignatureTrailer {
+    public_key: [u8; 32],
+    signature: [u8; 64],
+}
+
+/// Separates signatures of executables from anything else signed with the same key.
+const CONTEXT: &[u8] = b"vxl executable";
+
+/// The offset of the flags in the header.
+const FLAGS_OFFSET: usize = 0x15;
+
+impl SignatureTrailer {
+    pub const SIZE: usize = 96;
+
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Self {
+        return Self {
+            public_key: bytes[..32].try_into().unwrap(),
+            signature: bytes[32..].try_into().unwrap(),
+        };
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+
+        bytes[..32].copy_from_slice(&self.public_key);
+        bytes[32..].copy_from_slice(&self.signature);
+
+        return bytes;
+    }
+
+    pub fn public_key(&self) -> [u8; 32] {
+        return self.public_key;
+    }
+
+    pub fn signature(&self) -> [u8; 64] {
+        return self.signature;
+    }
+}
+
+/// The public keys whose signatures are accepted when loading a file.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct TrustedKeys {
+    keys: Vec<VerifyingKey>,
+}
+
+impl TrustedKeys {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    /// Returns false if the bytes are not a valid Ed25519 public key.
+    pub fn add_key(&mut self, public_key: &[u8; 32]) -> bool {
+        return match VerifyingKey::from_bytes(public_key) {
+            Ok(key) => {
+                self.keys.push(key);
+                true
+            }
+            Err(_) => false,
+        };
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.keys.is_empty();
+    }
+
+    /// Checks a signature against the SHA-512 hash of the header and program bytes.
+    pub(crate) fn verify(
+        &self,
+        trailer: &SignatureTrailer,
+        prehash: Sha512,
+    ) -> Result<(), LoaderError> {
+        let key = self
+            .keys
+            .iter()
+            .find(|key| key.as_bytes() == &trailer.public_key)
+            .ok_or(LoaderError::UntrustedSignature)?;
+
+        let signature = Signature::from_bytes(&trailer.signature);
+
+        return key
+            .verify_prehashed(prehash, Some(CONTEXT), &signature)
+            .map_err(|_| LoaderError::InvalidSignature);
+    }
+}
+
+/// Signs a complete file, replacing any existing signature.
+pub fn sign_file(bytes: &[u8], key: &SigningKey) -> Result<Vec<u8>, LoaderError> {
+    let loader = Loader::load_bytes(bytes)?;
+    let length = VXLHeader::HEADER_SIZE + loader.get_header().file_size() as usize;
+
+    return Ok(sign_unchecked(bytes[..length].to_vec(), key));
+}
+
+/// Sets the signed flag of a file and appends a trailer, the file must not already be signed.
+pub(crate) fn sign_unchecked(mut bytes: Vec<u8>, key: &SigningKey) -> Vec<u8> {
+    bytes[FLAGS_OFFSET] |= Loader::SIGNED_FLAG;
+
+    let signature = key
+        .sign_prehashed(Sha512::new().chain_update(&bytes), Some(CONTEXT))
+        .expect("The context is short enough to be valid.");
+
+    let trailer = SignatureTrailer {
+        public_key: key.verifying_key().to_bytes(),
+        signature: signature.to_bytes(),
+    };
+
+    bytes.extend_from_slice(&trailer.to_bytes());
+
+    return bytes;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::VerificationError;
+    use crate::loader::ChecksumAlgorithm;
+    use crate::source::ChunkedSource;
+    use crate::validator::BulkValidator;
+    use crate::writer::Writer;
+    use alloc::vec;
+    use vxl_iset::instruction::Instruction;
+
+    fn signed_file(key: &SigningKey) -> Vec<u8> {
+        let writer = Writer::new(vec![Instruction::Halt], 0, ChecksumAlgorithm::Sha2_224);
+
+        return writer.to_signed_bytes(key);
+    }
+
+    fn trusting(key: &SigningKey) -> TrustedKeys {
+        let mut keys = TrustedKeys::new();
+        assert!(keys.add_key(&key.verifying_key().to_bytes()));
+
+        return keys;
+    }
+
+    #[test]
+    fn test_signed_file() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let other = SigningKey::from_bytes(&[8; 32]);
+        let bytes = signed_file(&key);
+
+        let loader = Loader::load_trusted(&bytes, &trusting(&key)).unwrap();
+        assert_eq!(
+            loader.signature().unwrap().public_key(),
+            key.verifying_key().to_bytes()
+        );
+        assert_eq!(loader.validate(), Ok(()));
+
+        assert_eq!(
+            Loader::load_trusted(&bytes, &trusting(&other)),
+            Err(LoaderError::UntrustedSignature)
+        );
+
+        // Signing again replaces the signature.
+        let resigned = sign_file(&bytes, &other).unwrap();
+        assert_eq!(resigned.len(), bytes.len());
+        assert!(Loader::load_trusted(&resigned, &trusting(&other)).is_ok());
+
+        let unsigned = Writer::new(vec![Instruction::Halt], 0, ChecksumAlgorithm::Sha2_224);
+        assert_eq!(
+            Loader::load_trusted(&unsigned.to_bytes(), &trusting(&key)),
+            Err(LoaderError::MissingSignature)
+        );
+    }
+
+    #[test]
+    fn test_modified_file() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let keys = trusting(&key);
+        let mut bytes = signed_file(&key);
+
+        // The entry offset isn't covered by the checksum but is signed.
+        bytes[0xd] = 1;
+
+        assert_eq!(
+            Loader::load_trusted(&bytes, &keys),
+            Err(LoaderError::InvalidSignature)
+        );
+
+        let chunks = bytes.chunks(5).map(|chunk| chunk.to_vec());
+        assert_eq!(
+            Loader::load_from_trusted(ChunkedSource::new(chunks), BulkValidator::new(), &keys),
+            Err(VerificationError::Loader(LoaderError::InvalidSignature))
+        );
+
+        // The flags still say that the file is signed.
+        bytes.truncate(bytes.len() - SignatureTrailer::SIZE);
+        assert_eq!(
+            Loader::load_bytes(&bytes),
+            Err(LoaderError::MissingSignature)
+        );
+    }
+
+    #[test]
+    fn test_stream_signed_file() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let bytes = signed_file(&key);
+
+        let (_header, module) =
+            Loader::load_from_trusted(&bytes[..], BulkValidator::new(), &trusting(&key)).unwrap();
+        assert_eq!(module.instructions(), &[Instruction::Halt]);
+
+        // Without trusted keys the signature is skipped.
+        assert!(Loader::load_from(&bytes[..], BulkValidator::new()).is_ok());
+    }
+}
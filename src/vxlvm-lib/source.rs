@@ -0,0 +1,137 @@
+use alloc::vec::Vec;
+
+use crate::error::LoaderError;
+
+/// A source of file bytes for `Loader::load_from`, read a chunk at a time so that the whole file
+/// never has to be held in memory.
+pub trait ByteSource {
+    /// Fills the start of `buffer`, returning the number of bytes read. Zero is only returned
+    /// once the source is exhausted.
+    fn read_chunk(&mut self, buffer: &mut [u8]) -> Result<usize, LoaderError>;
+
+    /// Fills the whole buffer, returning false if the source ends first.
+    fn fill(&mut self, buffer: &mut [u8]) -> Result<bool, LoaderError> {
+        let mut length = 0;
+
+        while length < buffer.len() {
+            let read = self.read_chunk(&mut buffer[length..])?;
+
+            if read == 0 {
+                return Ok(false);
+            }
+
+            length += read;
+        }
+
+        return Ok(true);
+    }
+}
+
+impl ByteSource for &[u8] {
+    fn read_chunk(&mut self, buffer: &mut [u8]) -> Result<usize, LoaderError> {
+        let length = buffer.len().min(self.len());
+        let (chunk, rest) = self.split_at(length);
+
+        buffer[..length].copy_from_slice(chunk);
+        *self = rest;
+
+        return Ok(length);
+    }
+}
+
+/// A `ByteSource` over a sequence of chunks, such as the blocks of a file as they are received.
+#[derive(Clone, Debug)]
+pub struct ChunkedSource<I> {
+    chunks: I,
+    current: Vec<u8>,
+    position: usize,
+}
+
+impl<I: Iterator<Item = Vec<u8>>> ChunkedSource<I> {
+    pub fn new(chunks: I) -> Self {
+        return Self {
+            chunks,
+            current: Vec::new(),
+            position: 0,
+        };
+    }
+}
+
+impl<I: Iterator<Item = Vec<u8>>> ByteSource for ChunkedSource<I> {
+    fn read_chunk(&mut self, buffer: &mut [u8]) -> Result<usize, LoaderError> {
+        // Empty chunks are skipped so that zero is only returned at the end.
+        while self.position >= self.current.len() {
+            match self.chunks.next() {
+                Some(chunk) => {
+                    self.current = chunk;
+                    self.position = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+
+        let mut remaining = &self.current[self.position..];
+        let length = remaining.read_chunk(buffer)?;
+        self.position += length;
+
+        return Ok(length);
+    }
+}
+
+/// A `ByteSource` for anything implementing `std::io::Read`, such as a file.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct ReadSource<R> {
+    reader: R,
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read> ReadSource<R> {
+    pub fn new(reader: R) -> Self {
+        return Self { reader };
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read> ByteSource for ReadSource<R> {
+    fn read_chunk(&mut self, buffer: &mut [u8]) -> Result<usize, LoaderError> {
+        loop {
+            match self.reader.read(buffer) {
+                Ok(length) => return Ok(length),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(_) => return Err(LoaderError::ReadFailed),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_chunked_source() {
+        let mut source = ChunkedSource::new(vec![vec![1, 2, 3], vec![], vec![4]].into_iter());
+        let mut buffer = [0u8; 2];
+
+        assert_eq!(source.read_chunk(&mut buffer), Ok(2));
+        assert_eq!(buffer, [1, 2]);
+        assert_eq!(source.read_chunk(&mut buffer), Ok(1));
+        assert_eq!(buffer[0], 3);
+        assert_eq!(source.read_chunk(&mut buffer), Ok(1));
+        assert_eq!(buffer[0], 4);
+        assert_eq!(source.read_chunk(&mut buffer), Ok(0));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_read_source() {
+        let mut source = ReadSource::new(std::io::Cursor::new(vec![1, 2, 3]));
+        let mut buffer = [0u8; 4];
+
+        assert_eq!(source.read_chunk(&mut buffer), Ok(3));
+        assert_eq!(buffer, [1, 2, 3, 0]);
+        assert_eq!(source.read_chunk(&mut buffer), Ok(0));
+    }
+}
@@ -0,0 +1,387 @@
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::cfg::{ControlFlowGraph, EdgeKind, Function};
+use crate::effects::{branch_target, written_registers};
+use crate::stdlib::StandardLibrary;
+use vxl_iset::instruction::Instruction;
+use vxl_iset::instruction_arguments::Register;
+
+/// The number of values `call` pushes for `ret` to restore: `rfp`, `rsp` and the return address.
+const CALL_FRAME: u64 = 3;
+
+/// The number of bytes of each value on the stack.
+const VALUE_SIZE: u64 = 8;
+
+/// The stack usage of each function in a program, found without running it.
+///
+/// Each function starts with an empty frame of its own, `push` adds a value and `pop` removes
+/// one. A call adds the frame of the call and the deepest point of the callee. Syscalls are
+/// assumed not to use the stack, except `StandardLibrary::CALL` which calls code that isn't
+/// known until it runs.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct StackUsage {
+    entry: usize,
+    /// Sorted by their first instruction.
+    functions: Vec<FunctionUsage>,
+    /// Sorted instruction indices in the entry function.
+    entry_underflows: Vec<usize>,
+    /// Sorted instruction indices only in functions that are called.
+    call_underflows: Vec<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct FunctionUsage {
+    start: usize,
+    max_depth: Option<u64>,
+}
+
+impl FunctionUsage {
+    /// The index of the first instruction.
+    pub fn start(&self) -> usize {
+        return self.start;
+    }
+
+    /// The most bytes that can be on the stack from the start of the function until it returns,
+    /// including the functions it calls. `None` if this isn't bounded or can't be known, which
+    /// happens for loops that push more than they pop, recursion and writes to `rfp`.
+    pub fn max_depth(&self) -> Option<u64> {
+        return self.max_depth;
+    }
+}
+
+/// What is known about a function's own frame before its calls are taken into account.
+struct LocalUsage {
+    /// In values.
+    max_depth: u64,
+    /// The callee and the depth of the frame at each call.
+    calls: Vec<(usize, u64)>,
+    bounded: bool,
+}
+
+impl StackUsage {
+    pub fn new(instructions: &[Instruction], entry: u64) -> Self {
+        let graph = ControlFlowGraph::new(instructions, entry);
+        let mut entry_underflows = Vec::new();
+        let mut call_underflows = Vec::new();
+
+        let locals: BTreeMap<usize, LocalUsage> = graph
+            .functions()
+            .iter()
+            .map(|function| {
+                let underflows = if function.start() as u64 == entry {
+                    &mut entry_underflows
+                } else {
+                    &mut call_underflows
+                };
+                let usage = analyse_function(instructions, &graph, function, underflows);
+
+                (function.start(), usage)
+            })
+            .collect();
+
+        let mut depths = BTreeMap::new();
+
+        for start in locals.keys() {
+            total_depth(*start, &locals, &mut depths, &mut Vec::new());
+        }
+
+        entry_underflows.sort_unstable();
+        entry_underflows.dedup();
+        call_underflows.sort_unstable();
+        call_underflows.dedup();
+        call_underflows.retain(|index| entry_underflows.binary_search(index).is_err());
+
+        return Self {
+            entry: entry as usize,
+            functions: depths
+                .into_iter()
+                .map(|(start, max_depth)| FunctionUsage {
+                    start,
+                    max_depth: max_depth.map(|depth| depth * VALUE_SIZE),
+                })
+                .collect(),
+            entry_underflows,
+            call_underflows,
+        };
+    }
+
+    pub fn functions(&self) -> &[FunctionUsage] {
+        return &self.functions;
+    }
+
+    pub fn function_starting_at(&self, index: usize) -> Option<&FunctionUsage> {
+        return self
+            .functions
+            .binary_search_by_key(&index, |function| function.start)
+            .ok()
+            .map(|i| &self.functions[i]);
+    }
+
+    /// The most bytes that the whole program can have on the stack, the size of stack it needs.
+    pub fn max_depth(&self) -> Option<u64> {
+        return self.function_starting_at(self.entry)?.max_depth;
+    }
+
+    /// The `pop` instructions in the entry function that can run when it has nothing left on the
+    /// stack, which is always an `AccessBeyondStackBounds` error.
+    pub fn entry_underflows(&self) -> &[usize] {
+        return &self.entry_underflows;
+    }
+
+    /// The `pop` instructions only in called functions that can run when their function has
+    /// nothing left on the stack. These remove the values pushed by the call rather than failing.
+    pub fn call_underflows(&self) -> &[usize] {
+        return &self.call_underflows;
+    }
+}
+
+/// Follows the paths through a function, recording the depth of its frame at the start of each
+/// block. A block reached with two different depths makes the function unbounded.
+fn analyse_function(
+    instructions: &[Instruction],
+    graph: &ControlFlowGraph,
+    function: &Function,
+    underflows: &mut Vec<usize>,
+) -> LocalUsage {
+    let mut usage = LocalUsage {
+        max_depth: 0,
+        calls: Vec::new(),
+        bounded: true,
+    };
+
+    // Only the shallowest path into each block is followed, which finds every underflow.
+    let mut lowest: Vec<Option<u64>> = vec![None; graph.blocks().len()];
+    let mut pending: Vec<(usize, u64)> = graph
+        .block_starting_at(function.start())
+        .map(|block| (block, 0))
+        .into_iter()
+        .collect();
+
+    while let Some((block, depth)) = pending.pop() {
+        if let Some(previous) = lowest[block] {
+            if depth != previous {
+                usage.bounded = false;
+            }
+
+            if depth >= previous {
+                continue;
+            }
+        }
+
+        lowest[block] = Some(depth);
+
+        let mut depth = depth;
+        let mut continues = true;
+
+        for index in graph.blocks()[block].range() {
+            let instruction = &instructions[index];
+
+            if written_registers(instruction).contains(&Register::RFP) {
+                usage.bounded = false;
+                continues = false;
+                break;
+            }
+
+            match instruction {
+                Instruction::Push(_) => {
+                    depth += 1;
+                    usage.max_depth = usage.max_depth.max(depth);
+                }
+                Instruction::Pop(_) if depth == 0 => {
+                    underflows.push(index);
+                    continues = false;
+                    break;
+                }
+                Instruction::Pop(_) => depth -= 1,
+                Instruction::Call(_) => {
+                    if let Some(target) = branch_target(instruction) {
+                        if target < instructions.len() as u64 {
+                            usage.calls.push((target as usize, depth));
+                        }
+                    }
+                }
+                Instruction::Syscall(i) if u64::from(*i) == StandardLibrary::CALL => {
+                    usage.bounded = false;
+                }
+                _ => (),
+            }
+        }
+
+        if !continues {
+            continue;
+        }
+
+        for edge in graph.successors(block) {
+            if matches!(edge.kind(), EdgeKind::FallThrough | EdgeKind::Branch) {
+                pending.push((edge.to(), depth));
+            }
+        }
+    }
+
+    return usage;
+}
+
+/// Adds the deepest call of a function to its own frame, in values. Calling a function that is
+/// already in `active` is recursion, which is unbounded.
+fn total_depth(
+    start: usize,
+    locals: &BTreeMap<usize, LocalUsage>,
+    depths: &mut BTreeMap<usize, Option<u64>>,
+    active: &mut Vec<usize>,
+) -> Option<u64> {
+    if let Some(depth) = depths.get(&start) {
+        return *depth;
+    }
+
+    if active.contains(&start) {
+        return None;
+    }
+
+    let local = &locals[&start];
+    active.push(start);
+
+    let mut depth = Some(local.max_depth).filter(|_| local.bounded);
+
+    for (callee, call_depth) in &local.calls {
+        let callee_depth = total_depth(*callee, locals, depths, active);
+
+        depth = match (depth, callee_depth) {
+            (Some(depth), Some(callee_depth)) => {
+                Some(depth.max(call_depth + CALL_FRAME + callee_depth))
+            }
+            _ => None,
+        };
+    }
+
+    active.pop();
+    depths.insert(start, depth);
+
+    return depth;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use vxl_iset::instruction_arguments::{Address, Immediate};
+
+    #[test]
+    fn test_nested_calls() {
+        // 0: push $r0
+        // 1: call 4
+        // 2: pop $r0
+        // 3: halt
+        // 4: push $r1
+        // 5: push $r2
+        // 6: pop $r2
+        // 7: pop $r1
+        // 8: ret
+        let instructions = vec![
+            Instruction::Push(Register::R0),
+            Instruction::Call(Address::from(4u64)),
+            Instruction::Pop(Register::R0),
+            Instruction::Halt,
+            Instruction::Push(Register::R1),
+            Instruction::Push(Register::R2),
+            Instruction::Pop(Register::R2),
+            Instruction::Pop(Register::R1),
+            Instruction::Ret,
+        ];
+
+        let usage = StackUsage::new(&instructions, 0);
+
+        assert_eq!(usage.function_starting_at(4).unwrap().max_depth(), Some(16));
+        // One value, the call frame and the two values of the callee.
+        assert_eq!(usage.max_depth(), Some(48));
+        assert!(usage.entry_underflows().is_empty());
+        assert!(usage.call_underflows().is_empty());
+    }
+
+    #[test]
+    fn test_underflow() {
+        // 0: cmp $r0, $r1
+        // 1: jeq 3
+        // 2: push $r0
+        // 3: pop $r0
+        // 4: halt
+        let instructions = vec![
+            Instruction::Cmp(Register::R0, Register::R1),
+            Instruction::Jeq(Address::from(3u64)),
+            Instruction::Push(Register::R0),
+            Instruction::Pop(Register::R0),
+            Instruction::Halt,
+        ];
+
+        let usage = StackUsage::new(&instructions, 0);
+
+        assert_eq!(usage.entry_underflows(), &[3]);
+        assert!(usage.call_underflows().is_empty());
+        // The depth at the pop depends on the path taken.
+        assert_eq!(usage.max_depth(), None);
+    }
+
+    #[test]
+    fn test_call_underflow() {
+        // 0: call 2
+        // 1: halt
+        // 2: pop $r0
+        // 3: ret
+        let instructions = vec![
+            Instruction::Call(Address::from(2u64)),
+            Instruction::Halt,
+            Instruction::Pop(Register::R0),
+            Instruction::Ret,
+        ];
+
+        let usage = StackUsage::new(&instructions, 0);
+
+        assert!(usage.entry_underflows().is_empty());
+        assert_eq!(usage.call_underflows(), &[2]);
+
+        // Run on its own the same function underflows the whole stack.
+        let usage = StackUsage::new(&instructions, 2);
+
+        assert_eq!(usage.entry_underflows(), &[2]);
+        assert!(usage.call_underflows().is_empty());
+    }
+
+    #[test]
+    fn test_unbounded() {
+        // 0: push $r0
+        // 1: jmp 0
+        let looping = vec![
+            Instruction::Push(Register::R0),
+            Instruction::Jmp(Address::from(0u64)),
+        ];
+        assert_eq!(StackUsage::new(&looping, 0).max_depth(), None);
+
+        // 0: call 2
+        // 1: halt
+        // 2: call 2
+        // 3: ret
+        let recursive = vec![
+            Instruction::Call(Address::from(2u64)),
+            Instruction::Halt,
+            Instruction::Call(Address::from(2u64)),
+            Instruction::Ret,
+        ];
+        let usage = StackUsage::new(&recursive, 0);
+        assert_eq!(usage.max_depth(), None);
+        assert_eq!(usage.function_starting_at(2).unwrap().max_depth(), None);
+
+        let moved = vec![
+            Instruction::Ldi(Immediate::from(64u64), Register::RFP),
+            Instruction::Halt,
+        ];
+        assert_eq!(StackUsage::new(&moved, 0).max_depth(), None);
+
+        let balanced = vec![
+            Instruction::Push(Register::R0),
+            Instruction::Pop(Register::R0),
+            Instruction::Jmp(Address::from(0u64)),
+        ];
+        assert_eq!(StackUsage::new(&balanced, 0).max_depth(), Some(8));
+    }
+}
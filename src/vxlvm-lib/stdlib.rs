@@ -0,0 +1,148 @@
+use crate::error::VMError;
+use crate::linker::Module;
+use crate::loader::LoadPolicy;
+use crate::vm::VM;
+
+use alloc::string::String;
+
+use vxl_iset::instruction_arguments::Register;
+
+/// Target specific system calls implemented natively by the VM.
+///
+/// A `SyscallHandler` can forward its target specific calls to `execute_call`, which returns
+/// `None` for any call that is not part of the standard library. Arguments are passed in `r0`
+/// onwards and the result is placed in `rou`.
+///
+/// Calls that only report success return `0`, `1` if a block does not exist or is read only and
+/// `2` if a range lies outside of its block.
+#[derive(Debug, Default)]
+pub struct StandardLibrary {
+    /// Checks the modules loaded with `LOAD_MODULE`.
+    policy: LoadPolicy,
+}
+
+impl StandardLibrary {
+    /// Sets `r2` bytes of block `r0` from offset `r1` to the low byte of `r3`.
+    pub const MEMORY_FILL: u64 = 0x100;
+    /// Compares `r4` bytes of block `r0` from offset `r1` with block `r2` from offset `r3`,
+    /// setting the flags in `rfl` as `cmp` would.
+    pub const MEMORY_COMPARE: u64 = 0x101;
+    /// Searches `r2` bytes of block `r0` from offset `r1` for the low byte of `r3`. Returns the
+    /// offset of the first match, or `u64::MAX` if there is no match or the range is invalid.
+    pub const MEMORY_FIND: u64 = 0x102;
+    /// Resizes block `r0` to `r1` bytes without changing its address.
+    pub const MEMORY_RESIZE: u64 = 0x103;
+    /// Returns the handle of data section `r0`, or `u64::MAX` if there are not that many data
+    /// sections.
+    pub const DATA_SECTION: u64 = 0x104;
+    /// Loads the `.xvl` file stored in block `r0` into the running program, see
+    /// `VM::load_module`, which verifies it. The file must be accepted by the `LoadPolicy`.
+    /// Returns a handle for the module, or `u64::MAX` if it could not be loaded.
+    pub const LOAD_MODULE: u64 = 0x105;
+    /// Returns the index of the function named by the UTF-8 contents of block `r1` exported by
+    /// module `r0`, or `u64::MAX` if there is no such export.
+    pub const RESOLVE_EXPORT: u64 = 0x106;
+    /// Calls the function at index `r0`, which returns to the instruction after the system
+    /// call. Returns `u64::MAX` without calling if there is no instruction at the index.
+    pub const CALL: u64 = 0x107;
+    /// Returns the handle of data section `r1` of module `r0`, or `u64::MAX` if there is no such
+    /// data section. The program itself is module 0.
+    pub const MODULE_DATA_SECTION: u64 = 0x108;
+
+    /// Loads modules with the default `LoadPolicy`, which refuses files without a checksum.
+    pub fn new() -> Self {
+        return Self::with_policy(LoadPolicy::default());
+    }
+
+    pub fn with_policy(policy: LoadPolicy) -> Self {
+        return Self { policy };
+    }
+
+    pub fn execute_call(&mut self, call: u64, machine: &mut VM) -> Option<u64> {
+        let registers = machine.registers();
+        let r0 = registers.get_value(Register::R0 as u8);
+        let r1 = registers.get_value(Register::R1 as u8);
+        let r2 = registers.get_value(Register::R2 as u8);
+        let r3 = registers.get_value(Register::R3 as u8);
+        let r4 = registers.get_value(Register::R4 as u8);
+
+        match call {
+            Self::MEMORY_FILL => {
+                return Some(Self::status(
+                    machine.memory_mut().fill(&r0, &r1, &r2, r3 as u8),
+                ));
+            }
+            Self::MEMORY_COMPARE => {
+                let result = machine.memory().compare(&r0, &r1, &r2, &r3, &r4);
+
+                return Some(Self::status(result.map(|ordering| {
+                    machine.set_comparison_flags(ordering);
+                })));
+            }
+            Self::MEMORY_FIND => {
+                return Some(
+                    machine
+                        .memory()
+                        .find(&r0, &r1, &r2, r3 as u8)
+                        .ok()
+                        .flatten()
+                        .unwrap_or(u64::MAX),
+                );
+            }
+            Self::MEMORY_RESIZE => {
+                return Some(Self::status(machine.memory_mut().resize(&r0, r1)));
+            }
+            Self::DATA_SECTION => {
+                return Some(machine.data_section(r0 as usize).unwrap_or(u64::MAX));
+            }
+            Self::LOAD_MODULE => {
+                return Some(self.load_module(r0, machine).unwrap_or(u64::MAX));
+            }
+            Self::RESOLVE_EXPORT => {
+                let name = machine
+                    .memory()
+                    .retrieve(&r1)
+                    .and_then(|bytes| String::from_utf8(bytes.clone()).ok());
+
+                return Some(
+                    name.and_then(|name| machine.resolve_export(r0, &name))
+                        .unwrap_or(u64::MAX),
+                );
+            }
+            Self::CALL => {
+                if r0 >= machine.instructions().len() as u64 {
+                    return Some(u64::MAX);
+                }
+
+                return Some(machine.call(r0 as usize).map_or(u64::MAX, |_| 0));
+            }
+            Self::MODULE_DATA_SECTION => {
+                return Some(
+                    machine
+                        .module_data_section(r0, r1 as usize)
+                        .unwrap_or(u64::MAX),
+                );
+            }
+            _ => return None,
+        }
+    }
+
+    fn load_module(&self, address: u64, machine: &mut VM) -> Option<u64> {
+        let loader = self
+            .policy
+            .load_bytes(machine.memory().retrieve(&address)?)
+            .ok()?;
+
+        let module = Module::from_loader(loader).ok()?;
+
+        return machine.load_module(&module).ok();
+    }
+
+    fn status(result: Result<(), VMError>) -> u64 {
+        return match result {
+            Ok(()) => 0,
+            Err(VMError::IndexBeyondBoundsError(_, _)) => 2,
+            Err(_) => 1,
+        };
+    }
+}
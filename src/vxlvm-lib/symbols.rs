@@ -0,0 +1,321 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+
+use crate::error::LoaderError;
+
+/*
+The contents of a symbols section, all integers are little endian u64s and strings are a length
+followed by that many bytes of UTF-8.
+
+Number of functions, then for each function its first instruction index and name
+Number of source files, then each file name
+Number of line entries, then for each entry its instruction index, file number and line
+*/
+
+/// Maps instruction indices to the functions and source lines they were assembled from.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct SymbolTable {
+    /// Sorted by the index of the first instruction.
+    functions: Vec<Function>,
+    files: Vec<String>,
+    /// Sorted by instruction index, each entry applies until the next.
+    lines: Vec<LineEntry>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Function {
+    start: u64,
+    name: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+struct LineEntry {
+    index: u64,
+    file: u64,
+    line: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct SourceLocation<'a> {
+    pub file: &'a str,
+    pub line: u64,
+}
+
+impl Function {
+    pub fn start(&self) -> u64 {
+        return self.start;
+    }
+
+    pub fn name(&self) -> &str {
+        return &self.name;
+    }
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    /// Names the function beginning at the instruction `start`, replacing any existing name.
+    pub fn add_function(&mut self, start: u64, name: String) {
+        match self.functions.binary_search_by_key(&start, |f| f.start) {
+            Ok(i) => self.functions[i].name = name,
+            Err(i) => self.functions.insert(i, Function { start, name }),
+        }
+    }
+
+    /// Returns the number used to refer to the file in `add_line`.
+    pub fn add_file(&mut self, name: String) -> u64 {
+        if let Some(i) = self.files.iter().position(|file| *file == name) {
+            return i as u64;
+        }
+
+        self.files.push(name);
+
+        return self.files.len() as u64 - 1;
+    }
+
+    /// Records that the instructions from `index` onwards were assembled from `line` of `file`.
+    pub fn add_line(&mut self, index: u64, file: u64, line: u64) {
+        let entry = LineEntry { index, file, line };
+
+        match self.lines.binary_search_by_key(&index, |e| e.index) {
+            Ok(i) => self.lines[i] = entry,
+            Err(i) => self.lines.insert(i, entry),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.functions.is_empty() && self.lines.is_empty();
+    }
+
+    pub fn functions(&self) -> &[Function] {
+        return &self.functions;
+    }
+
+    /// Returns the function containing the instruction, the last one starting at or before it.
+    pub fn function_at(&self, index: u64) -> Option<&Function> {
+        let end = self.functions.partition_point(|f| f.start <= index);
+
+        return end.checked_sub(1).map(|i| &self.functions[i]);
+    }
+
+    /// Returns the function that begins at exactly this instruction.
+    pub fn function_starting_at(&self, index: u64) -> Option<&Function> {
+        return self
+            .function_at(index)
+            .filter(|function| function.start == index);
+    }
+
+    pub fn function_named(&self, name: &str) -> Option<&Function> {
+        return self.functions.iter().find(|function| function.name == name);
+    }
+
+    pub fn location_at(&self, index: u64) -> Option<SourceLocation<'_>> {
+        let end = self.lines.partition_point(|e| e.index <= index);
+        let entry = self.lines.get(end.checked_sub(1)?)?;
+
+        return Some(SourceLocation {
+            file: self.files.get(entry.file as usize)?,
+            line: entry.line,
+        });
+    }
+
+    /// Describes an instruction as `function+offset (file:line)`, falling back to its index for
+    /// anything that is unknown.
+    pub fn describe(&self, index: u64) -> String {
+        let mut description = match self.function_at(index) {
+            Some(function) if function.start == index => String::from(function.name()),
+            Some(function) => format!("{}+{}", function.name, index - function.start),
+            None => format!("instruction {}", index),
+        };
+
+        if let Some(location) = self.location_at(index) {
+            description.push_str(&format!(" ({}:{})", location.file, location.line));
+        }
+
+        return description;
+    }
+
+    /// Adds every symbol of `other` with its instruction indices moved forward by `offset`.
+    pub fn append(&mut self, other: &SymbolTable, offset: u64) {
+        for function in &other.functions {
+            self.add_function(function.start + offset, function.name.clone());
+        }
+
+        for entry in &other.lines {
+            let file = self.add_file(other.files[entry.file as usize].clone());
+
+            self.add_line(entry.index + offset, file, entry.line);
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        bytes.extend_from_slice(&(self.functions.len() as u64).to_le_bytes());
+
+        for function in &self.functions {
+            bytes.extend_from_slice(&function.start.to_le_bytes());
+            write_string(&function.name, &mut bytes);
+        }
+
+        bytes.extend_from_slice(&(self.files.len() as u64).to_le_bytes());
+
+        for file in &self.files {
+            write_string(file, &mut bytes);
+        }
+
+        bytes.extend_from_slice(&(self.lines.len() as u64).to_le_bytes());
+
+        for entry in &self.lines {
+            bytes.extend_from_slice(&entry.index.to_le_bytes());
+            bytes.extend_from_slice(&entry.file.to_le_bytes());
+            bytes.extend_from_slice(&entry.line.to_le_bytes());
+        }
+
+        return bytes;
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, LoaderError> {
+        let mut reader = Reader::new(bytes, LoaderError::InvalidSymbolTable);
+        let mut table = Self::new();
+
+        for _ in 0..reader.read_u64()? {
+            let start = reader.read_u64()?;
+            let name = reader.read_string()?;
+
+            table.add_function(start, name);
+        }
+
+        for _ in 0..reader.read_u64()? {
+            table.files.push(reader.read_string()?);
+        }
+
+        for _ in 0..reader.read_u64()? {
+            let index = reader.read_u64()?;
+            let file = reader.read_u64()?;
+            let line = reader.read_u64()?;
+
+            if file >= table.files.len() as u64 {
+                return Err(LoaderError::InvalidSymbolTable);
+            }
+
+            table.add_line(index, file, line);
+        }
+
+        reader.finish()?;
+
+        return Ok(table);
+    }
+}
+
+pub(crate) fn write_string(string: &str, bytes: &mut Vec<u8>) {
+    bytes.extend_from_slice(&(string.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(string.as_bytes());
+}
+
+/// Reads the integers and strings of a section, reporting `error` if it is malformed.
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    error: LoaderError,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8], error: LoaderError) -> Self {
+        return Self {
+            bytes,
+            position: 0,
+            error,
+        };
+    }
+
+    fn read(&mut self, length: u64) -> Result<&'a [u8], LoaderError> {
+        let end = (self.position as u64)
+            .checked_add(length)
+            .filter(|end| *end <= self.bytes.len() as u64)
+            .ok_or(self.error)? as usize;
+
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+
+        return Ok(bytes);
+    }
+
+    pub(crate) fn read_u64(&mut self) -> Result<u64, LoaderError> {
+        return Ok(u64::from_le_bytes(self.read(8)?.try_into().unwrap()));
+    }
+
+    pub(crate) fn read_string(&mut self) -> Result<String, LoaderError> {
+        let length = self.read_u64()?;
+        let error = self.error;
+
+        return String::from_utf8(self.read(length)?.to_vec()).map_err(|_| error);
+    }
+
+    /// Fails if there are unread bytes.
+    pub(crate) fn finish(&self) -> Result<(), LoaderError> {
+        if self.position != self.bytes.len() {
+            return Err(self.error);
+        }
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> SymbolTable {
+        let mut table = SymbolTable::new();
+
+        table.add_function(4, String::from("pow"));
+        table.add_function(0, String::from("main"));
+
+        let file = table.add_file(String::from("pow.vsm"));
+        table.add_line(0, file, 1);
+        table.add_line(4, file, 7);
+        table.add_line(6, file, 9);
+
+        return table;
+    }
+
+    #[test]
+    fn test_lookups() {
+        let table = example();
+
+        assert_eq!(table.function_at(3).unwrap().name(), "main");
+        assert_eq!(table.function_at(4).unwrap().name(), "pow");
+        assert_eq!(table.function_at(100).unwrap().name(), "pow");
+        assert!(table.function_starting_at(5).is_none());
+        assert_eq!(table.function_named("pow").unwrap().start(), 4);
+
+        assert_eq!(
+            table.location_at(5),
+            Some(SourceLocation {
+                file: "pow.vsm",
+                line: 7
+            })
+        );
+
+        assert_eq!(table.describe(4), "pow (pow.vsm:7)");
+        assert_eq!(table.describe(7), "pow+3 (pow.vsm:9)");
+        assert_eq!(SymbolTable::new().describe(2), "instruction 2");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let table = example();
+        let bytes = table.to_bytes();
+
+        assert_eq!(SymbolTable::from_bytes(&bytes), Ok(table));
+        assert_eq!(
+            SymbolTable::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(LoaderError::InvalidSymbolTable)
+        );
+    }
+}
@@ -1,10 +1,12 @@
 use core::usize;
 
+use alloc::vec;
 use alloc::vec::Vec;
 use vxl_iset::instruction::Instruction;
 use vxl_iset::instruction_arguments::{Address, Immediate, InstructionArgument, Register};
 
-use crate::error::ValidatorError;
+use crate::effects::branch_target;
+use crate::error::{ProgramError, ValidatorError};
 
 pub trait Validator {
     fn append_bytes(&mut self, bytes: Vec<u8>);
@@ -113,6 +115,11 @@ impl BulkValidator {
             bytes,
         };
     }
+
+    /// The number of bytes consumed so far.
+    pub fn position(&self) -> usize {
+        return self.current_location;
+    }
 }
 
 impl Validator for BulkValidator {
@@ -143,6 +150,194 @@ impl Validator for BulkValidator {
     }
 }
 
+/// Decodes instructions from bytes that arrive in chunks of any size, such as bytecode streamed
+/// from a socket or typed into a REPL. An instruction is only decoded once all of its bytes have
+/// arrived, until then its bytes stay buffered.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct IncrementalValidator {
+    current_location: usize,
+    bytes: Vec<u8>,
+    /// The number of bytes dropped from the front of `bytes`.
+    dropped: usize,
+}
+
+impl IncrementalValidator {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    /// The number of bytes consumed so far.
+    pub fn position(&self) -> usize {
+        return self.dropped + self.current_location;
+    }
+
+    /// The number of bytes received that aren't part of a decoded instruction.
+    pub fn buffered(&self) -> usize {
+        return self.bytes.len() - self.current_location;
+    }
+
+    /// Decodes the next instruction if all of its bytes have arrived.
+    ///
+    /// After an error the bytes that follow can't be decoded reliably, so everything buffered is
+    /// discarded.
+    pub fn next_instruction(&mut self) -> Result<Option<Instruction>, ValidatorError> {
+        let opcode = match self.bytes.get(self.current_location) {
+            Some(opcode) => *opcode,
+            None => return Ok(None),
+        };
+
+        // An unknown opcode is left for `take_next_instruction` to report.
+        if let Some(length) = instruction_length(opcode) {
+            if self.buffered() < length {
+                return Ok(None);
+            }
+        }
+
+        let result = self.take_next_instruction();
+
+        if result.is_err() {
+            self.current_location = self.bytes.len();
+        }
+
+        return result.map(Some);
+    }
+
+    /// Decodes every instruction whose bytes have all arrived.
+    pub fn take_instructions(&mut self) -> Result<Vec<Instruction>, ValidatorError> {
+        let mut instructions = Vec::new();
+
+        while let Some(instruction) = self.next_instruction()? {
+            instructions.push(instruction);
+        }
+
+        return Ok(instructions);
+    }
+
+    /// Drops the bytes of decoded instructions.
+    fn compact(&mut self) {
+        self.bytes.drain(..self.current_location);
+        self.dropped += self.current_location;
+        self.current_location = 0;
+    }
+}
+
+impl Validator for IncrementalValidator {
+    fn append_bytes(&mut self, bytes: Vec<u8>) {
+        self.compact();
+        self.bytes.extend(bytes);
+    }
+
+    fn append_byte(&mut self, byte: u8) {
+        self.compact();
+        self.bytes.push(byte);
+    }
+
+    fn next_byte(&mut self) -> Option<u8> {
+        let byte = *self.bytes.get(self.current_location)?;
+        self.current_location += 1;
+
+        return Some(byte);
+    }
+
+    fn has_next_byte(&self) -> bool {
+        return self.current_location < self.bytes.len();
+    }
+}
+
+/// The number of bytes in an encoded instruction with the opcode.
+fn instruction_length(opcode: u8) -> Option<usize> {
+    let registers = Instruction::register_count(opcode)?;
+    let addresses = Instruction::address_count(opcode)?;
+    let immediates = Instruction::immediate_count(opcode)?;
+
+    return Some(
+        1 + addresses * Address::BYTES + immediates * Immediate::BYTES + registers.div_ceil(2),
+    );
+}
+
+/// Checks the control flow of a decoded program, returning every problem found in order of
+/// instruction index.
+///
+/// Every jump and call must target an instruction and the entry offset must be an instruction.
+/// Execution must not be able to continue past the last instruction, which is only checked if
+/// it can be reached from the entry offset. Calls are assumed to return and calls made by system
+/// calls are not followed.
+pub fn verify_program(instructions: &[Instruction], entry: u64) -> Vec<ProgramError> {
+    return verify_module(instructions, 0, entry, &[]);
+}
+
+/// Checks the control flow of a module placed after the first `base` instructions of a program,
+/// as `verify_program` does for a whole program.
+///
+/// The instructions must already be placed with `linker::place_module`, so jumps and calls may
+/// also target any earlier instruction, which was checked when it was loaded. The entry offset and
+/// exports are indices into the module and execution is followed from each of them. Problems are
+/// reported at indices of the combined program.
+pub fn verify_module(
+    instructions: &[Instruction],
+    base: u64,
+    entry: u64,
+    exports: &[u64],
+) -> Vec<ProgramError> {
+    let mut problems = Vec::new();
+    let end = base + instructions.len() as u64;
+
+    for (index, instruction) in instructions.iter().enumerate() {
+        if let Some(target) = branch_target(instruction) {
+            if target >= end {
+                problems.push(ProgramError::TargetOutOfBounds(
+                    base as usize + index,
+                    target,
+                ));
+            }
+        }
+    }
+
+    if entry >= instructions.len() as u64 {
+        problems.push(ProgramError::EntryOutOfBounds(entry));
+        return problems;
+    }
+
+    let mut reachable = vec![false; instructions.len()];
+    let mut pending = vec![entry as usize];
+    pending.extend(exports.iter().map(|export| *export as usize));
+
+    while let Some(index) = pending.pop() {
+        if index >= instructions.len() || reachable[index] {
+            continue;
+        }
+
+        reachable[index] = true;
+
+        let instruction = &instructions[index];
+
+        // Targets before the module are in code that has already been checked.
+        if let Some(target) = branch_target(instruction) {
+            if target >= base {
+                pending.push((target - base) as usize);
+            }
+        }
+
+        if falls_through(instruction) {
+            if index + 1 == instructions.len() {
+                problems.push(ProgramError::FallsOffEnd(base as usize + index));
+            }
+
+            pending.push(index + 1);
+        }
+    }
+
+    return problems;
+}
+
+/// Whether execution can continue with the next instruction, calls are assumed to return.
+fn falls_through(instruction: &Instruction) -> bool {
+    return !matches!(
+        instruction,
+        Instruction::Jmp(_) | Instruction::Ret | Instruction::Halt
+    );
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -282,4 +477,184 @@ mod tests {
             );
         }
     }
+
+    mod incremental_tests {
+        use super::*;
+        use crate::writer::encode_instruction;
+        use alloc::vec;
+
+        #[test]
+        fn test_byte_at_a_time() {
+            let instructions = vec![
+                Instruction::Ldi(Immediate::from(63i64), Register::R0),
+                Instruction::Mov(Register::R1, Register::R0),
+                Instruction::Call(Address::from(0u64)),
+                Instruction::Halt,
+            ];
+
+            let mut bytes = Vec::new();
+
+            for instruction in &instructions {
+                encode_instruction(*instruction, &mut bytes);
+            }
+
+            let mut validator = IncrementalValidator::new();
+            let mut decoded = Vec::new();
+
+            for byte in &bytes {
+                validator.append_byte(*byte);
+
+                while let Some(instruction) = validator.next_instruction().unwrap() {
+                    decoded.push(instruction);
+                }
+            }
+
+            assert_eq!(decoded, instructions);
+            assert_eq!(validator.position(), bytes.len());
+            assert_eq!(validator.buffered(), 0);
+        }
+
+        #[test]
+        fn test_partial_instruction() {
+            let mut bytes = Vec::new();
+            encode_instruction(
+                Instruction::Ldi(Immediate::from(1i64), Register::R0),
+                &mut bytes,
+            );
+            encode_instruction(Instruction::Nop, &mut bytes);
+
+            let mut validator = IncrementalValidator::new();
+
+            validator.append_bytes(bytes[..4].to_vec());
+            assert_eq!(validator.take_instructions(), Ok(vec![]));
+            assert_eq!(validator.buffered(), 4);
+
+            validator.append_bytes(bytes[4..].to_vec());
+            assert_eq!(
+                validator.take_instructions(),
+                Ok(vec![
+                    Instruction::Ldi(Immediate::from(1i64), Register::R0),
+                    Instruction::Nop
+                ])
+            );
+
+            // Unlike the bulk validator, running out of bytes is only an error when asked for.
+            validator.append_byte(0b0000_0011);
+            assert_eq!(validator.next_instruction(), Ok(None));
+            assert_eq!(
+                validator.clone().process_all_instructions(),
+                Err(ValidatorError::UnexpectedEndOfBytes)
+            );
+        }
+
+        #[test]
+        fn test_invalid_opcode() {
+            let mut validator = IncrementalValidator::new();
+
+            validator.append_bytes(vec![0xff, 0x00]);
+            assert!(validator.next_instruction().is_err());
+            assert_eq!(validator.buffered(), 0);
+
+            validator.append_byte(0x00);
+            assert_eq!(validator.next_instruction(), Ok(Some(Instruction::Nop)));
+            assert_eq!(validator.position(), 3);
+        }
+    }
+
+    mod program_tests {
+        use super::*;
+        use alloc::vec;
+
+        #[test]
+        fn test_valid_program() {
+            // 0: jmp 2
+            // 1: halt
+            // 2: call 4
+            // 3: jmp 1
+            // 4: ret
+            let instructions = vec![
+                Instruction::Jmp(Address::from(2u64)),
+                Instruction::Halt,
+                Instruction::Call(Address::from(4u64)),
+                Instruction::Jmp(Address::from(1u64)),
+                Instruction::Ret,
+            ];
+
+            assert_eq!(verify_program(&instructions, 0), vec![]);
+        }
+
+        #[test]
+        fn test_reports_every_problem() {
+            // 0: jeq 9
+            // 1: call 5
+            // 2: nop
+            let instructions = vec![
+                Instruction::Jeq(Address::from(9u64)),
+                Instruction::Call(Address::from(5u64)),
+                Instruction::Nop,
+            ];
+
+            assert_eq!(
+                verify_program(&instructions, 0),
+                vec![
+                    ProgramError::TargetOutOfBounds(0, 9),
+                    ProgramError::TargetOutOfBounds(1, 5),
+                    ProgramError::FallsOffEnd(2),
+                ]
+            );
+            assert_eq!(
+                verify_program(&instructions, 3),
+                vec![
+                    ProgramError::TargetOutOfBounds(0, 9),
+                    ProgramError::TargetOutOfBounds(1, 5),
+                    ProgramError::EntryOutOfBounds(3),
+                ]
+            );
+            assert_eq!(
+                verify_program(&[], 0),
+                vec![ProgramError::EntryOutOfBounds(0)]
+            );
+        }
+
+        #[test]
+        fn test_unreachable_end() {
+            // The nop after the halt can't be reached.
+            let instructions = vec![Instruction::Halt, Instruction::Nop];
+
+            assert_eq!(verify_program(&instructions, 0), vec![]);
+            assert_eq!(
+                verify_program(&instructions, 1),
+                vec![ProgramError::FallsOffEnd(1)]
+            );
+        }
+
+        #[test]
+        fn test_module() {
+            // Placed after two instructions of an earlier module:
+            // 2: call 0
+            // 3: ret
+            // 4: jmp 2
+            // 5: nop
+            let instructions = vec![
+                Instruction::Call(Address::from(0u64)),
+                Instruction::Ret,
+                Instruction::Jmp(Address::from(2u64)),
+                Instruction::Nop,
+            ];
+
+            assert_eq!(verify_module(&instructions, 2, 0, &[2]), vec![]);
+
+            // The export at 3 can continue past the last instruction.
+            assert_eq!(
+                verify_module(&instructions, 2, 0, &[3]),
+                vec![ProgramError::FallsOffEnd(5)]
+            );
+
+            // Targets are checked against the end of the module rather than its length.
+            assert_eq!(
+                verify_module(&[Instruction::Jmp(Address::from(3u64))], 2, 0, &[]),
+                vec![ProgramError::TargetOutOfBounds(2, 3)]
+            );
+        }
+    }
 }
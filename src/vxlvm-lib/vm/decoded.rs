@@ -0,0 +1,339 @@
+use super::VM;
+use crate::error::VMError;
+
+use alloc::vec::Vec;
+use vxl_iset::instruction::Instruction;
+use vxl_iset::instruction_arguments::Register;
+
+/// A function that executes a single pre-decoded instruction against the machine.
+///
+/// A return value of true indicates that the IP was modified and shouldn't be updated this cycle.
+pub(super) type Handler = fn(&mut VM, &Operands) -> Result<bool, VMError>;
+
+/// The operands of an instruction with registers resolved to their indices and immediates and
+/// addresses converted to their raw values.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub(super) struct Operands {
+    pub(super) registers: [u8; 5],
+    pub(super) values: [u64; 3],
+}
+
+/// An instruction in the form that the machine executes it.
+///
+/// Every instruction except for syscall is resolved to the handler that implements it, so
+/// dispatching is a single indirect call. Syscalls depend on the handler passed to `VM::run`
+/// and so are dispatched separately.
+#[derive(Clone, Copy)]
+pub(super) enum DecodedInstruction {
+    Native(Handler, Operands),
+    Syscall(u64),
+}
+
+impl Operands {
+    pub(super) fn new<const R: usize, const V: usize>(
+        registers: [Register; R],
+        values: [u64; V],
+    ) -> Self {
+        let mut operands = Self::default();
+
+        for (slot, register) in operands.registers.iter_mut().zip(registers) {
+            *slot = register as u8;
+        }
+
+        operands.values[..V].copy_from_slice(&values);
+
+        return operands;
+    }
+}
+
+impl DecodedInstruction {
+    pub(super) fn decode(instruction: Instruction) -> Self {
+        use DecodedInstruction::Native;
+
+        return match instruction {
+            Instruction::Nop => Native(|vm, _| vm.op_nop(), Operands::default()),
+            Instruction::Syscall(i) => DecodedInstruction::Syscall(i.into()),
+            // The mask is applied here so that ldb can share the ldi handler.
+            Instruction::Ldb(i, r) => Native(
+                |vm, o| vm.op_ldi(o.values[0], o.registers[0]),
+                Operands::new([r], [u64::from(i) & 0xFF]),
+            ),
+            Instruction::Ldi(i, r) => Native(
+                |vm, o| vm.op_ldi(o.values[0], o.registers[0]),
+                Operands::new([r], [i.into()]),
+            ),
+            Instruction::Ldf(i, r) => Native(
+                |vm, o| vm.op_ldf(o.values[0], o.registers[0]),
+                Operands::new([r], [i.into()]),
+            ),
+            Instruction::Mov(r, r1) => Native(
+                |vm, o| vm.op_mov(o.registers[0], o.registers[1]),
+                Operands::new([r, r1], []),
+            ),
+            Instruction::Push(r) => {
+                Native(|vm, o| vm.op_push(o.registers[0]), Operands::new([r], []))
+            }
+            Instruction::Pop(r) => {
+                Native(|vm, o| vm.op_pop(o.registers[0]), Operands::new([r], []))
+            }
+            Instruction::Sget(r, r1) => Native(
+                |vm, o| vm.op_sget(o.registers[0], o.registers[1]),
+                Operands::new([r, r1], []),
+            ),
+            Instruction::Malloc(r, r1) => Native(
+                |vm, o| vm.op_malloc(o.registers[0], o.registers[1]),
+                Operands::new([r, r1], []),
+            ),
+            Instruction::Malloci(i, r) => Native(
+                |vm, o| vm.op_malloci(o.values[0], o.registers[0]),
+                Operands::new([r], [i.into()]),
+            ),
+            Instruction::Free(r) => {
+                Native(|vm, o| vm.op_free(o.registers[0]), Operands::new([r], []))
+            }
+            Instruction::Freea(a) => Native(
+                |vm, o| vm.op_freea(o.values[0]),
+                Operands::new([], [a.into()]),
+            ),
+            Instruction::Setb(r, r1, r2) => Native(
+                |vm, o| vm.op_setb(o.registers[0], o.registers[1], o.registers[2]),
+                Operands::new([r, r1, r2], []),
+            ),
+            Instruction::Seti(r, r1, r2) => Native(
+                |vm, o| vm.op_seti(o.registers[0], o.registers[1], o.registers[2]),
+                Operands::new([r, r1, r2], []),
+            ),
+            Instruction::Isetb(i, r, r1) => Native(
+                |vm, o| vm.op_isetb(o.values[0], o.registers[0], o.registers[1]),
+                Operands::new([r, r1], [i.into()]),
+            ),
+            Instruction::Iseti(i, r, r1) => Native(
+                |vm, o| vm.op_iseti(o.values[0], o.registers[0], o.registers[1]),
+                Operands::new([r, r1], [i.into()]),
+            ),
+            Instruction::Getb(r, r1, r2) => Native(
+                |vm, o| vm.op_getb(o.registers[0], o.registers[1], o.registers[2]),
+                Operands::new([r, r1, r2], []),
+            ),
+            Instruction::Geti(r, r1, r2) => Native(
+                |vm, o| vm.op_geti(o.registers[0], o.registers[1], o.registers[2]),
+                Operands::new([r, r1, r2], []),
+            ),
+            Instruction::Igetb(i, r, r1) => Native(
+                |vm, o| vm.op_igetb(o.values[0], o.registers[0], o.registers[1]),
+                Operands::new([r, r1], [i.into()]),
+            ),
+            Instruction::Igeti(i, r, r1) => Native(
+                |vm, o| vm.op_igeti(o.values[0], o.registers[0], o.registers[1]),
+                Operands::new([r, r1], [i.into()]),
+            ),
+            Instruction::Last(r, r1) => Native(
+                |vm, o| vm.op_last(o.registers[0], o.registers[1]),
+                Operands::new([r, r1], []),
+            ),
+            Instruction::Length(r, r1) => Native(
+                |vm, o| vm.op_length(o.registers[0], o.registers[1]),
+                Operands::new([r, r1], []),
+            ),
+            Instruction::Clone(r, r1) => Native(
+                |vm, o| vm.op_clone(o.registers[0], o.registers[1]),
+                Operands::new([r, r1], []),
+            ),
+            Instruction::Copy(r, r1, r2, r3, r4) => Native(
+                |vm, o| {
+                    vm.op_copy(
+                        o.registers[0],
+                        o.registers[1],
+                        o.registers[2],
+                        o.registers[3],
+                        o.registers[4],
+                    )
+                },
+                Operands::new([r, r1, r2, r3, r4], []),
+            ),
+            Instruction::Copyi(i, i1, i2, r, r1) => Native(
+                |vm, o| {
+                    vm.op_copyi(
+                        o.values[0],
+                        o.values[1],
+                        o.values[2],
+                        o.registers[0],
+                        o.registers[1],
+                    )
+                },
+                Operands::new([r, r1], [i.into(), i1.into(), i2.into()]),
+            ),
+            Instruction::Addi(r, r1, r2) => Native(
+                |vm, o| vm.op_addi(o.registers[0], o.registers[1], o.registers[2]),
+                Operands::new([r, r1, r2], []),
+            ),
+            Instruction::Subi(r, r1, r2) => Native(
+                |vm, o| vm.op_subi(o.registers[0], o.registers[1], o.registers[2]),
+                Operands::new([r, r1, r2], []),
+            ),
+            Instruction::Muli(r, r1, r2) => Native(
+                |vm, o| vm.op_muli(o.registers[0], o.registers[1], o.registers[2]),
+                Operands::new([r, r1, r2], []),
+            ),
+            Instruction::Divi(r, r1, r2) => Native(
+                |vm, o| vm.op_divi(o.registers[0], o.registers[1], o.registers[2]),
+                Operands::new([r, r1, r2], []),
+            ),
+            Instruction::Modi(r, r1, r2) => Native(
+                |vm, o| vm.op_modi(o.registers[0], o.registers[1], o.registers[2]),
+                Operands::new([r, r1, r2], []),
+            ),
+            Instruction::Addu(r, r1, r2) => Native(
+                |vm, o| vm.op_addu(o.registers[0], o.registers[1], o.registers[2]),
+                Operands::new([r, r1, r2], []),
+            ),
+            Instruction::Subu(r, r1, r2) => Native(
+                |vm, o| vm.op_subu(o.registers[0], o.registers[1], o.registers[2]),
+                Operands::new([r, r1, r2], []),
+            ),
+            Instruction::Mulu(r, r1, r2) => Native(
+                |vm, o| vm.op_mulu(o.registers[0], o.registers[1], o.registers[2]),
+                Operands::new([r, r1, r2], []),
+            ),
+            Instruction::Divu(r, r1, r2) => Native(
+                |vm, o| vm.op_divu(o.registers[0], o.registers[1], o.registers[2]),
+                Operands::new([r, r1, r2], []),
+            ),
+            Instruction::Modu(r, r1, r2) => Native(
+                |vm, o| vm.op_modu(o.registers[0], o.registers[1], o.registers[2]),
+                Operands::new([r, r1, r2], []),
+            ),
+            Instruction::Addf(r, r1, r2) => Native(
+                |vm, o| vm.op_addf(o.registers[0], o.registers[1], o.registers[2]),
+                Operands::new([r, r1, r2], []),
+            ),
+            Instruction::Subf(r, r1, r2) => Native(
+                |vm, o| vm.op_subf(o.registers[0], o.registers[1], o.registers[2]),
+                Operands::new([r, r1, r2], []),
+            ),
+            Instruction::Mulf(r, r1, r2) => Native(
+                |vm, o| vm.op_mulf(o.registers[0], o.registers[1], o.registers[2]),
+                Operands::new([r, r1, r2], []),
+            ),
+            Instruction::Divf(r, r1, r2) => Native(
+                |vm, o| vm.op_divf(o.registers[0], o.registers[1], o.registers[2]),
+                Operands::new([r, r1, r2], []),
+            ),
+            Instruction::Rotl(r, r1) => Native(
+                |vm, o| vm.op_rotl(o.registers[0], o.registers[1]),
+                Operands::new([r, r1], []),
+            ),
+            Instruction::Rotli(i, r) => Native(
+                |vm, o| vm.op_rotli(o.values[0], o.registers[0]),
+                Operands::new([r], [i.into()]),
+            ),
+            Instruction::Rotr(r, r1) => Native(
+                |vm, o| vm.op_rotr(o.registers[0], o.registers[1]),
+                Operands::new([r, r1], []),
+            ),
+            Instruction::Rotri(i, r) => Native(
+                |vm, o| vm.op_rotri(o.values[0], o.registers[0]),
+                Operands::new([r], [i.into()]),
+            ),
+            Instruction::Sll(r, r1) => Native(
+                |vm, o| vm.op_sll(o.registers[0], o.registers[1]),
+                Operands::new([r, r1], []),
+            ),
+            Instruction::Slli(i, r) => Native(
+                |vm, o| vm.op_slli(o.values[0], o.registers[0]),
+                Operands::new([r], [i.into()]),
+            ),
+            Instruction::Srl(r, r1) => Native(
+                |vm, o| vm.op_srl(o.registers[0], o.registers[1]),
+                Operands::new([r, r1], []),
+            ),
+            Instruction::Srli(i, r) => Native(
+                |vm, o| vm.op_srli(o.values[0], o.registers[0]),
+                Operands::new([r], [i.into()]),
+            ),
+            Instruction::Not(r) => {
+                Native(|vm, o| vm.op_not(o.registers[0]), Operands::new([r], []))
+            }
+            Instruction::And(r, r1, r2) => Native(
+                |vm, o| vm.op_and(o.registers[0], o.registers[1], o.registers[2]),
+                Operands::new([r, r1, r2], []),
+            ),
+            Instruction::Or(r, r1, r2) => Native(
+                |vm, o| vm.op_or(o.registers[0], o.registers[1], o.registers[2]),
+                Operands::new([r, r1, r2], []),
+            ),
+            Instruction::Xor(r, r1, r2) => Native(
+                |vm, o| vm.op_xor(o.registers[0], o.registers[1], o.registers[2]),
+                Operands::new([r, r1, r2], []),
+            ),
+            Instruction::Cmp(r, r1) => Native(
+                |vm, o| vm.op_cmp(o.registers[0], o.registers[1]),
+                Operands::new([r, r1], []),
+            ),
+            Instruction::Cmpi(r, r1) => Native(
+                |vm, o| vm.op_cmpi(o.registers[0], o.registers[1]),
+                Operands::new([r, r1], []),
+            ),
+            Instruction::Cmpf(r, r1) => Native(
+                |vm, o| vm.op_cmpf(o.registers[0], o.registers[1]),
+                Operands::new([r, r1], []),
+            ),
+            Instruction::Jmp(a) => Native(
+                |vm, o| vm.op_jmp(o.values[0] as usize),
+                Operands::new([], [a.into()]),
+            ),
+            Instruction::Jeq(a) => Native(
+                |vm, o| vm.op_jeq(o.values[0] as usize),
+                Operands::new([], [a.into()]),
+            ),
+            Instruction::Jne(a) => Native(
+                |vm, o| vm.op_jne(o.values[0] as usize),
+                Operands::new([], [a.into()]),
+            ),
+            Instruction::Jge(a) => Native(
+                |vm, o| vm.op_jge(o.values[0] as usize),
+                Operands::new([], [a.into()]),
+            ),
+            Instruction::Jgt(a) => Native(
+                |vm, o| vm.op_jgt(o.values[0] as usize),
+                Operands::new([], [a.into()]),
+            ),
+            Instruction::Jle(a) => Native(
+                |vm, o| vm.op_jle(o.values[0] as usize),
+                Operands::new([], [a.into()]),
+            ),
+            Instruction::Jlt(a) => Native(
+                |vm, o| vm.op_jlt(o.values[0] as usize),
+                Operands::new([], [a.into()]),
+            ),
+            Instruction::I2f(r) => {
+                Native(|vm, o| vm.op_i2f(o.registers[0]), Operands::new([r], []))
+            }
+            Instruction::F2i(r) => {
+                Native(|vm, o| vm.op_f2i(o.registers[0]), Operands::new([r], []))
+            }
+            Instruction::Swpa(a, a1) => Native(
+                |vm, o| vm.op_swpa(o.values[0], o.values[1]),
+                Operands::new([], [a.into(), a1.into()]),
+            ),
+            Instruction::Swpar(r, r1) => Native(
+                |vm, o| vm.op_swpar(o.registers[0], o.registers[1]),
+                Operands::new([r, r1], []),
+            ),
+            Instruction::Swpr(r, r1) => Native(
+                |vm, o| vm.op_swpr(o.registers[0], o.registers[1]),
+                Operands::new([r, r1], []),
+            ),
+            Instruction::Call(a) => Native(
+                |vm, o| vm.op_call(o.values[0] as usize),
+                Operands::new([], [a.into()]),
+            ),
+            Instruction::Ret => Native(|vm, _| vm.op_ret(), Operands::default()),
+            Instruction::Halt => Native(|vm, _| vm.op_halt(), Operands::default()),
+        };
+    }
+
+    pub(super) fn decode_all(instructions: &[Instruction]) -> Vec<Self> {
+        return instructions.iter().map(|i| Self::decode(*i)).collect();
+    }
+}
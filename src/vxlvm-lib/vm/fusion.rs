@@ -0,0 +1,161 @@
+use super::decoded::{DecodedInstruction, Handler, Operands};
+use super::VM;
+
+use vxl_iset::instruction::Instruction;
+use vxl_iset::instruction_arguments::{Address, Register};
+
+macro_rules! compare_jump {
+    ($compare:ident, $jump:ident) => {
+        |vm: &mut VM, o: &Operands| {
+            vm.execute_fused(
+                |vm| vm.$compare(o.registers[0], o.registers[1]),
+                |vm| vm.$jump(o.values[0] as usize),
+            )
+        }
+    };
+}
+
+macro_rules! load_operation {
+    ($operation:ident) => {
+        |vm: &mut VM, o: &Operands| {
+            vm.execute_fused(
+                |vm| vm.op_ldi(o.values[0], o.registers[0]),
+                |vm| vm.$operation(o.registers[1], o.registers[2], o.registers[3]),
+            )
+        }
+    };
+}
+
+/// Replaces common instruction pairs with a single superinstruction.
+///
+/// A fused instruction is placed at the index of the first instruction of the pair, the second
+/// instruction is left untouched so that any jump to it still executes only that instruction.
+pub(super) fn fuse(instructions: &[Instruction], decoded: &mut [DecodedInstruction]) {
+    for (index, pair) in instructions.windows(2).enumerate() {
+        if let Some(fused) = fuse_pair(pair[0], pair[1]) {
+            decoded[index] = fused;
+        }
+    }
+}
+
+fn fuse_pair(first: Instruction, second: Instruction) -> Option<DecodedInstruction> {
+    return match (first, second) {
+        (Instruction::Cmp(r, r1), Instruction::Jeq(a)) => {
+            fused_compare_jump(compare_jump!(op_cmp, op_jeq), r, r1, a)
+        }
+        (Instruction::Cmp(r, r1), Instruction::Jne(a)) => {
+            fused_compare_jump(compare_jump!(op_cmp, op_jne), r, r1, a)
+        }
+        (Instruction::Cmp(r, r1), Instruction::Jge(a)) => {
+            fused_compare_jump(compare_jump!(op_cmp, op_jge), r, r1, a)
+        }
+        (Instruction::Cmp(r, r1), Instruction::Jgt(a)) => {
+            fused_compare_jump(compare_jump!(op_cmp, op_jgt), r, r1, a)
+        }
+        (Instruction::Cmp(r, r1), Instruction::Jle(a)) => {
+            fused_compare_jump(compare_jump!(op_cmp, op_jle), r, r1, a)
+        }
+        (Instruction::Cmp(r, r1), Instruction::Jlt(a)) => {
+            fused_compare_jump(compare_jump!(op_cmp, op_jlt), r, r1, a)
+        }
+        (Instruction::Cmpi(r, r1), Instruction::Jeq(a)) => {
+            fused_compare_jump(compare_jump!(op_cmpi, op_jeq), r, r1, a)
+        }
+        (Instruction::Cmpi(r, r1), Instruction::Jne(a)) => {
+            fused_compare_jump(compare_jump!(op_cmpi, op_jne), r, r1, a)
+        }
+        (Instruction::Cmpi(r, r1), Instruction::Jge(a)) => {
+            fused_compare_jump(compare_jump!(op_cmpi, op_jge), r, r1, a)
+        }
+        (Instruction::Cmpi(r, r1), Instruction::Jgt(a)) => {
+            fused_compare_jump(compare_jump!(op_cmpi, op_jgt), r, r1, a)
+        }
+        (Instruction::Cmpi(r, r1), Instruction::Jle(a)) => {
+            fused_compare_jump(compare_jump!(op_cmpi, op_jle), r, r1, a)
+        }
+        (Instruction::Cmpi(r, r1), Instruction::Jlt(a)) => {
+            fused_compare_jump(compare_jump!(op_cmpi, op_jlt), r, r1, a)
+        }
+        (Instruction::Cmpf(r, r1), Instruction::Jeq(a)) => {
+            fused_compare_jump(compare_jump!(op_cmpf, op_jeq), r, r1, a)
+        }
+        (Instruction::Cmpf(r, r1), Instruction::Jne(a)) => {
+            fused_compare_jump(compare_jump!(op_cmpf, op_jne), r, r1, a)
+        }
+        (Instruction::Cmpf(r, r1), Instruction::Jge(a)) => {
+            fused_compare_jump(compare_jump!(op_cmpf, op_jge), r, r1, a)
+        }
+        (Instruction::Cmpf(r, r1), Instruction::Jgt(a)) => {
+            fused_compare_jump(compare_jump!(op_cmpf, op_jgt), r, r1, a)
+        }
+        (Instruction::Cmpf(r, r1), Instruction::Jle(a)) => {
+            fused_compare_jump(compare_jump!(op_cmpf, op_jle), r, r1, a)
+        }
+        (Instruction::Cmpf(r, r1), Instruction::Jlt(a)) => {
+            fused_compare_jump(compare_jump!(op_cmpf, op_jlt), r, r1, a)
+        }
+        (Instruction::Ldi(i, r), Instruction::Addi(r1, r2, r3)) => {
+            fused_load_operation(load_operation!(op_addi), i.into(), [r, r1, r2, r3])
+        }
+        (Instruction::Ldi(i, r), Instruction::Subi(r1, r2, r3)) => {
+            fused_load_operation(load_operation!(op_subi), i.into(), [r, r1, r2, r3])
+        }
+        (Instruction::Ldi(i, r), Instruction::Muli(r1, r2, r3)) => {
+            fused_load_operation(load_operation!(op_muli), i.into(), [r, r1, r2, r3])
+        }
+        (Instruction::Ldi(i, r), Instruction::Divi(r1, r2, r3)) => {
+            fused_load_operation(load_operation!(op_divi), i.into(), [r, r1, r2, r3])
+        }
+        (Instruction::Ldi(i, r), Instruction::Modi(r1, r2, r3)) => {
+            fused_load_operation(load_operation!(op_modi), i.into(), [r, r1, r2, r3])
+        }
+        (Instruction::Ldi(i, r), Instruction::Addu(r1, r2, r3)) => {
+            fused_load_operation(load_operation!(op_addu), i.into(), [r, r1, r2, r3])
+        }
+        (Instruction::Ldi(i, r), Instruction::Subu(r1, r2, r3)) => {
+            fused_load_operation(load_operation!(op_subu), i.into(), [r, r1, r2, r3])
+        }
+        (Instruction::Ldi(i, r), Instruction::Mulu(r1, r2, r3)) => {
+            fused_load_operation(load_operation!(op_mulu), i.into(), [r, r1, r2, r3])
+        }
+        (Instruction::Ldi(i, r), Instruction::Divu(r1, r2, r3)) => {
+            fused_load_operation(load_operation!(op_divu), i.into(), [r, r1, r2, r3])
+        }
+        (Instruction::Ldi(i, r), Instruction::Modu(r1, r2, r3)) => {
+            fused_load_operation(load_operation!(op_modu), i.into(), [r, r1, r2, r3])
+        }
+        (Instruction::Ldi(i, r), Instruction::And(r1, r2, r3)) => {
+            fused_load_operation(load_operation!(op_and), i.into(), [r, r1, r2, r3])
+        }
+        (Instruction::Ldi(i, r), Instruction::Or(r1, r2, r3)) => {
+            fused_load_operation(load_operation!(op_or), i.into(), [r, r1, r2, r3])
+        }
+        (Instruction::Ldi(i, r), Instruction::Xor(r1, r2, r3)) => {
+            fused_load_operation(load_operation!(op_xor), i.into(), [r, r1, r2, r3])
+        }
+        _ => None,
+    };
+}
+
+fn fused_compare_jump(
+    handler: Handler,
+    r: Register,
+    r1: Register,
+    a: Address,
+) -> Option<DecodedInstruction> {
+    return Some(DecodedInstruction::Native(
+        handler,
+        Operands::new([r, r1], [a.into()]),
+    ));
+}
+
+fn fused_load_operation(
+    handler: Handler,
+    value: u64,
+    registers: [Register; 4],
+) -> Option<DecodedInstruction> {
+    return Some(DecodedInstruction::Native(
+        handler,
+        Operands::new(registers, [value]),
+    ));
+}
@@ -1,13 +1,26 @@
+use super::decoded::DecodedInstruction;
+use super::fusion;
 use super::{Memory, Registers, Stack};
-use crate::error::VMError;
+use crate::effects::branch_target;
+use crate::error::{LinkError, ProgramError, VMError};
+use crate::linker::{self, Module};
+use crate::symbols::SymbolTable;
+use crate::validator;
 
 use vxl_iset::execute_instruction::ExecuteInstruction;
 use vxl_iset::instruction::Instruction;
 use vxl_iset::instruction_arguments::{Address, Immediate, Register};
 use vxl_iset::syscall_handler::SyscallHandler;
 
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
 use alloc::vec::Vec;
+use core::cmp::Ordering;
 use core::convert::TryInto;
+use core::ops::Range;
 use paste::paste;
 
 macro_rules! compute_operation {
@@ -66,9 +79,24 @@ pub struct VM {
     stack: Stack,
     register_bank: Registers,
     instructions: Vec<Instruction>,
+    decoded: Vec<DecodedInstruction>,
+    /// Whether `fuse_superinstructions` has been called, so that changed code is fused too.
+    fused: bool,
+    /// Called with the indices of any instructions changed after construction.
+    code_change_hook: Option<Box<dyn FnMut(Range<usize>)>>,
     ip: usize,
+    /// Set whenever the IP is moved other than by advancing it, so that a system call that moves
+    /// it doesn't also advance it.
+    ip_moved: bool,
     halted: bool,
     behaviour: OverflowBehaviour,
+    /// The handles of the data sections of each module, indexed by module handle. The program
+    /// itself is the first entry.
+    data_sections: Vec<Vec<u64>>,
+    symbols: Option<SymbolTable>,
+    /// The exports of each module loaded by `load_module`, indexed by handle. The program itself
+    /// is the first entry and exports nothing.
+    module_exports: Vec<BTreeMap<String, u64>>,
 }
 
 impl Default for OverflowBehaviour {
@@ -92,10 +120,17 @@ impl VM {
             memory: Memory::default(),
             stack: Stack::default(),
             register_bank: Registers::default(),
+            decoded: DecodedInstruction::decode_all(&instructions),
+            fused: false,
+            code_change_hook: None,
             instructions,
             ip,
+            ip_moved: false,
             halted: false,
             behaviour: OverflowBehaviour::default(),
+            data_sections: vec![Vec::new()],
+            symbols: None,
+            module_exports: vec![BTreeMap::new()],
         };
     }
 
@@ -104,15 +139,32 @@ impl VM {
         instructions: Vec<Instruction>,
         ip: usize,
         behaviour: OverflowBehaviour,
+    ) -> Self {
+        return Self::new_with_memory(Memory::new(), stack_size, instructions, ip, behaviour);
+    }
+
+    pub fn new_with_memory(
+        memory: Memory,
+        stack_size: usize,
+        instructions: Vec<Instruction>,
+        ip: usize,
+        behaviour: OverflowBehaviour,
     ) -> Self {
         return Self {
-            memory: Memory::new(),
+            memory,
             stack: Stack::new(stack_size),
             register_bank: Registers::default(),
+            decoded: DecodedInstruction::decode_all(&instructions),
+            fused: false,
+            code_change_hook: None,
             instructions,
             ip,
+            ip_moved: false,
             halted: false,
             behaviour,
+            data_sections: vec![Vec::new()],
+            symbols: None,
+            module_exports: vec![BTreeMap::new()],
         };
     }
 
@@ -129,21 +181,125 @@ impl VM {
             return Err(VMError::SystemHalted);
         }
 
-        if self.ip > self.instructions.len() {
-            return Err(VMError::NoInstruction);
-        }
+        let modified_ip = match self.decoded.get(self.ip) {
+            Some(&DecodedInstruction::Native(execute, operands)) => execute(self, &operands)?,
+            Some(&DecodedInstruction::Syscall(call)) => self.op_syscall(handler, call)?,
+            None => return Err(VMError::NoInstruction),
+        };
 
-        if !self.execute_instruction(self.instructions[self.ip], handler)? {
+        if !modified_ip {
             self.ip += 1;
         }
 
         return Ok(());
     }
 
+    /// Fuses common instruction pairs, such as a compare followed by a conditional jump, into
+    /// superinstructions.
+    ///
+    /// Jump targets and errors are unaffected, however a fused pair is executed by a single call
+    /// to `run_next`.
+    pub fn fuse_superinstructions(&mut self) {
+        fusion::fuse(&self.instructions, &mut self.decoded);
+        self.fused = true;
+    }
+
+    /// Undoes `fuse_superinstructions`, so that every instruction is executed by its own call to
+    /// `run_next`.
+    pub fn unfuse(&mut self) {
+        self.decoded = DecodedInstruction::decode_all(&self.instructions);
+        self.fused = false;
+    }
+
+    /// Adds instructions after the last one, keeping the registers, stack and memory. A machine
+    /// that has run past its last instruction continues with the first new one, a halted machine
+    /// stays halted.
+    ///
+    /// Every jump and call must target an instruction of the extended program.
+    pub fn append_instructions(
+        &mut self,
+        instructions: Vec<Instruction>,
+    ) -> Result<(), ProgramError> {
+        let start = self.instructions.len();
+
+        check_targets(&instructions, start, start + instructions.len())?;
+
+        self.instructions.extend(instructions);
+        self.refresh_code(start..self.instructions.len());
+
+        return Ok(());
+    }
+
+    /// Replaces the instructions from `start` onwards, which may include the current
+    /// instruction. The program can't be made longer this way, see `append_instructions`.
+    ///
+    /// Every jump and call must target an instruction of the program.
+    pub fn patch_instructions(
+        &mut self,
+        start: usize,
+        instructions: Vec<Instruction>,
+    ) -> Result<(), ProgramError> {
+        let end = start
+            .checked_add(instructions.len())
+            .filter(|end| *end <= self.instructions.len())
+            .ok_or(ProgramError::PatchOutOfBounds(start))?;
+
+        check_targets(&instructions, start, self.instructions.len())?;
+
+        self.instructions[start..end].copy_from_slice(&instructions);
+        self.refresh_code(start..end);
+
+        return Ok(());
+    }
+
+    /// Sets a function to call with the indices of the instructions whenever instructions are
+    /// appended, patched or loaded, so that anything derived from them can be updated.
+    pub fn set_code_change_hook<F: FnMut(Range<usize>) + 'static>(&mut self, hook: F) {
+        self.code_change_hook = Some(Box::new(hook));
+    }
+
+    /// Decodes changed instructions again, as well as a fused instruction before them that may
+    /// include the first, then calls the hook.
+    fn refresh_code(&mut self, changed: Range<usize>) {
+        let from = changed.start.saturating_sub(1);
+        let decoded = DecodedInstruction::decode_all(&self.instructions[from..changed.end]);
+
+        self.decoded
+            .splice(from..changed.end.min(self.decoded.len()), decoded);
+
+        if self.fused {
+            let to = (changed.end + 1).min(self.instructions.len());
+
+            fusion::fuse(&self.instructions[from..to], &mut self.decoded[from..to]);
+        }
+
+        if let Some(hook) = &mut self.code_change_hook {
+            hook(changed);
+        }
+    }
+
+    /// Sets the comparison flags in `RFL` as a `cmp` instruction with the given result would.
+    pub fn set_comparison_flags(&mut self, ordering: Ordering) {
+        let value = match ordering {
+            Ordering::Less => 0b010,
+            Ordering::Equal => 0b001,
+            Ordering::Greater => 0b100,
+        };
+
+        self.register_bank.set_value(
+            Register::RFL as u8,
+            self.register_bank.get_value(Register::RFL as u8) & Self::FLAGS_MASK | value,
+        );
+    }
+
     pub fn halt(&mut self) {
         self.halted = true;
     }
 
+    pub fn is_halted(&self) -> bool {
+        return self.halted;
+    }
+
     pub fn registers(&self) -> &Registers {
         return &self.register_bank;
     }
@@ -152,6 +308,20 @@ impl VM {
         return &mut self.register_bank;
     }
 
+    pub fn ip(&self) -> usize {
+        return self.ip;
+    }
+
+    /// Moves the IP, which may be past the last instruction to stop `run`.
+    pub fn set_ip(&mut self, ip: usize) {
+        self.ip = ip;
+        self.ip_moved = true;
+    }
+
+    pub fn instructions(&self) -> &[Instruction] {
+        return &self.instructions;
+    }
+
     pub fn stack(&self) -> &Stack {
         return &self.stack;
     }
@@ -164,6 +334,181 @@ impl VM {
         return &mut self.memory;
     }
 
+    /// Places each data section in a read only block, in order. The handles can be found by the
+    /// guest with `StandardLibrary::DATA_SECTION`.
+    pub fn load_data_sections(&mut self, sections: Vec<Vec<u8>>) -> VMResult<()> {
+        for section in sections {
+            let address = self
+                .memory
+                .allocate_read_only(section)
+                .ok_or(VMError::FailedMalloc)?;
+
+            self.data_sections[0].push(address);
+        }
+
+        return Ok(());
+    }
+
+    /// Returns the handle of a data section loaded by `load_data_sections`.
+    pub fn data_section(&self, index: usize) -> Option<u64> {
+        return self.module_data_section(0, index);
+    }
+
+    /// Returns the handle of a data section of a module loaded by `load_module`, the program
+    /// itself is module 0.
+    pub fn module_data_section(&self, handle: u64, index: usize) -> Option<u64> {
+        return self.data_sections.get(handle as usize)?.get(index).copied();
+    }
+
+    pub fn set_symbols(&mut self, symbols: SymbolTable) {
+        self.symbols = Some(symbols);
+    }
+
+    pub fn symbols(&self) -> Option<&SymbolTable> {
+        return self.symbols.as_ref();
+    }
+
+    /// Describes an instruction using the symbol table if there is one, see
+    /// `SymbolTable::describe`.
+    pub fn describe_instruction(&self, index: usize) -> String {
+        return match &self.symbols {
+            Some(symbols) => symbols.describe(index as u64),
+            None => format!("instruction {}", index),
+        };
+    }
+
+    /// Returns the current instruction followed by the `call` instruction of each active frame,
+    /// innermost first.
+    pub fn call_stack(&self) -> Vec<usize> {
+        let mut frames = vec![self.ip];
+        let mut rsp = self.register_bank.get_value(Register::RSP as u8);
+
+        // Each call stores the return address and the caller's RSP just below the callee's RSP.
+        while rsp != 0 {
+            let (return_ip, caller_rsp) = match (
+                self.stack.get_top_u64(rsp),
+                rsp.checked_sub(8)
+                    .and_then(|top| self.stack.get_top_u64(top)),
+            ) {
+                (Some(return_ip), Some(caller_rsp)) if return_ip > 0 => (return_ip, caller_rsp),
+                _ => break,
+            };
+
+            frames.push(return_ip as usize - 1);
+
+            // Frames always grow upwards, anything else is a corrupted stack.
+            if caller_rsp >= rsp {
+                break;
+            }
+
+            rsp = caller_rsp;
+        }
+
+        return frames;
+    }
+
+    /// Adds the instructions of a module after those already loaded, returning a handle for
+    /// `resolve_export`.
+    ///
+    /// Imports are resolved using the exports of previously loaded modules. The placed module
+    /// must pass `validator::verify_module` from its entry offset and every export. The data
+    /// sections of the module are placed in read only blocks, see `module_data_section`.
+    pub fn load_module(&mut self, module: &Module) -> Result<u64, LinkError> {
+        let handle = self.module_exports.len();
+
+        let mut available = BTreeMap::new();
+
+        for exports in &self.module_exports {
+            available.extend(exports.iter().map(|(name, index)| (name.clone(), *index)));
+        }
+
+        linker::check_exports(module, handle)?;
+
+        let base = self.instructions.len() as u64;
+        let instructions = linker::place_module(module, handle, base, &available)?;
+        let export_indices: Vec<u64> = module
+            .link_table()
+            .exports()
+            .iter()
+            .map(|export| export.index())
+            .collect();
+
+        if let Some(problem) =
+            validator::verify_module(&instructions, base, module.entry(), &export_indices).first()
+        {
+            return Err(LinkError::InvalidProgram(handle, *problem));
+        }
+
+        let mut exports = BTreeMap::new();
+
+        for export in module.link_table().exports() {
+            if available.contains_key(export.name())
+                || exports
+                    .insert(String::from(export.name()), export.index() + base)
+                    .is_some()
+            {
+                return Err(LinkError::DuplicateSymbol(String::from(export.name())));
+            }
+        }
+
+        let mut data_sections = Vec::new();
+
+        for section in module.data_sections() {
+            match self.memory.allocate_read_only(section.clone()) {
+                Some(address) => data_sections.push(address),
+                None => {
+                    for address in &data_sections {
+                        self.memory.free_read_only(address);
+                    }
+
+                    return Err(LinkError::FailedDataSectionAllocation(handle));
+                }
+            }
+        }
+
+        let symbols = self.symbols.get_or_insert_with(SymbolTable::new);
+
+        if let Some(module_symbols) = module.symbols() {
+            symbols.append(module_symbols, base);
+        }
+
+        for (name, index) in &exports {
+            if symbols.function_starting_at(*index).is_none() {
+                symbols.add_function(*index, name.clone());
+            }
+        }
+
+        self.instructions.extend(instructions);
+        self.refresh_code(base as usize..self.instructions.len());
+        self.module_exports.push(exports);
+        self.data_sections.push(data_sections);
+
+        return Ok(handle as u64);
+    }
+
+    /// Returns the index of the first instruction of a function exported by a loaded module.
+    pub fn resolve_export(&self, handle: u64, name: &str) -> Option<u64> {
+        return self.module_exports.get(handle as usize)?.get(name).copied();
+    }
+
+    /// Calls the function beginning at `target` as a `call` instruction at the current
+    /// instruction would, so that it returns to the next instruction.
+    pub fn call(&mut self, target: usize) -> VMResult<()> {
+        self.op_call(target)?;
+
+        return Ok(());
+    }
+
+    fn check_swappable(&self, a: u64, a1: u64) -> VMResult<()> {
+        for address in [a, a1] {
+            if self.memory.is_read_only(&address) {
+                return Err(VMError::WriteToReadOnlyMemoryError(address));
+            }
+        }
+
+        return Ok(());
+    }
+
     fn push_stack(&mut self, value: u64) -> VMResult<()> {
         if !self
             .stack
@@ -193,6 +538,24 @@ impl VM {
         }
     }
 
+    /// Executes both halves of a fused instruction, leaving the IP where executing them
+    /// separately would have.
+    pub(super) fn execute_fused<F, S>(&mut self, first: F, second: S) -> VMResult<bool>
+    where
+        F: FnOnce(&mut Self) -> VMResult<bool>,
+        S: FnOnce(&mut Self) -> VMResult<bool>,
+    {
+        // Only instructions that never modify the IP are fused as the first half.
+        first(self)?;
+        self.ip += 1;
+
+        if !second(self)? {
+            self.ip += 1;
+        }
+
+        return Ok(true);
+    }
+
     compute_operation!(i64, add);
     compute_operation!(i64, sub);
     compute_operation!(i64, mul);
@@ -259,101 +622,106 @@ impl VM {
     compute_float_operation!(mul, *);
 }
 
-impl ExecuteInstruction for VM {
-    type Machine = Self;
-    // A value of true indicates that the IP was modified and shouldn't be updated this cycle.
-    type Output = VMResult<bool>;
-
-    fn execute_nop(&mut self) -> Self::Output {
+// The implementation of each instruction, operating on resolved register indices and raw values.
+// These are shared by the pre-decoded dispatch in `run_next` and the `ExecuteInstruction` impl.
+impl VM {
+    pub(super) fn op_nop(&mut self) -> VMResult<bool> {
         return Ok(false);
     }
 
-    fn execute_syscall<H: SyscallHandler<Self::Machine>>(
+    pub(super) fn op_syscall<H: SyscallHandler<Self>>(
         &mut self,
         handler: &mut H,
-        i: Immediate,
-    ) -> Self::Output {
+        i: u64,
+    ) -> VMResult<bool> {
+        self.ip_moved = false;
+
         let output = handler
-            .execute_call(i.into(), self)
-            .ok_or(VMError::UnknownSystemCall(i.into()))?;
+            .execute_call(i, self)
+            .ok_or(VMError::UnknownSystemCall(i))?;
 
         self.register_bank.set_value(Register::ROU as u8, output);
 
-        return Ok(false);
+        // A call made by the handler has already moved the IP.
+        return Ok(self.ip_moved);
     }
 
-    fn execute_ldb(&mut self, i: Immediate, r: Register) -> Self::Output {
-        let mut value: u64 = i.into();
+    pub(super) fn op_ldb(&mut self, i: u64, r: u8) -> VMResult<bool> {
+        let mut value: u64 = i;
         value &= 0xFF;
 
-        self.register_bank.set_value(r as u8, value);
+        self.register_bank.set_value(r, value);
 
         return Ok(false);
     }
 
-    fn execute_ldi(&mut self, i: Immediate, r: Register) -> Self::Output {
-        self.register_bank.set_value(r as u8, i.into());
+    pub(super) fn op_ldi(&mut self, i: u64, r: u8) -> VMResult<bool> {
+        self.register_bank.set_value(r, i);
 
         return Ok(false);
     }
 
-    fn execute_ldf(&mut self, i: Immediate, r: Register) -> Self::Output {
-        self.register_bank.set_value(r as u8, i.into());
+    pub(super) fn op_ldf(&mut self, i: u64, r: u8) -> VMResult<bool> {
+        self.register_bank.set_value(r, i);
 
         return Ok(false);
     }
 
-    fn execute_mov(&mut self, r: Register, r1: Register) -> Self::Output {
+    pub(super) fn op_mov(&mut self, r: u8, r1: u8) -> VMResult<bool> {
         self.register_bank
-            .set_value(r as u8, self.register_bank.get_value(r1 as u8));
+            .set_value(r, self.register_bank.get_value(r1));
 
         return Ok(false);
     }
 
-    fn execute_push(&mut self, r: Register) -> Self::Output {
-        let v = self.register_bank.get_value(r as u8);
+    pub(super) fn op_push(&mut self, r: u8) -> VMResult<bool> {
+        let v = self.register_bank.get_value(r);
 
         self.push_stack(v)?;
 
         return Ok(false);
     }
 
-    fn execute_pop(&mut self, r: Register) -> Self::Output {
+    pub(super) fn op_pop(&mut self, r: u8) -> VMResult<bool> {
         let v = self.pop_stack()?;
-        self.register_bank.set_value(r as u8, v);
+        self.register_bank.set_value(r, v);
 
         return Ok(false);
     }
 
-    fn execute_sget(&mut self, r: Register, r1: Register) -> Self::Output {
+    pub(super) fn op_sget(&mut self, r: u8, r1: u8) -> VMResult<bool> {
         self.register_bank.set_value(
-            r as u8,
+            r,
             self.stack
-                .get_top_u64(self.register_bank.get_value(r1 as u8))
+                .get_top_u64(self.register_bank.get_value(r1))
                 .ok_or(VMError::AccessBeyondStackBounds)?,
         );
 
         return Ok(false);
     }
 
-    fn execute_malloc(&mut self, r: Register, r1: Register) -> Self::Output {
-        let address = self.memory.allocate(self.register_bank.get_value(r1 as u8));
+    pub(super) fn op_malloc(&mut self, r: u8, r1: u8) -> VMResult<bool> {
+        let address = self.memory.allocate(self.register_bank.get_value(r1));
         self.register_bank
-            .set_value(r as u8, address.ok_or(VMError::FailedMalloc)?);
+            .set_value(r, address.ok_or(VMError::FailedMalloc)?);
 
         return Ok(false);
     }
 
-    fn execute_malloci(&mut self, i: Immediate, r: Register) -> Self::Output {
-        let address = self.memory.allocate(i.into());
+    pub(super) fn op_malloci(&mut self, i: u64, r: u8) -> VMResult<bool> {
+        let address = self.memory.allocate(i);
         self.register_bank
-            .set_value(r as u8, address.ok_or(VMError::FailedMalloc)?);
+            .set_value(r, address.ok_or(VMError::FailedMalloc)?);
 
         return Ok(false);
     }
 
-    fn execute_free(&mut self, r: Register) -> Self::Output {
-        let address = self.register_bank.get_value(r as u8);
+    pub(super) fn op_free(&mut self, r: u8) -> VMResult<bool> {
+        let address = self.register_bank.get_value(r);
+
+        if self.memory.is_read_only(&address) {
+            return Err(VMError::WriteToReadOnlyMemoryError(address));
+        }
 
         if !self.memory.free(&address) {
             return Err(VMError::FailedFreeNoAddressError(address));
@@ -362,8 +730,12 @@ impl ExecuteInstruction for VM {
         return Ok(false);
     }
 
-    fn execute_freea(&mut self, a: Address) -> Self::Output {
-        let address = a.into();
+    pub(super) fn op_freea(&mut self, a: u64) -> VMResult<bool> {
+        let address = a;
+
+        if self.memory.is_read_only(&address) {
+            return Err(VMError::WriteToReadOnlyMemoryError(address));
+        }
 
         if !self.memory.free(&address) {
             return Err(VMError::FailedFreeNoAddressError(address));
@@ -372,18 +744,15 @@ impl ExecuteInstruction for VM {
         return Ok(false);
     }
 
-    fn execute_setb(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+    pub(super) fn op_setb(&mut self, r: u8, r1: u8, r2: u8) -> VMResult<bool> {
         // r = address
         // r1 = index
         // r2 = value
-        let address = self.register_bank.get_value(r as u8);
-        let index = self.register_bank.get_value(r1 as u8);
-        let value = self.register_bank.get_value(r2 as u8);
+        let address = self.register_bank.get_value(r);
+        let index = self.register_bank.get_value(r1);
+        let value = self.register_bank.get_value(r2);
 
-        let block = self
-            .memory
-            .retrieve_mutable(&address)
-            .ok_or(VMError::FailedSetNoAddressError(address))?;
+        let block = self.memory.block_mut(&address)?;
 
         if index >= block.len() as u64 {
             return Err(VMError::IndexBeyondBoundsError(index, block.len() as u64));
@@ -395,18 +764,15 @@ impl ExecuteInstruction for VM {
         return Ok(false);
     }
 
-    fn execute_seti(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+    pub(super) fn op_seti(&mut self, r: u8, r1: u8, r2: u8) -> VMResult<bool> {
         // r = address
         // r1 = index
         // r2 = value
-        let address = self.register_bank.get_value(r as u8);
-        let index = self.register_bank.get_value(r1 as u8);
-        let value = self.register_bank.get_value(r2 as u8);
+        let address = self.register_bank.get_value(r);
+        let index = self.register_bank.get_value(r1);
+        let value = self.register_bank.get_value(r2);
 
-        let block = self
-            .memory
-            .retrieve_mutable(&address)
-            .ok_or(VMError::FailedSetNoAddressError(address))?;
+        let block = self.memory.block_mut(&address)?;
 
         if index + 8 >= block.len() as u64 {
             return Err(VMError::IndexBeyondBoundsError(index, block.len() as u64));
@@ -419,19 +785,16 @@ impl ExecuteInstruction for VM {
         return Ok(false);
     }
 
-    fn execute_isetb(&mut self, i: Immediate, r: Register, r1: Register) -> Self::Output {
+    pub(super) fn op_isetb(&mut self, i: u64, r: u8, r1: u8) -> VMResult<bool> {
         // i = index
         // r = address
         // r1 = value
 
-        let index = i.into();
-        let address = self.register_bank.get_value(r as u8);
-        let value = self.register_bank.get_value(r1 as u8);
+        let index = i;
+        let address = self.register_bank.get_value(r);
+        let value = self.register_bank.get_value(r1);
 
-        let block = self
-            .memory
-            .retrieve_mutable(&address)
-            .ok_or(VMError::FailedSetNoAddressError(address))?;
+        let block = self.memory.block_mut(&address)?;
 
         if index >= block.len() as u64 {
             return Err(VMError::IndexBeyondBoundsError(index, block.len() as u64));
@@ -443,19 +806,16 @@ impl ExecuteInstruction for VM {
         return Ok(false);
     }
 
-    fn execute_iseti(&mut self, i: Immediate, r: Register, r1: Register) -> Self::Output {
+    pub(super) fn op_iseti(&mut self, i: u64, r: u8, r1: u8) -> VMResult<bool> {
         // i = index
         // r = address
         // r1 = value
 
-        let index = i.into();
-        let address = self.register_bank.get_value(r as u8);
-        let value = self.register_bank.get_value(r1 as u8);
+        let index = i;
+        let address = self.register_bank.get_value(r);
+        let value = self.register_bank.get_value(r1);
 
-        let block = self
-            .memory
-            .retrieve_mutable(&address)
-            .ok_or(VMError::FailedSetNoAddressError(address))?;
+        let block = self.memory.block_mut(&address)?;
 
         if index + 8 >= block.len() as u64 {
             return Err(VMError::IndexBeyondBoundsError(index, block.len() as u64));
@@ -468,13 +828,13 @@ impl ExecuteInstruction for VM {
         return Ok(false);
     }
 
-    fn execute_getb(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+    pub(super) fn op_getb(&mut self, r: u8, r1: u8, r2: u8) -> VMResult<bool> {
         // r = dest
         // r1 = address
         // r2 = offset
 
-        let address = self.register_bank.get_value(r1 as u8);
-        let offset = self.register_bank.get_value(r2 as u8);
+        let address = self.register_bank.get_value(r1);
+        let offset = self.register_bank.get_value(r2);
 
         let loc = self
             .memory
@@ -485,19 +845,18 @@ impl ExecuteInstruction for VM {
             return Err(VMError::IndexBeyondBoundsError(offset, loc.len() as u64));
         }
 
-        self.register_bank
-            .set_value(r as u8, loc[offset as usize] as u64);
+        self.register_bank.set_value(r, loc[offset as usize] as u64);
 
         return Ok(false);
     }
 
-    fn execute_geti(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+    pub(super) fn op_geti(&mut self, r: u8, r1: u8, r2: u8) -> VMResult<bool> {
         // r = dest
         // r1 = address
         // r2 = offset
 
-        let address = self.register_bank.get_value(r1 as u8);
-        let offset = self.register_bank.get_value(r2 as u8);
+        let address = self.register_bank.get_value(r1);
+        let offset = self.register_bank.get_value(r2);
 
         let loc = self
             .memory
@@ -512,19 +871,18 @@ impl ExecuteInstruction for VM {
             .try_into()
             .expect("Unexpected conversion to u64 fail.");
 
-        self.register_bank
-            .set_value(r as u8, u64::from_le_bytes(arr));
+        self.register_bank.set_value(r, u64::from_le_bytes(arr));
 
         return Ok(false);
     }
 
-    fn execute_igetb(&mut self, i: Immediate, r: Register, r1: Register) -> Self::Output {
+    pub(super) fn op_igetb(&mut self, i: u64, r: u8, r1: u8) -> VMResult<bool> {
         // i = offset
         // r = destination
         // r1 = address
 
-        let address = self.register_bank.get_value(r1 as u8);
-        let offset = i.into();
+        let address = self.register_bank.get_value(r1);
+        let offset = i;
 
         let loc = self
             .memory
@@ -535,19 +893,18 @@ impl ExecuteInstruction for VM {
             return Err(VMError::IndexBeyondBoundsError(offset, loc.len() as u64));
         }
 
-        self.register_bank
-            .set_value(r as u8, loc[offset as usize] as u64);
+        self.register_bank.set_value(r, loc[offset as usize] as u64);
 
         return Ok(false);
     }
 
-    fn execute_igeti(&mut self, i: Immediate, r: Register, r1: Register) -> Self::Output {
+    pub(super) fn op_igeti(&mut self, i: u64, r: u8, r1: u8) -> VMResult<bool> {
         // i = offset
         // r = destination
         // r1 = address
 
-        let address = self.register_bank.get_value(r1 as u8);
-        let offset = i.into();
+        let address = self.register_bank.get_value(r1);
+        let offset = i;
 
         let loc = self
             .memory
@@ -562,17 +919,16 @@ impl ExecuteInstruction for VM {
             .try_into()
             .expect("Unexpected conversion to u64 fail.");
 
-        self.register_bank
-            .set_value(r as u8, u64::from_le_bytes(arr));
+        self.register_bank.set_value(r, u64::from_le_bytes(arr));
 
         return Ok(false);
     }
 
-    fn execute_last(&mut self, r: Register, r1: Register) -> Self::Output {
+    pub(super) fn op_last(&mut self, r: u8, r1: u8) -> VMResult<bool> {
         // r = dest
         // r1 = address
 
-        let address = self.register_bank.get_value(r1 as u8);
+        let address = self.register_bank.get_value(r1);
         let arr = self
             .memory
             .retrieve(&address)
@@ -580,7 +936,7 @@ impl ExecuteInstruction for VM {
 
         if arr.len() < 8 {
             self.register_bank.set_value(
-                r as u8,
+                r,
                 *arr.last()
                     .ok_or(VMError::IndexBeyondBoundsError(1, arr.len() as u64))?
                     as u64,
@@ -593,32 +949,32 @@ impl ExecuteInstruction for VM {
             }
 
             self.register_bank
-                .set_value(r as u8, u64::from_le_bytes(last_word));
+                .set_value(r, u64::from_le_bytes(last_word));
         }
 
         return Ok(false);
     }
 
-    fn execute_length(&mut self, r: Register, r1: Register) -> Self::Output {
+    pub(super) fn op_length(&mut self, r: u8, r1: u8) -> VMResult<bool> {
         // r = dest
         // r1 = address
-        let address = self.register_bank.get_value(r1 as u8);
+        let address = self.register_bank.get_value(r1);
 
         let loc = self
             .memory
             .retrieve(&address)
             .ok_or(VMError::FailedGetNoAddressError(address))?;
 
-        self.register_bank.set_value(r as u8, loc.len() as u64);
+        self.register_bank.set_value(r, loc.len() as u64);
 
         return Ok(false);
     }
 
-    fn execute_clone(&mut self, r: Register, r1: Register) -> Self::Output {
+    pub(super) fn op_clone(&mut self, r: u8, r1: u8) -> VMResult<bool> {
         // r = dest
         // r1 = src address
 
-        let src_address = self.register_bank.get_value(r1 as u8);
+        let src_address = self.register_bank.get_value(r1);
 
         let bytes = self
             .memory
@@ -627,7 +983,7 @@ impl ExecuteInstruction for VM {
             .clone();
 
         self.register_bank.set_value(
-            r as u8,
+            r,
             self.memory
                 .allocate_with(bytes)
                 .ok_or(VMError::FailedMalloc)?,
@@ -636,483 +992,448 @@ impl ExecuteInstruction for VM {
         return Ok(false);
     }
 
-    fn execute_copy(
-        &mut self,
-        r: Register,
-        r1: Register,
-        r2: Register,
-        r3: Register,
-        r4: Register,
-    ) -> Self::Output {
+    pub(super) fn op_copy(&mut self, r: u8, r1: u8, r2: u8, r3: u8, r4: u8) -> VMResult<bool> {
         // r = destination address
         // r1 = destination offset
         // r2 = src address
         // r3 = src offset
         // r4 = number of bytes
 
-        let dest_address = self.register_bank.get_value(r as u8);
-        let dest_offset = self.register_bank.get_value(r1 as u8);
-        let src_address = self.register_bank.get_value(r2 as u8);
-        let src_offset = self.register_bank.get_value(r3 as u8);
-        let bytes = self.register_bank.get_value(r4 as u8);
-
-        for i in 0..bytes {
-            let s_off = src_offset + i;
-            let d_off = dest_offset + i;
-
-            self.memory.set(
-                &dest_address,
-                &d_off,
-                self.memory.get(&src_address, &s_off)?,
-            )?;
-        }
+        let dest_address = self.register_bank.get_value(r);
+        let dest_offset = self.register_bank.get_value(r1);
+        let src_address = self.register_bank.get_value(r2);
+        let src_offset = self.register_bank.get_value(r3);
+        let bytes = self.register_bank.get_value(r4);
+
+        self.memory.copy(
+            &dest_address,
+            &dest_offset,
+            &src_address,
+            &src_offset,
+            &bytes,
+        )?;
 
         return Ok(false);
     }
 
-    fn execute_copyi(
-        &mut self,
-        i: Immediate,
-        i1: Immediate,
-        i2: Immediate,
-        r: Register,
-        r1: Register,
-    ) -> Self::Output {
+    pub(super) fn op_copyi(&mut self, i: u64, i1: u64, i2: u64, r: u8, r1: u8) -> VMResult<bool> {
         // i = destination offset
         // i1 = src offset
         // i2 = number of bytes
         // r = destination address
         // r1 = src address
 
-        let dest_address = self.register_bank.get_value(r as u8);
-        let dest_offset: u64 = i.into();
-        let src_address = self.register_bank.get_value(r1 as u8);
-        let src_offset: u64 = i1.into();
-        let bytes: u64 = i2.into();
-
-        for i in 0..bytes {
-            self.memory.set(
-                &dest_address,
-                &(dest_offset + i),
-                self.memory.get(&src_address, &(src_offset + i))?,
-            )?;
-        }
+        let dest_address = self.register_bank.get_value(r);
+        let dest_offset: u64 = i;
+        let src_address = self.register_bank.get_value(r1);
+        let src_offset: u64 = i1;
+        let bytes: u64 = i2;
+
+        self.memory.copy(
+            &dest_address,
+            &dest_offset,
+            &src_address,
+            &src_offset,
+            &bytes,
+        )?;
 
         return Ok(false);
     }
 
-    fn execute_addi(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+    pub(super) fn op_addi(&mut self, r: u8, r1: u8, r2: u8) -> VMResult<bool> {
         // r = dest
         // r1 = lhs
         // r2 = rhs
 
         let value = self.compute_i64_add(
-            self.register_bank.get_value(r1 as u8) as i64,
-            self.register_bank.get_value(r2 as u8) as i64,
+            self.register_bank.get_value(r1) as i64,
+            self.register_bank.get_value(r2) as i64,
         )?;
 
-        self.register_bank.set_value(r as u8, value as u64);
+        self.register_bank.set_value(r, value as u64);
 
         return Ok(false);
     }
 
-    fn execute_subi(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+    pub(super) fn op_subi(&mut self, r: u8, r1: u8, r2: u8) -> VMResult<bool> {
         // r = dest
         // r1 = lhs
         // r2 = rhs
 
         let value = self.compute_i64_sub(
-            self.register_bank.get_value(r1 as u8) as i64,
-            self.register_bank.get_value(r2 as u8) as i64,
+            self.register_bank.get_value(r1) as i64,
+            self.register_bank.get_value(r2) as i64,
         )?;
 
-        self.register_bank.set_value(r as u8, value as u64);
+        self.register_bank.set_value(r, value as u64);
 
         return Ok(false);
     }
 
-    fn execute_muli(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+    pub(super) fn op_muli(&mut self, r: u8, r1: u8, r2: u8) -> VMResult<bool> {
         // r = dest
         // r1 = lhs
         // r2 = rhs
 
         let value = self.compute_i64_mul(
-            self.register_bank.get_value(r1 as u8) as i64,
-            self.register_bank.get_value(r2 as u8) as i64,
+            self.register_bank.get_value(r1) as i64,
+            self.register_bank.get_value(r2) as i64,
         )?;
 
-        self.register_bank.set_value(r as u8, value as u64);
+        self.register_bank.set_value(r, value as u64);
 
         return Ok(false);
     }
 
-    fn execute_divi(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+    pub(super) fn op_divi(&mut self, r: u8, r1: u8, r2: u8) -> VMResult<bool> {
         // r = dest
         // r1 = lhs
         // r2 = rhs
 
         let value = self.compute_i64_div(
-            self.register_bank.get_value(r1 as u8) as i64,
-            self.register_bank.get_value(r2 as u8) as i64,
+            self.register_bank.get_value(r1) as i64,
+            self.register_bank.get_value(r2) as i64,
         )?;
 
-        self.register_bank.set_value(r as u8, value as u64);
+        self.register_bank.set_value(r, value as u64);
 
         return Ok(false);
     }
 
-    fn execute_modi(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+    pub(super) fn op_modi(&mut self, r: u8, r1: u8, r2: u8) -> VMResult<bool> {
         // r = dest
         // r1 = lhs
         // r2 = rhs
 
         let value = self.compute_i64_mod(
-            self.register_bank.get_value(r1 as u8) as i64,
-            self.register_bank.get_value(r2 as u8) as i64,
+            self.register_bank.get_value(r1) as i64,
+            self.register_bank.get_value(r2) as i64,
         )?;
 
-        self.register_bank.set_value(r as u8, value as u64);
+        self.register_bank.set_value(r, value as u64);
 
         return Ok(false);
     }
 
-    fn execute_addu(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+    pub(super) fn op_addu(&mut self, r: u8, r1: u8, r2: u8) -> VMResult<bool> {
         // r = dest
         // r1 = lhs
         // r2 = rhs
 
         let value = self.compute_u64_add(
-            self.register_bank.get_value(r1 as u8),
-            self.register_bank.get_value(r2 as u8),
+            self.register_bank.get_value(r1),
+            self.register_bank.get_value(r2),
         )?;
 
-        self.register_bank.set_value(r as u8, value as u64);
+        self.register_bank.set_value(r, value as u64);
 
         return Ok(false);
     }
 
-    fn execute_subu(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+    pub(super) fn op_subu(&mut self, r: u8, r1: u8, r2: u8) -> VMResult<bool> {
         // r = dest
         // r1 = lhs
         // r2 = rhs
 
         let value = self.compute_u64_sub(
-            self.register_bank.get_value(r1 as u8),
-            self.register_bank.get_value(r2 as u8),
+            self.register_bank.get_value(r1),
+            self.register_bank.get_value(r2),
         )?;
 
-        self.register_bank.set_value(r as u8, value as u64);
+        self.register_bank.set_value(r, value as u64);
 
         return Ok(false);
     }
 
-    fn execute_mulu(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+    pub(super) fn op_mulu(&mut self, r: u8, r1: u8, r2: u8) -> VMResult<bool> {
         // r = dest
         // r1 = lhs
         // r2 = rhs
 
         let value = self.compute_u64_mul(
-            self.register_bank.get_value(r1 as u8),
-            self.register_bank.get_value(r2 as u8),
+            self.register_bank.get_value(r1),
+            self.register_bank.get_value(r2),
         )?;
 
-        self.register_bank.set_value(r as u8, value as u64);
+        self.register_bank.set_value(r, value as u64);
 
         return Ok(false);
     }
 
-    fn execute_divu(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+    pub(super) fn op_divu(&mut self, r: u8, r1: u8, r2: u8) -> VMResult<bool> {
         // r = dest
         // r1 = lhs
         // r2 = rhs
 
         let value = self.compute_u64_div(
-            self.register_bank.get_value(r1 as u8),
-            self.register_bank.get_value(r2 as u8),
+            self.register_bank.get_value(r1),
+            self.register_bank.get_value(r2),
         )?;
 
-        self.register_bank.set_value(r as u8, value as u64);
+        self.register_bank.set_value(r, value as u64);
 
         return Ok(false);
     }
 
-    fn execute_modu(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+    pub(super) fn op_modu(&mut self, r: u8, r1: u8, r2: u8) -> VMResult<bool> {
         // r = dest
         // r1 = lhs
         // r2 = rhs
 
         let value = self.compute_u64_mod(
-            self.register_bank.get_value(r1 as u8),
-            self.register_bank.get_value(r2 as u8),
+            self.register_bank.get_value(r1),
+            self.register_bank.get_value(r2),
         )?;
 
-        self.register_bank.set_value(r as u8, value as u64);
+        self.register_bank.set_value(r, value as u64);
 
         return Ok(false);
     }
 
-    fn execute_addf(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+    pub(super) fn op_addf(&mut self, r: u8, r1: u8, r2: u8) -> VMResult<bool> {
         // r = dest
         // r1 = lhs
         // r2 = rhs
 
         let value = self.compute_float_add(
-            self.register_bank.get_value(r1 as u8),
-            self.register_bank.get_value(r2 as u8),
+            self.register_bank.get_value(r1),
+            self.register_bank.get_value(r2),
         );
 
-        self.register_bank.set_value(r as u8, value);
+        self.register_bank.set_value(r, value);
 
         return Ok(false);
     }
 
-    fn execute_subf(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+    pub(super) fn op_subf(&mut self, r: u8, r1: u8, r2: u8) -> VMResult<bool> {
         // r = dest
         // r1 = lhs
         // r2 = rhs
 
         let value = self.compute_float_sub(
-            self.register_bank.get_value(r1 as u8),
-            self.register_bank.get_value(r2 as u8),
+            self.register_bank.get_value(r1),
+            self.register_bank.get_value(r2),
         );
 
-        self.register_bank.set_value(r as u8, value);
+        self.register_bank.set_value(r, value);
 
         return Ok(false);
     }
 
-    fn execute_mulf(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+    pub(super) fn op_mulf(&mut self, r: u8, r1: u8, r2: u8) -> VMResult<bool> {
         // r = dest
         // r1 = lhs
         // r2 = rhs
 
         let value = self.compute_float_mul(
-            self.register_bank.get_value(r1 as u8),
-            self.register_bank.get_value(r2 as u8),
+            self.register_bank.get_value(r1),
+            self.register_bank.get_value(r2),
         );
 
-        self.register_bank.set_value(r as u8, value);
+        self.register_bank.set_value(r, value);
 
         return Ok(false);
     }
 
-    fn execute_divf(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+    pub(super) fn op_divf(&mut self, r: u8, r1: u8, r2: u8) -> VMResult<bool> {
         // r = dest
         // r1 = lhs
         // r2 = rhs
 
         let value = self.compute_float_div(
-            self.register_bank.get_value(r1 as u8),
-            self.register_bank.get_value(r2 as u8),
+            self.register_bank.get_value(r1),
+            self.register_bank.get_value(r2),
         );
 
-        self.register_bank.set_value(r as u8, value);
+        self.register_bank.set_value(r, value);
 
         return Ok(false);
     }
 
-    fn execute_rotl(&mut self, r: Register, r1: Register) -> Self::Output {
+    pub(super) fn op_rotl(&mut self, r: u8, r1: u8) -> VMResult<bool> {
         // r = register
         // r1 = number of rotations
 
-        let rotations = self.register_bank.get_value(r1 as u8);
-        let value = self.register_bank.get_value(r as u8);
+        let rotations = self.register_bank.get_value(r1);
+        let value = self.register_bank.get_value(r);
 
         self.register_bank.set_value(
-            r as u8,
+            r,
             value.rotate_left(rotations.try_into().unwrap_or(u32::MAX)),
         );
 
         return Ok(false);
     }
 
-    fn execute_rotli(&mut self, i: Immediate, r: Register) -> Self::Output {
+    pub(super) fn op_rotli(&mut self, i: u64, r: u8) -> VMResult<bool> {
         // i = number of rotations
         // r = register
 
-        let rotations: u64 = i.into();
-        let value = self.register_bank.get_value(r as u8);
+        let rotations: u64 = i;
+        let value = self.register_bank.get_value(r);
 
         self.register_bank.set_value(
-            r as u8,
+            r,
             value.rotate_left(rotations.try_into().unwrap_or(u32::MAX)),
         );
 
         return Ok(false);
     }
 
-    fn execute_rotr(&mut self, r: Register, r1: Register) -> Self::Output {
+    pub(super) fn op_rotr(&mut self, r: u8, r1: u8) -> VMResult<bool> {
         // r = register
         // r1 = number of rotations
 
-        let rotations = self.register_bank.get_value(r1 as u8);
-        let value = self.register_bank.get_value(r as u8);
+        let rotations = self.register_bank.get_value(r1);
+        let value = self.register_bank.get_value(r);
 
         self.register_bank.set_value(
-            r as u8,
+            r,
             value.rotate_right(rotations.try_into().unwrap_or(u32::MAX)),
         );
 
         return Ok(false);
     }
 
-    fn execute_rotri(&mut self, i: Immediate, r: Register) -> Self::Output {
+    pub(super) fn op_rotri(&mut self, i: u64, r: u8) -> VMResult<bool> {
         // r = register
         // i = number of rotations
 
-        let rotations: u64 = i.into();
-        let value = self.register_bank.get_value(r as u8);
+        let rotations: u64 = i;
+        let value = self.register_bank.get_value(r);
 
         self.register_bank.set_value(
-            r as u8,
+            r,
             value.rotate_right(rotations.try_into().unwrap_or(u32::MAX)),
         );
 
         return Ok(false);
     }
 
-    fn execute_sll(&mut self, r: Register, r1: Register) -> Self::Output {
+    pub(super) fn op_sll(&mut self, r: u8, r1: u8) -> VMResult<bool> {
         // r = register to shift
         // r1 = amount to shift
 
-        let shift = self.register_bank.get_value(r1 as u8);
+        let shift = self.register_bank.get_value(r1);
 
         self.register_bank
-            .set_value(r as u8, self.register_bank.get_value(r as u8) << shift);
+            .set_value(r, self.register_bank.get_value(r) << shift);
 
         return Ok(false);
     }
 
-    fn execute_slli(&mut self, i: Immediate, r: Register) -> Self::Output {
+    pub(super) fn op_slli(&mut self, i: u64, r: u8) -> VMResult<bool> {
         // r = register to shift
         // i = amount to shift
 
-        let shift: u64 = i.into();
+        let shift: u64 = i;
 
         self.register_bank
-            .set_value(r as u8, self.register_bank.get_value(r as u8) << shift);
+            .set_value(r, self.register_bank.get_value(r) << shift);
 
         return Ok(false);
     }
 
-    fn execute_srl(&mut self, r: Register, r1: Register) -> Self::Output {
+    pub(super) fn op_srl(&mut self, r: u8, r1: u8) -> VMResult<bool> {
         // r = register to shift
         // r1 = amount to shift
 
-        let shift = self.register_bank.get_value(r1 as u8);
+        let shift = self.register_bank.get_value(r1);
 
         self.register_bank
-            .set_value(r as u8, self.register_bank.get_value(r as u8) >> shift);
+            .set_value(r, self.register_bank.get_value(r) >> shift);
 
         return Ok(false);
     }
 
-    fn execute_srli(&mut self, i: Immediate, r: Register) -> Self::Output {
+    pub(super) fn op_srli(&mut self, i: u64, r: u8) -> VMResult<bool> {
         // r = register to shift
         // i = amount to shift
 
-        let shift: u64 = i.into();
+        let shift: u64 = i;
 
         self.register_bank
-            .set_value(r as u8, self.register_bank.get_value(r as u8) >> shift);
+            .set_value(r, self.register_bank.get_value(r) >> shift);
 
         return Ok(false);
     }
 
-    fn execute_not(&mut self, r: Register) -> Self::Output {
+    pub(super) fn op_not(&mut self, r: u8) -> VMResult<bool> {
         self.register_bank
-            .set_value(r as u8, !self.register_bank.get_value(r as u8));
+            .set_value(r, !self.register_bank.get_value(r));
 
         return Ok(false);
     }
 
-    fn execute_and(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+    pub(super) fn op_and(&mut self, r: u8, r1: u8, r2: u8) -> VMResult<bool> {
         // r = dest
         // r1 = lhs
         // r2 = rhs
 
-        let lhs = self.register_bank.get_value(r1 as u8);
-        let rhs = self.register_bank.get_value(r2 as u8);
+        let lhs = self.register_bank.get_value(r1);
+        let rhs = self.register_bank.get_value(r2);
 
-        self.register_bank.set_value(r as u8, lhs & rhs);
+        self.register_bank.set_value(r, lhs & rhs);
 
         return Ok(false);
     }
 
-    fn execute_or(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+    pub(super) fn op_or(&mut self, r: u8, r1: u8, r2: u8) -> VMResult<bool> {
         // r = dest
         // r1 = lhs
         // r2 = rhs
 
-        let lhs = self.register_bank.get_value(r1 as u8);
-        let rhs = self.register_bank.get_value(r2 as u8);
+        let lhs = self.register_bank.get_value(r1);
+        let rhs = self.register_bank.get_value(r2);
 
-        self.register_bank.set_value(r as u8, lhs | rhs);
+        self.register_bank.set_value(r, lhs | rhs);
 
         return Ok(false);
     }
 
-    fn execute_xor(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+    pub(super) fn op_xor(&mut self, r: u8, r1: u8, r2: u8) -> VMResult<bool> {
         // r = dest
         // r1 = lhs
         // r2 = rhs
 
-        let lhs = self.register_bank.get_value(r1 as u8);
-        let rhs = self.register_bank.get_value(r2 as u8);
+        let lhs = self.register_bank.get_value(r1);
+        let rhs = self.register_bank.get_value(r2);
 
-        self.register_bank.set_value(r as u8, lhs ^ rhs);
+        self.register_bank.set_value(r, lhs ^ rhs);
 
         return Ok(false);
     }
 
-    fn execute_cmp(&mut self, r: Register, r1: Register) -> Self::Output {
+    pub(super) fn op_cmp(&mut self, r: u8, r1: u8) -> VMResult<bool> {
         // r = lhs
         // r1 = rhs
 
-        let lhs = self.register_bank.get_value(r as u8);
-        let rhs = self.register_bank.get_value(r1 as u8);
-
-        let v = match lhs.cmp(&rhs) {
-            core::cmp::Ordering::Less => 0b010,
-            core::cmp::Ordering::Equal => 0b001,
-            core::cmp::Ordering::Greater => 0b100,
-        };
+        let lhs = self.register_bank.get_value(r);
+        let rhs = self.register_bank.get_value(r1);
 
-        self.register_bank.set_value(
-            Register::RFL as u8,
-            self.register_bank.get_value(Register::RFL as u8) & Self::FLAGS_MASK | v,
-        );
+        self.set_comparison_flags(lhs.cmp(&rhs));
 
         return Ok(false);
     }
 
-    fn execute_cmpi(&mut self, r: Register, r1: Register) -> Self::Output {
+    pub(super) fn op_cmpi(&mut self, r: u8, r1: u8) -> VMResult<bool> {
         // r = lhs
         // r1 = rhs
 
-        let lhs = self.register_bank.get_value(r as u8) as i64;
-        let rhs = self.register_bank.get_value(r1 as u8) as i64;
+        let lhs = self.register_bank.get_value(r) as i64;
+        let rhs = self.register_bank.get_value(r1) as i64;
 
-        let v = match lhs.cmp(&rhs) {
-            core::cmp::Ordering::Less => 0b010,
-            core::cmp::Ordering::Equal => 0b001,
-            core::cmp::Ordering::Greater => 0b100,
-        };
-
-        self.register_bank.set_value(
-            Register::RFL as u8,
-            self.register_bank.get_value(Register::RFL as u8) & Self::FLAGS_MASK | v,
-        );
+        self.set_comparison_flags(lhs.cmp(&rhs));
 
         return Ok(false);
     }
 
-    fn execute_cmpf(&mut self, r: Register, r1: Register) -> Self::Output {
+    pub(super) fn op_cmpf(&mut self, r: u8, r1: u8) -> VMResult<bool> {
         // r = lhs
         // r1 = rhs
 
-        let lhs: f64 = unsafe { core::mem::transmute(self.register_bank.get_value(r as u8)) };
-        let rhs: f64 = unsafe { core::mem::transmute(self.register_bank.get_value(r1 as u8)) };
+        let lhs: f64 = unsafe { core::mem::transmute(self.register_bank.get_value(r)) };
+        let rhs: f64 = unsafe { core::mem::transmute(self.register_bank.get_value(r1)) };
 
         let value;
 
@@ -1134,19 +1455,17 @@ impl ExecuteInstruction for VM {
         return Ok(false);
     }
 
-    fn execute_jmp(&mut self, a: Address) -> Self::Output {
-        let new_ip: u64 = a.into();
-        self.ip = new_ip as usize;
+    pub(super) fn op_jmp(&mut self, a: usize) -> VMResult<bool> {
+        self.ip = a;
 
         return Ok(true);
     }
 
-    fn execute_jeq(&mut self, a: Address) -> Self::Output {
+    pub(super) fn op_jeq(&mut self, a: usize) -> VMResult<bool> {
         let flags = self.register_bank.get_value(Register::RFL as u8);
 
         if (flags & Self::EQUALS_MASK) == 1 {
-            let new_ip: u64 = a.into();
-            self.ip = new_ip as usize;
+            self.ip = a;
 
             return Ok(true);
         } else {
@@ -1154,12 +1473,11 @@ impl ExecuteInstruction for VM {
         }
     }
 
-    fn execute_jne(&mut self, a: Address) -> Self::Output {
+    pub(super) fn op_jne(&mut self, a: usize) -> VMResult<bool> {
         let flags = self.register_bank.get_value(Register::RFL as u8);
 
         if (flags & Self::EQUALS_MASK) == 0 {
-            let new_ip: u64 = a.into();
-            self.ip = new_ip as usize;
+            self.ip = a;
 
             return Ok(true);
         } else {
@@ -1167,12 +1485,11 @@ impl ExecuteInstruction for VM {
         }
     }
 
-    fn execute_jge(&mut self, a: Address) -> Self::Output {
+    pub(super) fn op_jge(&mut self, a: usize) -> VMResult<bool> {
         let flags = self.register_bank.get_value(Register::RFL as u8);
 
         if (flags & Self::GREATER_THAN_MASK) >> 2 == 1 || (flags & Self::EQUALS_MASK) == 1 {
-            let new_ip: u64 = a.into();
-            self.ip = new_ip as usize;
+            self.ip = a;
 
             return Ok(true);
         } else {
@@ -1180,12 +1497,11 @@ impl ExecuteInstruction for VM {
         }
     }
 
-    fn execute_jgt(&mut self, a: Address) -> Self::Output {
+    pub(super) fn op_jgt(&mut self, a: usize) -> VMResult<bool> {
         let flags = self.register_bank.get_value(Register::RFL as u8);
 
         if (flags & Self::GREATER_THAN_MASK) >> 2 == 1 {
-            let new_ip: u64 = a.into();
-            self.ip = new_ip as usize;
+            self.ip = a;
 
             return Ok(true);
         } else {
@@ -1193,12 +1509,11 @@ impl ExecuteInstruction for VM {
         }
     }
 
-    fn execute_jle(&mut self, a: Address) -> Self::Output {
+    pub(super) fn op_jle(&mut self, a: usize) -> VMResult<bool> {
         let flags = self.register_bank.get_value(Register::RFL as u8);
 
         if (flags & Self::LESS_THAN_MASK) >> 1 == 1 || (flags & Self::EQUALS_MASK) == 1 {
-            let new_ip: u64 = a.into();
-            self.ip = new_ip as usize;
+            self.ip = a;
 
             return Ok(true);
         } else {
@@ -1206,12 +1521,11 @@ impl ExecuteInstruction for VM {
         }
     }
 
-    fn execute_jlt(&mut self, a: Address) -> Self::Output {
+    pub(super) fn op_jlt(&mut self, a: usize) -> VMResult<bool> {
         let flags = self.register_bank.get_value(Register::RFL as u8);
 
         if (flags & Self::LESS_THAN_MASK) >> 1 == 1 {
-            let new_ip: u64 = a.into();
-            self.ip = new_ip as usize;
+            self.ip = a;
 
             return Ok(true);
         } else {
@@ -1219,47 +1533,51 @@ impl ExecuteInstruction for VM {
         }
     }
 
-    fn execute_i2f(&mut self, r: Register) -> Self::Output {
-        let v = self.register_bank.get_value(r as u8) as i64;
+    pub(super) fn op_i2f(&mut self, r: u8) -> VMResult<bool> {
+        let v = self.register_bank.get_value(r) as i64;
 
         let value: f64 = v as f64;
         let float = unsafe { core::mem::transmute(value) };
 
-        self.register_bank.set_value(r as u8, float);
+        self.register_bank.set_value(r, float);
 
         return Ok(false);
     }
 
-    fn execute_f2i(&mut self, r: Register) -> Self::Output {
-        let v: f64 = unsafe { core::mem::transmute(self.register_bank.get_value(r as u8)) };
+    pub(super) fn op_f2i(&mut self, r: u8) -> VMResult<bool> {
+        let v: f64 = unsafe { core::mem::transmute(self.register_bank.get_value(r)) };
 
         let value = (v as i64) as u64;
 
-        self.register_bank.set_value(r as u8, value);
+        self.register_bank.set_value(r, value);
 
         return Ok(false);
     }
 
-    fn execute_swpa(&mut self, a: Address, a1: Address) -> Self::Output {
+    pub(super) fn op_swpa(&mut self, a: u64, a1: u64) -> VMResult<bool> {
+        self.check_swappable(a, a1)?;
+
         let a_mem = self
             .memory
-            .take(&a.into())
-            .ok_or(VMError::FailedGetNoAddressError(a.into()))?;
+            .take(&a)
+            .ok_or(VMError::FailedGetNoAddressError(a))?;
 
         let a1_mem = self
             .memory
-            .take(&a1.into())
-            .ok_or(VMError::FailedGetNoAddressError(a1.into()))?;
+            .take(&a1)
+            .ok_or(VMError::FailedGetNoAddressError(a1))?;
 
-        self.memory.assign_empty(a.into(), a1_mem);
-        self.memory.assign_empty(a1.into(), a_mem);
+        self.memory.assign_empty(a, a1_mem);
+        self.memory.assign_empty(a1, a_mem);
 
         return Ok(false);
     }
 
-    fn execute_swpar(&mut self, r: Register, r1: Register) -> Self::Output {
-        let a = self.register_bank.get_value(r as u8);
-        let a1 = self.register_bank.get_value(r1 as u8);
+    pub(super) fn op_swpar(&mut self, r: u8, r1: u8) -> VMResult<bool> {
+        let a = self.register_bank.get_value(r);
+        let a1 = self.register_bank.get_value(r1);
+
+        self.check_swappable(a, a1)?;
 
         let a_mem = self
             .memory
@@ -1277,20 +1595,20 @@ impl ExecuteInstruction for VM {
         return Ok(false);
     }
 
-    fn execute_swpr(&mut self, r: Register, r1: Register) -> Self::Output {
+    pub(super) fn op_swpr(&mut self, r: u8, r1: u8) -> VMResult<bool> {
         // a = r
         // b = r1
 
-        let temp = self.register_bank.get_value(r as u8);
+        let temp = self.register_bank.get_value(r);
 
         self.register_bank
-            .set_value(r as u8, self.register_bank.get_value(r1 as u8));
-        self.register_bank.set_value(r1 as u8, temp);
+            .set_value(r, self.register_bank.get_value(r1));
+        self.register_bank.set_value(r1, temp);
 
         return Ok(false);
     }
 
-    fn execute_call(&mut self, a: Address) -> Self::Output {
+    pub(super) fn op_call(&mut self, a: usize) -> VMResult<bool> {
         self.push_stack(self.register_bank.get_value(Register::RFP as u8))?;
         self.push_stack(self.register_bank.get_value(Register::RSP as u8))?;
         self.push_stack(self.ip as u64 + 1)?;
@@ -1300,14 +1618,13 @@ impl ExecuteInstruction for VM {
             self.register_bank.get_value(Register::RFP as u8),
         );
 
-        let new_ip: u64 = a.into();
-
-        self.ip = new_ip as usize;
+        self.ip = a;
+        self.ip_moved = true;
 
         return Ok(true);
     }
 
-    fn execute_ret(&mut self) -> Self::Output {
+    pub(super) fn op_ret(&mut self) -> VMResult<bool> {
         let ip = self.pop_stack()?;
         let rsp = self.pop_stack()?;
         let rfp = self.pop_stack()?;
@@ -1319,9 +1636,330 @@ impl ExecuteInstruction for VM {
         return Ok(true);
     }
 
-    fn execute_halt(&mut self) -> Self::Output {
+    pub(super) fn op_halt(&mut self) -> VMResult<bool> {
         self.halt();
 
         return Ok(false);
     }
 }
+
+/// Checks that the jumps and calls of instructions placed at `start` target one of the first
+/// `length` instructions.
+fn check_targets(
+    instructions: &[Instruction],
+    start: usize,
+    length: usize,
+) -> Result<(), ProgramError> {
+    for (i, instruction) in instructions.iter().enumerate() {
+        if let Some(target) = branch_target(instruction) {
+            if target >= length as u64 {
+                return Err(ProgramError::TargetOutOfBounds(start + i, target));
+            }
+        }
+    }
+
+    return Ok(());
+}
+
+impl ExecuteInstruction for VM {
+    type Machine = Self;
+    // A value of true indicates that the IP was modified and shouldn't be updated this cycle.
+    type Output = VMResult<bool>;
+    fn execute_nop(&mut self) -> Self::Output {
+        return self.op_nop();
+    }
+
+    fn execute_syscall<H: SyscallHandler<Self::Machine>>(
+        &mut self,
+        handler: &mut H,
+        i: Immediate,
+    ) -> Self::Output {
+        return self.op_syscall(handler, i.into());
+    }
+
+    fn execute_ldb(&mut self, i: Immediate, r: Register) -> Self::Output {
+        return self.op_ldb(i.into(), r as u8);
+    }
+
+    fn execute_ldi(&mut self, i: Immediate, r: Register) -> Self::Output {
+        return self.op_ldi(i.into(), r as u8);
+    }
+
+    fn execute_ldf(&mut self, i: Immediate, r: Register) -> Self::Output {
+        return self.op_ldf(i.into(), r as u8);
+    }
+
+    fn execute_mov(&mut self, r: Register, r1: Register) -> Self::Output {
+        return self.op_mov(r as u8, r1 as u8);
+    }
+
+    fn execute_push(&mut self, r: Register) -> Self::Output {
+        return self.op_push(r as u8);
+    }
+
+    fn execute_pop(&mut self, r: Register) -> Self::Output {
+        return self.op_pop(r as u8);
+    }
+
+    fn execute_sget(&mut self, r: Register, r1: Register) -> Self::Output {
+        return self.op_sget(r as u8, r1 as u8);
+    }
+
+    fn execute_malloc(&mut self, r: Register, r1: Register) -> Self::Output {
+        return self.op_malloc(r as u8, r1 as u8);
+    }
+
+    fn execute_malloci(&mut self, i: Immediate, r: Register) -> Self::Output {
+        return self.op_malloci(i.into(), r as u8);
+    }
+
+    fn execute_free(&mut self, r: Register) -> Self::Output {
+        return self.op_free(r as u8);
+    }
+
+    fn execute_freea(&mut self, a: Address) -> Self::Output {
+        return self.op_freea(a.into());
+    }
+
+    fn execute_setb(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+        return self.op_setb(r as u8, r1 as u8, r2 as u8);
+    }
+
+    fn execute_seti(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+        return self.op_seti(r as u8, r1 as u8, r2 as u8);
+    }
+
+    fn execute_isetb(&mut self, i: Immediate, r: Register, r1: Register) -> Self::Output {
+        return self.op_isetb(i.into(), r as u8, r1 as u8);
+    }
+
+    fn execute_iseti(&mut self, i: Immediate, r: Register, r1: Register) -> Self::Output {
+        return self.op_iseti(i.into(), r as u8, r1 as u8);
+    }
+
+    fn execute_getb(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+        return self.op_getb(r as u8, r1 as u8, r2 as u8);
+    }
+
+    fn execute_geti(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+        return self.op_geti(r as u8, r1 as u8, r2 as u8);
+    }
+
+    fn execute_igetb(&mut self, i: Immediate, r: Register, r1: Register) -> Self::Output {
+        return self.op_igetb(i.into(), r as u8, r1 as u8);
+    }
+
+    fn execute_igeti(&mut self, i: Immediate, r: Register, r1: Register) -> Self::Output {
+        return self.op_igeti(i.into(), r as u8, r1 as u8);
+    }
+
+    fn execute_last(&mut self, r: Register, r1: Register) -> Self::Output {
+        return self.op_last(r as u8, r1 as u8);
+    }
+
+    fn execute_length(&mut self, r: Register, r1: Register) -> Self::Output {
+        return self.op_length(r as u8, r1 as u8);
+    }
+
+    fn execute_clone(&mut self, r: Register, r1: Register) -> Self::Output {
+        return self.op_clone(r as u8, r1 as u8);
+    }
+
+    fn execute_copy(
+        &mut self,
+        r: Register,
+        r1: Register,
+        r2: Register,
+        r3: Register,
+        r4: Register,
+    ) -> Self::Output {
+        return self.op_copy(r as u8, r1 as u8, r2 as u8, r3 as u8, r4 as u8);
+    }
+
+    fn execute_copyi(
+        &mut self,
+        i: Immediate,
+        i1: Immediate,
+        i2: Immediate,
+        r: Register,
+        r1: Register,
+    ) -> Self::Output {
+        return self.op_copyi(i.into(), i1.into(), i2.into(), r as u8, r1 as u8);
+    }
+
+    fn execute_addi(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+        return self.op_addi(r as u8, r1 as u8, r2 as u8);
+    }
+
+    fn execute_subi(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+        return self.op_subi(r as u8, r1 as u8, r2 as u8);
+    }
+
+    fn execute_muli(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+        return self.op_muli(r as u8, r1 as u8, r2 as u8);
+    }
+
+    fn execute_divi(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+        return self.op_divi(r as u8, r1 as u8, r2 as u8);
+    }
+
+    fn execute_modi(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+        return self.op_modi(r as u8, r1 as u8, r2 as u8);
+    }
+
+    fn execute_addu(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+        return self.op_addu(r as u8, r1 as u8, r2 as u8);
+    }
+
+    fn execute_subu(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+        return self.op_subu(r as u8, r1 as u8, r2 as u8);
+    }
+
+    fn execute_mulu(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+        return self.op_mulu(r as u8, r1 as u8, r2 as u8);
+    }
+
+    fn execute_divu(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+        return self.op_divu(r as u8, r1 as u8, r2 as u8);
+    }
+
+    fn execute_modu(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+        return self.op_modu(r as u8, r1 as u8, r2 as u8);
+    }
+
+    fn execute_addf(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+        return self.op_addf(r as u8, r1 as u8, r2 as u8);
+    }
+
+    fn execute_subf(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+        return self.op_subf(r as u8, r1 as u8, r2 as u8);
+    }
+
+    fn execute_mulf(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+        return self.op_mulf(r as u8, r1 as u8, r2 as u8);
+    }
+
+    fn execute_divf(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+        return self.op_divf(r as u8, r1 as u8, r2 as u8);
+    }
+
+    fn execute_rotl(&mut self, r: Register, r1: Register) -> Self::Output {
+        return self.op_rotl(r as u8, r1 as u8);
+    }
+
+    fn execute_rotli(&mut self, i: Immediate, r: Register) -> Self::Output {
+        return self.op_rotli(i.into(), r as u8);
+    }
+
+    fn execute_rotr(&mut self, r: Register, r1: Register) -> Self::Output {
+        return self.op_rotr(r as u8, r1 as u8);
+    }
+
+    fn execute_rotri(&mut self, i: Immediate, r: Register) -> Self::Output {
+        return self.op_rotri(i.into(), r as u8);
+    }
+
+    fn execute_sll(&mut self, r: Register, r1: Register) -> Self::Output {
+        return self.op_sll(r as u8, r1 as u8);
+    }
+
+    fn execute_slli(&mut self, i: Immediate, r: Register) -> Self::Output {
+        return self.op_slli(i.into(), r as u8);
+    }
+
+    fn execute_srl(&mut self, r: Register, r1: Register) -> Self::Output {
+        return self.op_srl(r as u8, r1 as u8);
+    }
+
+    fn execute_srli(&mut self, i: Immediate, r: Register) -> Self::Output {
+        return self.op_srli(i.into(), r as u8);
+    }
+
+    fn execute_not(&mut self, r: Register) -> Self::Output {
+        return self.op_not(r as u8);
+    }
+
+    fn execute_and(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+        return self.op_and(r as u8, r1 as u8, r2 as u8);
+    }
+
+    fn execute_or(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+        return self.op_or(r as u8, r1 as u8, r2 as u8);
+    }
+
+    fn execute_xor(&mut self, r: Register, r1: Register, r2: Register) -> Self::Output {
+        return self.op_xor(r as u8, r1 as u8, r2 as u8);
+    }
+
+    fn execute_cmp(&mut self, r: Register, r1: Register) -> Self::Output {
+        return self.op_cmp(r as u8, r1 as u8);
+    }
+
+    fn execute_cmpi(&mut self, r: Register, r1: Register) -> Self::Output {
+        return self.op_cmpi(r as u8, r1 as u8);
+    }
+
+    fn execute_cmpf(&mut self, r: Register, r1: Register) -> Self::Output {
+        return self.op_cmpf(r as u8, r1 as u8);
+    }
+
+    fn execute_jmp(&mut self, a: Address) -> Self::Output {
+        return self.op_jmp(u64::from(a) as usize);
+    }
+
+    fn execute_jeq(&mut self, a: Address) -> Self::Output {
+        return self.op_jeq(u64::from(a) as usize);
+    }
+
+    fn execute_jne(&mut self, a: Address) -> Self::Output {
+        return self.op_jne(u64::from(a) as usize);
+    }
+
+    fn execute_jge(&mut self, a: Address) -> Self::Output {
+        return self.op_jge(u64::from(a) as usize);
+    }
+
+    fn execute_jgt(&mut self, a: Address) -> Self::Output {
+        return self.op_jgt(u64::from(a) as usize);
+    }
+
+    fn execute_jle(&mut self, a: Address) -> Self::Output {
+        return self.op_jle(u64::from(a) as usize);
+    }
+
+    fn execute_jlt(&mut self, a: Address) -> Self::Output {
+        return self.op_jlt(u64::from(a) as usize);
+    }
+
+    fn execute_i2f(&mut self, r: Register) -> Self::Output {
+        return self.op_i2f(r as u8);
+    }
+
+    fn execute_f2i(&mut self, r: Register) -> Self::Output {
+        return self.op_f2i(r as u8);
+    }
+
+    fn execute_swpa(&mut self, a: Address, a1: Address) -> Self::Output {
+        return self.op_swpa(a.into(), a1.into());
+    }
+
+    fn execute_swpar(&mut self, r: Register, r1: Register) -> Self::Output {
+        return self.op_swpar(r as u8, r1 as u8);
+    }
+
+    fn execute_swpr(&mut self, r: Register, r1: Register) -> Self::Output {
+        return self.op_swpr(r as u8, r1 as u8);
+    }
+
+    fn execute_call(&mut self, a: Address) -> Self::Output {
+        return self.op_call(u64::from(a) as usize);
+    }
+
+    fn execute_ret(&mut self) -> Self::Output {
+        return self.op_ret();
+    }
+
+    fn execute_halt(&mut self) -> Self::Output {
+        return self.op_halt();
+    }
+}
@@ -1,91 +1,168 @@
-use alloc::collections::{BTreeMap, BinaryHeap};
-use alloc::vec;
+use alloc::boxed::Box;
+use alloc::collections::BTreeSet;
 use alloc::vec::Vec;
-use core::cmp::Reverse;
+use core::cmp::Ordering;
+use core::fmt::Debug;
+use core::ops::Range;
 
+use super::{SlabHeap, TreeHeap};
 use crate::error::VMError;
 
+/// The storage used by `Memory` for heap blocks.
+///
+/// Blocks are identified by a handle (the address seen by guest programs) and must keep their
+/// handle until they are freed.
+pub trait HeapBackend: Debug {
+    fn allocate(&mut self, size: u64) -> Option<u64>;
+    fn allocate_with(&mut self, bytes: Vec<u8>) -> Option<u64>;
+
+    /// Replaces the contents of an existing block.
+    fn assign(&mut self, address: u64, data: Vec<u8>) -> bool;
+    /// Places a block at an address that is not currently in use.
+    fn assign_empty(&mut self, address: u64, data: Vec<u8>) -> bool;
+
+    fn free(&mut self, address: &u64) -> bool;
+    /// Removes a block without releasing its address for reuse.
+    fn take(&mut self, address: &u64) -> Option<Vec<u8>>;
+
+    fn retrieve(&self, address: &u64) -> Option<&Vec<u8>>;
+    fn retrieve_mutable(&mut self, address: &u64) -> Option<&mut Vec<u8>>;
+
+    fn total_allocated(&self) -> u64;
+    /// The addresses of every block, in increasing order.
+    fn addresses(&self) -> Vec<u64>;
+}
+
+#[derive(Debug)]
+enum Heap {
+    Tree(TreeHeap),
+    Slab(SlabHeap),
+    Custom(Box<dyn HeapBackend>),
+}
+
+/// Dispatches to the backend without a virtual call for the built in heaps.
+macro_rules! with_heap {
+    ($self:expr, $heap:ident => $body:expr) => {
+        match $self {
+            Heap::Tree($heap) => $body,
+            Heap::Slab($heap) => $body,
+            Heap::Custom($heap) => $body,
+        }
+    };
+}
+
 #[derive(Debug)]
 pub struct Memory {
-    memory: BTreeMap<u64, Vec<u8>>,
-    freed_addresses: BinaryHeap<Reverse<u64>>,
+    heap: Heap,
+    /// Blocks that can be read and copied from but never modified, freed or moved.
+    read_only: BTreeSet<u64>,
 }
 
 impl Memory {
     pub fn new() -> Self {
         return Self {
-            memory: BTreeMap::new(),
-            freed_addresses: BinaryHeap::new(),
+            heap: Heap::Tree(TreeHeap::new()),
+            read_only: BTreeSet::new(),
         };
     }
 
-    pub fn allocate(&mut self, size: u64) -> Option<u64> {
-        let address = self.alloc_next_address()?;
+    pub fn slab() -> Self {
+        return Self {
+            heap: Heap::Slab(SlabHeap::new()),
+            read_only: BTreeSet::new(),
+        };
+    }
 
-        self.memory.insert(address, vec![0; size as usize]);
+    pub fn with_backend(backend: Box<dyn HeapBackend>) -> Self {
+        return Self {
+            heap: Heap::Custom(backend),
+            read_only: BTreeSet::new(),
+        };
+    }
 
-        return Some(address);
+    pub fn allocate(&mut self, size: u64) -> Option<u64> {
+        return with_heap!(&mut self.heap, h => h.allocate(size));
     }
 
     pub fn assign(&mut self, address: u64, data: Vec<u8>) -> bool {
-        if !self.memory.contains_key(&address) {
+        if self.is_read_only(&address) {
             return false;
         }
 
-        self.memory.insert(address, data);
-
-        return true;
+        return with_heap!(&mut self.heap, h => h.assign(address, data));
     }
 
     pub fn assign_empty(&mut self, address: u64, data: Vec<u8>) -> bool {
-        if self.memory.contains_key(&address) {
-            return false;
-        }
-
-        self.memory.insert(address, data);
-
-        return true;
+        return with_heap!(&mut self.heap, h => h.assign_empty(address, data));
     }
 
     pub fn allocate_with(&mut self, bytes: Vec<u8>) -> Option<u64> {
-        let address = self.alloc_next_address()?;
+        return with_heap!(&mut self.heap, h => h.allocate_with(bytes));
+    }
+
+    /// Places `bytes` in a new block that can't be modified for the rest of execution.
+    pub fn allocate_read_only(&mut self, bytes: Vec<u8>) -> Option<u64> {
+        let address = self.allocate_with(bytes)?;
 
-        self.memory.insert(address, bytes);
+        self.read_only.insert(address);
 
         return Some(address);
     }
 
-    pub fn free(&mut self, address: &u64) -> bool {
-        let success = self.memory.remove(address).is_some();
+    pub fn is_read_only(&self, address: &u64) -> bool {
+        return self.read_only.contains(address);
+    }
 
-        if success {
-            if *address as usize != self.memory.len() {
-                self.freed_addresses.push(Reverse(*address));
-            }
+    pub fn free(&mut self, address: &u64) -> bool {
+        if self.is_read_only(address) {
+            return false;
         }
 
-        return success;
+        return with_heap!(&mut self.heap, h => h.free(address));
+    }
+
+    /// Frees a block from `allocate_read_only` that nothing can refer to yet, such as the data
+    /// sections of a module that failed to load.
+    pub(crate) fn free_read_only(&mut self, address: &u64) -> bool {
+        self.read_only.remove(address);
+
+        return with_heap!(&mut self.heap, h => h.free(address));
     }
 
     pub fn retrieve(&self, address: &u64) -> Option<&Vec<u8>> {
-        return self.memory.get(address);
+        return with_heap!(&self.heap, h => h.retrieve(address));
     }
 
+    /// Returns `None` for read only blocks as well as missing blocks.
     pub fn retrieve_mutable(&mut self, address: &u64) -> Option<&mut Vec<u8>> {
-        return self.memory.get_mut(address);
+        if self.is_read_only(address) {
+            return None;
+        }
+
+        return with_heap!(&mut self.heap, h => h.retrieve_mutable(address));
+    }
+
+    /// Returns the block at `address` for modification, failing if it is missing or read only.
+    pub fn block_mut(&mut self, address: &u64) -> Result<&mut Vec<u8>, VMError> {
+        if self.is_read_only(address) {
+            return Err(VMError::WriteToReadOnlyMemoryError(*address));
+        }
+
+        return self
+            .retrieve_mutable(address)
+            .ok_or(VMError::FailedSetNoAddressError(*address));
     }
 
     pub fn set(&mut self, address: &u64, offset: &u64, value: u8) -> Result<(), VMError> {
-        if let Some(bytes) = self.retrieve_mutable(address) {
-            if *offset >= bytes.len() as u64 {
-                return Err(VMError::IndexBeyondBoundsError(*offset, bytes.len() as u64));
-            }
+        let bytes = self.block_mut(address)?;
 
-            bytes[*offset as usize] = value;
-            return Ok(());
-        } else {
-            return Err(VMError::FailedSetNoAddressError(*address));
+        if *offset >= bytes.len() as u64 {
+            return Err(VMError::IndexBeyondBoundsError(*offset, bytes.len() as u64));
         }
+
+        bytes[*offset as usize] = value;
+
+        return Ok(());
     }
 
     pub fn get(&self, address: &u64, offset: &u64) -> Result<u8, VMError> {
@@ -100,27 +177,165 @@ impl Memory {
         }
     }
 
-    pub fn take(&mut self, address: &u64) -> Option<Vec<u8>> {
-        return self.memory.remove(address);
+    /// Returns `length` bytes of the block at `address` starting from `offset`.
+    pub fn slice(&self, address: &u64, offset: &u64, length: &u64) -> Result<&[u8], VMError> {
+        let bytes = self
+            .retrieve(address)
+            .ok_or(VMError::FailedGetNoAddressError(*address))?;
+        let range = Self::range(bytes.len(), *offset, *length)?;
+
+        return Ok(&bytes[range]);
     }
 
-    #[inline]
-    fn alloc_next_address(&mut self) -> Option<u64> {
-        if let Some(a) = self.freed_addresses.pop() {
-            return Some(a.0);
-        } else {
-            return Some(self.memory.len() as u64);
+    /// Returns `length` bytes of the block at `address` starting from `offset`.
+    pub fn slice_mut(
+        &mut self,
+        address: &u64,
+        offset: &u64,
+        length: &u64,
+    ) -> Result<&mut [u8], VMError> {
+        let bytes = self.block_mut(address)?;
+        let range = Self::range(bytes.len(), *offset, *length)?;
+
+        return Ok(&mut bytes[range]);
+    }
+
+    pub fn fill(
+        &mut self,
+        address: &u64,
+        offset: &u64,
+        length: &u64,
+        value: u8,
+    ) -> Result<(), VMError> {
+        self.slice_mut(address, offset, length)?.fill(value);
+
+        return Ok(());
+    }
+
+    /// Compares two ranges of `length` bytes lexicographically.
+    pub fn compare(
+        &self,
+        lhs_address: &u64,
+        lhs_offset: &u64,
+        rhs_address: &u64,
+        rhs_offset: &u64,
+        length: &u64,
+    ) -> Result<Ordering, VMError> {
+        let lhs = self.slice(lhs_address, lhs_offset, length)?;
+        let rhs = self.slice(rhs_address, rhs_offset, length)?;
+
+        return Ok(lhs.cmp(rhs));
+    }
+
+    /// Returns the offset within the block of the first occurrence of `value` in the range.
+    pub fn find(
+        &self,
+        address: &u64,
+        offset: &u64,
+        length: &u64,
+        value: u8,
+    ) -> Result<Option<u64>, VMError> {
+        let position = self
+            .slice(address, offset, length)?
+            .iter()
+            .position(|byte| *byte == value);
+
+        return Ok(position.map(|position| *offset + position as u64));
+    }
+
+    /// Copies `length` bytes between two ranges, which may overlap if they are in the same block.
+    ///
+    /// Both ranges are checked before anything is written, so a failed copy leaves memory
+    /// unchanged. The error is the one a byte by byte copy would stop at, each byte is read before
+    /// it is written.
+    pub fn copy(
+        &mut self,
+        dest_address: &u64,
+        dest_offset: &u64,
+        src_address: &u64,
+        src_offset: &u64,
+        length: &u64,
+    ) -> Result<(), VMError> {
+        if *length == 0 {
+            return Ok(());
+        }
+
+        let src_len = self
+            .retrieve(src_address)
+            .ok_or(VMError::FailedGetNoAddressError(*src_address))?
+            .len();
+
+        // The first byte is read before the destination is looked up.
+        if *src_offset >= src_len as u64 {
+            return Err(VMError::IndexBeyondBoundsError(*src_offset, src_len as u64));
         }
+
+        let dest_len = self.block_mut(dest_address)?.len();
+
+        // When both ranges end beyond their blocks, report whichever fails at an earlier byte.
+        let (src, dest) = match (
+            Self::range(src_len, *src_offset, *length),
+            Self::range(dest_len, *dest_offset, *length),
+        ) {
+            (Ok(src), Ok(dest)) => (src, dest),
+            (
+                Err(VMError::IndexBeyondBoundsError(src_index, _)),
+                Err(error @ VMError::IndexBeyondBoundsError(dest_index, _)),
+            ) if dest_index - dest_offset < src_index - src_offset => return Err(error),
+            (Err(error), _) | (_, Err(error)) => return Err(error),
+        };
+
+        if dest_address == src_address {
+            self.block_mut(src_address)?.copy_within(src, dest.start);
+
+            return Ok(());
+        }
+
+        // Take the source out of the heap so that both blocks can be borrowed at once.
+        let bytes = self
+            .take(src_address)
+            .ok_or(VMError::FailedGetNoAddressError(*src_address))?;
+
+        let result = self
+            .block_mut(dest_address)
+            .map(|block| block[dest].copy_from_slice(&bytes[src]));
+
+        self.assign_empty(*src_address, bytes);
+
+        return result;
+    }
+
+    /// Changes the size of a block without changing its address, new bytes are zeroed.
+    pub fn resize(&mut self, address: &u64, size: u64) -> Result<(), VMError> {
+        self.block_mut(address)?.resize(size as usize, 0);
+
+        return Ok(());
+    }
+
+    pub fn take(&mut self, address: &u64) -> Option<Vec<u8>> {
+        return with_heap!(&mut self.heap, h => h.take(address));
     }
 
     pub fn total_allocated(&self) -> u64 {
-        let mut total = 0;
+        return with_heap!(&self.heap, h => h.total_allocated());
+    }
 
-        for (_, loc) in &self.memory {
-            total += loc.len() as u64;
-        }
+    /// The addresses of every block, including read only blocks, in increasing order.
+    pub fn addresses(&self) -> Vec<u64> {
+        return with_heap!(&self.heap, h => h.addresses());
+    }
 
-        return total;
+    fn range(len: usize, offset: u64, length: u64) -> Result<Range<usize>, VMError> {
+        match offset.checked_add(length) {
+            Some(end) if end <= len as u64 => return Ok(offset as usize..end as usize),
+            // Report the first index beyond the block.
+            _ => {
+                return Err(VMError::IndexBeyondBoundsError(
+                    offset.max(len as u64),
+                    len as u64,
+                ))
+            }
+        }
     }
 }
 
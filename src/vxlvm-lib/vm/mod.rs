@@ -1,10 +1,16 @@
+mod decoded;
+mod fusion;
 mod machine;
 mod memory;
 mod registers;
+mod slab_heap;
 mod stack;
+mod tree_heap;
 
-pub use memory::Memory;
+pub use memory::{HeapBackend, Memory};
 pub use registers::Registers;
+pub use slab_heap::SlabHeap;
 use stack::Stack;
+pub use tree_heap::TreeHeap;
 
-pub use machine::VM;
+pub use machine::{OverflowBehaviour, VM};
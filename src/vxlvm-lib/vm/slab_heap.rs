@@ -0,0 +1,243 @@
+use alloc::collections::BinaryHeap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+
+use super::HeapBackend;
+
+/// A heap that stores blocks in a flat table indexed directly by address.
+///
+/// Looking up a block is a single index into the table and the buffers of freed blocks are kept
+/// for reuse, which avoids most allocator traffic for programs that repeatedly allocate and free
+/// small blocks.
+#[derive(Debug)]
+pub struct SlabHeap {
+    slots: Vec<Option<Vec<u8>>>,
+    /// May contain addresses that have since been reassigned, these are skipped when allocating.
+    free_slots: BinaryHeap<Reverse<u64>>,
+    /// Buffers from freed blocks, grouped by the base 2 logarithm of their capacity.
+    spare_buffers: Vec<Vec<Vec<u8>>>,
+}
+
+impl SlabHeap {
+    // Buffers of up to 4KB are reused.
+    const SIZE_CLASSES: usize = 13;
+    const MAX_SPARE_BUFFERS: usize = 64;
+
+    pub fn new() -> Self {
+        return Self {
+            slots: Vec::new(),
+            free_slots: BinaryHeap::new(),
+            spare_buffers: vec![Vec::new(); Self::SIZE_CLASSES],
+        };
+    }
+
+    fn next_slot(&mut self) -> usize {
+        while let Some(Reverse(address)) = self.free_slots.pop() {
+            if self.slots[address as usize].is_none() {
+                return address as usize;
+            }
+        }
+
+        self.slots.push(None);
+
+        return self.slots.len() - 1;
+    }
+
+    fn new_buffer(&mut self, size: usize) -> Vec<u8> {
+        if size > 1 << (Self::SIZE_CLASSES - 1) {
+            return vec![0; size];
+        }
+
+        let class = size.next_power_of_two().trailing_zeros() as usize;
+
+        let mut buffer = self.spare_buffers[class]
+            .pop()
+            .unwrap_or_else(|| Vec::with_capacity(1 << class));
+        buffer.resize(size, 0);
+
+        return buffer;
+    }
+
+    fn recycle(&mut self, mut buffer: Vec<u8>) {
+        if buffer.capacity() == 0 {
+            return;
+        }
+
+        // A buffer can serve any size up to the largest power of two that fits in its capacity.
+        let class = (usize::BITS - 1 - buffer.capacity().leading_zeros()) as usize;
+
+        if class < Self::SIZE_CLASSES && self.spare_buffers[class].len() < Self::MAX_SPARE_BUFFERS {
+            buffer.clear();
+            self.spare_buffers[class].push(buffer);
+        }
+    }
+}
+
+impl HeapBackend for SlabHeap {
+    fn allocate(&mut self, size: u64) -> Option<u64> {
+        let buffer = self.new_buffer(size as usize);
+
+        return self.allocate_with(buffer);
+    }
+
+    fn allocate_with(&mut self, bytes: Vec<u8>) -> Option<u64> {
+        let address = self.next_slot();
+
+        self.slots[address] = Some(bytes);
+
+        return Some(address as u64);
+    }
+
+    fn assign(&mut self, address: u64, data: Vec<u8>) -> bool {
+        if let Some(Some(block)) = self.slots.get_mut(address as usize) {
+            let previous = core::mem::replace(block, data);
+            self.recycle(previous);
+
+            return true;
+        }
+
+        return false;
+    }
+
+    fn assign_empty(&mut self, address: u64, data: Vec<u8>) -> bool {
+        let index = address as usize;
+
+        if index >= self.slots.len() {
+            for vacant in self.slots.len()..index {
+                self.free_slots.push(Reverse(vacant as u64));
+            }
+
+            self.slots.resize_with(index + 1, || None);
+        } else if self.slots[index].is_some() {
+            return false;
+        }
+
+        self.slots[index] = Some(data);
+
+        return true;
+    }
+
+    fn free(&mut self, address: &u64) -> bool {
+        if let Some(buffer) = self.take(address) {
+            self.recycle(buffer);
+            self.free_slots.push(Reverse(*address));
+
+            return true;
+        }
+
+        return false;
+    }
+
+    fn take(&mut self, address: &u64) -> Option<Vec<u8>> {
+        return self.slots.get_mut(*address as usize)?.take();
+    }
+
+    fn retrieve(&self, address: &u64) -> Option<&Vec<u8>> {
+        return self.slots.get(*address as usize)?.as_ref();
+    }
+
+    fn retrieve_mutable(&mut self, address: &u64) -> Option<&mut Vec<u8>> {
+        return self.slots.get_mut(*address as usize)?.as_mut();
+    }
+
+    fn total_allocated(&self) -> u64 {
+        return self
+            .slots
+            .iter()
+            .flatten()
+            .map(|block| block.len() as u64)
+            .sum();
+    }
+
+    fn addresses(&self) -> Vec<u64> {
+        return self
+            .slots
+            .iter()
+            .enumerate()
+            .filter(|(_, block)| block.is_some())
+            .map(|(address, _)| address as u64)
+            .collect();
+    }
+}
+
+impl Default for SlabHeap {
+    fn default() -> Self {
+        return SlabHeap::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reuse_lowest_address() {
+        let mut heap = SlabHeap::new();
+
+        assert_eq!(heap.allocate(4), Some(0));
+        assert_eq!(heap.allocate(4), Some(1));
+        assert_eq!(heap.allocate(4), Some(2));
+
+        assert!(heap.free(&2));
+        assert!(heap.free(&0));
+        assert!(!heap.free(&0));
+
+        assert_eq!(heap.allocate(4), Some(0));
+        assert_eq!(heap.allocate(4), Some(2));
+        assert_eq!(heap.allocate(4), Some(3));
+    }
+
+    #[test]
+    fn test_recycled_buffer_is_zeroed() {
+        let mut heap = SlabHeap::new();
+
+        let address = heap.allocate(8).unwrap();
+        heap.retrieve_mutable(&address)
+            .unwrap()
+            .copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+
+        assert!(heap.free(&address));
+
+        let address = heap.allocate(6).unwrap();
+        assert_eq!(heap.retrieve(&address).unwrap(), &vec![0u8; 6]);
+        assert_eq!(heap.total_allocated(), 6);
+    }
+
+    #[test]
+    fn test_take_and_assign_empty() {
+        let mut heap = SlabHeap::new();
+
+        heap.allocate_with(vec![1, 2]);
+        heap.allocate_with(vec![3, 4]);
+
+        let a = heap.take(&0).unwrap();
+        let b = heap.take(&1).unwrap();
+
+        assert!(heap.assign_empty(0, b));
+        assert!(heap.assign_empty(1, a));
+        assert!(!heap.assign_empty(1, vec![]));
+
+        assert_eq!(heap.retrieve(&0).unwrap(), &vec![3, 4]);
+        assert_eq!(heap.retrieve(&1).unwrap(), &vec![1, 2]);
+
+        // Taken addresses are not handed out again.
+        assert_eq!(heap.allocate(1), Some(2));
+    }
+
+    #[test]
+    fn test_assign_empty_beyond_end() {
+        let mut heap = SlabHeap::new();
+
+        assert!(heap.assign_empty(2, vec![9]));
+        assert!(heap.retrieve(&0).is_none());
+
+        assert_eq!(heap.allocate(1), Some(0));
+        assert_eq!(heap.allocate(1), Some(1));
+        assert_eq!(heap.allocate(1), Some(3));
+        assert_eq!(heap.retrieve(&2).unwrap(), &vec![9]);
+
+        heap.free(&1);
+        assert_eq!(heap.addresses(), vec![0, 2, 3]);
+    }
+}
@@ -18,6 +18,11 @@ impl Stack {
         };
     }
 
+    /// The number of bytes the stack can hold.
+    pub fn size(&self) -> u64 {
+        return self.items.len() as u64;
+    }
+
     pub fn insert(&mut self, index: u64, values: Vec<u8>) -> bool {
         for i in 0..values.len() {
             if index as usize + i >= self.items.len() {
@@ -0,0 +1,113 @@
+use alloc::collections::{BTreeMap, BinaryHeap};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+
+use super::HeapBackend;
+
+/// A heap that stores each block in an ordered map keyed by address.
+#[derive(Debug)]
+pub struct TreeHeap {
+    memory: BTreeMap<u64, Vec<u8>>,
+    freed_addresses: BinaryHeap<Reverse<u64>>,
+}
+
+impl TreeHeap {
+    pub fn new() -> Self {
+        return Self {
+            memory: BTreeMap::new(),
+            freed_addresses: BinaryHeap::new(),
+        };
+    }
+
+    #[inline]
+    fn alloc_next_address(&mut self) -> Option<u64> {
+        if let Some(a) = self.freed_addresses.pop() {
+            return Some(a.0);
+        } else {
+            return Some(self.memory.len() as u64);
+        }
+    }
+}
+
+impl HeapBackend for TreeHeap {
+    fn allocate(&mut self, size: u64) -> Option<u64> {
+        let address = self.alloc_next_address()?;
+
+        self.memory.insert(address, vec![0; size as usize]);
+
+        return Some(address);
+    }
+
+    fn allocate_with(&mut self, bytes: Vec<u8>) -> Option<u64> {
+        let address = self.alloc_next_address()?;
+
+        self.memory.insert(address, bytes);
+
+        return Some(address);
+    }
+
+    fn assign(&mut self, address: u64, data: Vec<u8>) -> bool {
+        if !self.memory.contains_key(&address) {
+            return false;
+        }
+
+        self.memory.insert(address, data);
+
+        return true;
+    }
+
+    fn assign_empty(&mut self, address: u64, data: Vec<u8>) -> bool {
+        if self.memory.contains_key(&address) {
+            return false;
+        }
+
+        self.memory.insert(address, data);
+
+        return true;
+    }
+
+    fn free(&mut self, address: &u64) -> bool {
+        let success = self.memory.remove(address).is_some();
+
+        if success {
+            if *address as usize != self.memory.len() {
+                self.freed_addresses.push(Reverse(*address));
+            }
+        }
+
+        return success;
+    }
+
+    fn take(&mut self, address: &u64) -> Option<Vec<u8>> {
+        return self.memory.remove(address);
+    }
+
+    fn retrieve(&self, address: &u64) -> Option<&Vec<u8>> {
+        return self.memory.get(address);
+    }
+
+    fn retrieve_mutable(&mut self, address: &u64) -> Option<&mut Vec<u8>> {
+        return self.memory.get_mut(address);
+    }
+
+    fn total_allocated(&self) -> u64 {
+        let mut total = 0;
+
+        for (_, loc) in &self.memory {
+            total += loc.len() as u64;
+        }
+
+        return total;
+    }
+
+    fn addresses(&self) -> Vec<u64> {
+        return self.memory.keys().copied().collect();
+    }
+}
+
+impl Default for TreeHeap {
+    fn default() -> Self {
+        return TreeHeap::new();
+    }
+}
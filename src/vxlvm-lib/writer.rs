@@ -0,0 +1,461 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::linker::LinkTable;
+use crate::loader::{ChecksumAlgorithm, Loader, SectionKind};
+use crate::signature::{sign_unchecked, SigningKey};
+use crate::symbols::SymbolTable;
+use vxl_iset::instruction::Instruction;
+use vxl_iset::instruction_arguments::{Address, Immediate, Register};
+use vxl_iset::vxl_file::VXLHeader;
+
+/// Produces `.xvl` files, the inverse of `Loader`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Writer {
+    instructions: Vec<Instruction>,
+    starting_offset: u64,
+    algorithm: ChecksumAlgorithm,
+    data_sections: Vec<Vec<u8>>,
+    symbols: Option<SymbolTable>,
+    link_table: Option<LinkTable>,
+}
+
+impl Writer {
+    /// The starting offset is the index of the first instruction to execute.
+    pub fn new(
+        instructions: Vec<Instruction>,
+        starting_offset: u64,
+        algorithm: ChecksumAlgorithm,
+    ) -> Self {
+        return Self {
+            instructions,
+            starting_offset,
+            algorithm,
+            data_sections: Vec::new(),
+            symbols: None,
+            link_table: None,
+        };
+    }
+
+    /// Adds a read only data section, returning its index for `StandardLibrary::DATA_SECTION`.
+    ///
+    /// Files with data sections, symbols or a link table are written in the sectioned format,
+    /// otherwise the original format is used.
+    pub fn add_data_section(&mut self, bytes: Vec<u8>) -> u64 {
+        self.data_sections.push(bytes);
+
+        return self.data_sections.len() as u64 - 1;
+    }
+
+    pub fn set_symbols(&mut self, symbols: SymbolTable) {
+        self.symbols = Some(symbols);
+    }
+
+    pub fn set_link_table(&mut self, link_table: LinkTable) {
+        self.link_table = Some(link_table);
+    }
+
+    fn is_sectioned(&self) -> bool {
+        return !self.data_sections.is_empty()
+            || self.symbols.is_some()
+            || self.link_table.is_some();
+    }
+
+    fn version(&self) -> u8 {
+        if self.is_sectioned() {
+            return Loader::SECTIONED_VERSION;
+        } else {
+            return VXLHeader::SUPPORTED_VERSIONS[0];
+        }
+    }
+
+    pub fn to_program_bytes(&self) -> Vec<u8> {
+        let mut code = Vec::new();
+
+        for instruction in &self.instructions {
+            encode_instruction(*instruction, &mut code);
+        }
+
+        if !self.is_sectioned() {
+            return code;
+        }
+
+        let mut sections = vec![(SectionKind::Code, code)];
+
+        for section in &self.data_sections {
+            sections.push((SectionKind::Data, section.clone()));
+        }
+
+        if let Some(symbols) = &self.symbols {
+            sections.push((SectionKind::Symbols, symbols.to_bytes()));
+        }
+
+        if let Some(link_table) = &self.link_table {
+            sections.push((SectionKind::Link, link_table.to_bytes()));
+        }
+
+        let mut bytes = Vec::new();
+
+        bytes.extend_from_slice(&(sections.len() as u64).to_le_bytes());
+
+        for (kind, section) in &sections {
+            bytes.push(kind.as_u8());
+            bytes.extend_from_slice(&(section.len() as u64).to_le_bytes());
+        }
+
+        for (_kind, section) in &sections {
+            bytes.extend_from_slice(section);
+        }
+
+        return bytes;
+    }
+
+    pub fn header_for(&self, program_bytes: &[u8]) -> VXLHeader {
+        return VXLHeader::new(
+            self.version(),
+            program_bytes.len() as u64,
+            self.starting_offset,
+            self.algorithm.flags(),
+            self.algorithm.checksum(program_bytes),
+        );
+    }
+
+    /// Returns the complete file, a header followed by the encoded program.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let program_bytes = self.to_program_bytes();
+        let header = self.header_for(&program_bytes);
+
+        let mut bytes = Vec::with_capacity(VXLHeader::HEADER_SIZE + program_bytes.len());
+
+        encode_header(&header, &mut bytes);
+        bytes.extend_from_slice(&program_bytes);
+
+        return bytes;
+    }
+
+    /// Returns the complete file followed by a signature trailer, see `signature::sign_file`.
+    pub fn to_signed_bytes(&self, key: &SigningKey) -> Vec<u8> {
+        return sign_unchecked(self.to_bytes(), key);
+    }
+}
+
+/// Appends the bytes of a header, the inverse of reading it with `Loader`.
+pub(crate) fn encode_header(header: &VXLHeader, bytes: &mut Vec<u8>) {
+    bytes.extend_from_slice(&VXLHeader::MAGIC);
+    bytes.push(header.version());
+    bytes.extend_from_slice(&header.file_size().to_le_bytes());
+    bytes.extend_from_slice(&header.starting_offset().to_le_bytes());
+    bytes.push(header.flags());
+    bytes.extend_from_slice(&header.checksum());
+    bytes.push(VXLHeader::END_HEADER_BYTE);
+}
+
+/// Appends the encoding of an instruction to `bytes`, the inverse of
+/// `Validator::take_next_instruction`.
+pub fn encode_instruction(instruction: Instruction, bytes: &mut Vec<u8>) {
+    let (opcode, registers, addresses, immediates) = instruction_parts(instruction);
+
+    bytes.push(opcode);
+
+    // immediates | addresses | registers
+
+    for immediate in immediates {
+        bytes.extend_from_slice(&u64::from(immediate).to_le_bytes());
+    }
+
+    for address in addresses {
+        bytes.extend_from_slice(&u64::from(address).to_le_bytes());
+    }
+
+    // Registers are packed two to a byte, the first in the upper 4 bits.
+    for pair in registers.chunks(2) {
+        let mut byte = (pair[0] as u8) << 4;
+
+        if let Some(register) = pair.get(1) {
+            byte |= *register as u8 & 0xF;
+        }
+
+        bytes.push(byte);
+    }
+}
+
+/// The number of bytes in the encoding of an instruction.
+pub(crate) fn encoded_size(instruction: Instruction) -> usize {
+    let (_opcode, registers, addresses, immediates) = instruction_parts(instruction);
+
+    return 1
+        + immediates.len() * Immediate::BYTES
+        + addresses.len() * Address::BYTES
+        + registers.len().div_ceil(2);
+}
+
+/// Splits an instruction into the arguments accepted by `Instruction::new`.
+pub(crate) fn instruction_parts(
+    instruction: Instruction,
+) -> (u8, Vec<Register>, Vec<Address>, Vec<Immediate>) {
+    return match instruction {
+        Instruction::Nop => (0x00, vec![], vec![], vec![]),
+        Instruction::Syscall(i) => (0x01, vec![], vec![], vec![i]),
+        Instruction::Ldb(i, r) => (0x02, vec![r], vec![], vec![i]),
+        Instruction::Ldi(i, r) => (0x03, vec![r], vec![], vec![i]),
+        Instruction::Ldf(i, r) => (0x04, vec![r], vec![], vec![i]),
+        Instruction::Mov(r, r1) => (0x05, vec![r, r1], vec![], vec![]),
+        Instruction::Push(r) => (0x06, vec![r], vec![], vec![]),
+        Instruction::Pop(r) => (0x07, vec![r], vec![], vec![]),
+        Instruction::Sget(r, r1) => (0x08, vec![r, r1], vec![], vec![]),
+        Instruction::Malloc(r, r1) => (0x09, vec![r, r1], vec![], vec![]),
+        Instruction::Malloci(i, r) => (0x0a, vec![r], vec![], vec![i]),
+        Instruction::Free(r) => (0x0b, vec![r], vec![], vec![]),
+        Instruction::Freea(a) => (0x0c, vec![], vec![a], vec![]),
+        Instruction::Setb(r, r1, r2) => (0x0d, vec![r, r1, r2], vec![], vec![]),
+        Instruction::Seti(r, r1, r2) => (0x0e, vec![r, r1, r2], vec![], vec![]),
+        Instruction::Isetb(i, r, r1) => (0x0f, vec![r, r1], vec![], vec![i]),
+        Instruction::Iseti(i, r, r1) => (0x10, vec![r, r1], vec![], vec![i]),
+        Instruction::Getb(r, r1, r2) => (0x11, vec![r, r1, r2], vec![], vec![]),
+        Instruction::Geti(r, r1, r2) => (0x12, vec![r, r1, r2], vec![], vec![]),
+        Instruction::Igetb(i, r, r1) => (0x13, vec![r, r1], vec![], vec![i]),
+        Instruction::Igeti(i, r, r1) => (0x14, vec![r, r1], vec![], vec![i]),
+        Instruction::Last(r, r1) => (0x15, vec![r, r1], vec![], vec![]),
+        Instruction::Length(r, r1) => (0x16, vec![r, r1], vec![], vec![]),
+        Instruction::Clone(r, r1) => (0x17, vec![r, r1], vec![], vec![]),
+        Instruction::Copy(r, r1, r2, r3, r4) => (0x18, vec![r, r1, r2, r3, r4], vec![], vec![]),
+        Instruction::Copyi(i, i1, i2, r, r1) => (0x19, vec![r, r1], vec![], vec![i, i1, i2]),
+        Instruction::Addi(r, r1, r2) => (0x1a, vec![r, r1, r2], vec![], vec![]),
+        Instruction::Subi(r, r1, r2) => (0x1b, vec![r, r1, r2], vec![], vec![]),
+        Instruction::Muli(r, r1, r2) => (0x1c, vec![r, r1, r2], vec![], vec![]),
+        Instruction::Divi(r, r1, r2) => (0x1d, vec![r, r1, r2], vec![], vec![]),
+        Instruction::Modi(r, r1, r2) => (0x1e, vec![r, r1, r2], vec![], vec![]),
+        Instruction::Addu(r, r1, r2) => (0x1f, vec![r, r1, r2], vec![], vec![]),
+        Instruction::Subu(r, r1, r2) => (0x20, vec![r, r1, r2], vec![], vec![]),
+        Instruction::Mulu(r, r1, r2) => (0x21, vec![r, r1, r2], vec![], vec![]),
+        Instruction::Divu(r, r1, r2) => (0x22, vec![r, r1, r2], vec![], vec![]),
+        Instruction::Modu(r, r1, r2) => (0x23, vec![r, r1, r2], vec![], vec![]),
+        Instruction::Addf(r, r1, r2) => (0x24, vec![r, r1, r2], vec![], vec![]),
+        Instruction::Subf(r, r1, r2) => (0x25, vec![r, r1, r2], vec![], vec![]),
+        Instruction::Mulf(r, r1, r2) => (0x26, vec![r, r1, r2], vec![], vec![]),
+        Instruction::Divf(r, r1, r2) => (0x27, vec![r, r1, r2], vec![], vec![]),
+        Instruction::Rotl(r, r1) => (0x28, vec![r, r1], vec![], vec![]),
+        Instruction::Rotli(i, r) => (0x29, vec![r], vec![], vec![i]),
+        Instruction::Rotr(r, r1) => (0x2a, vec![r, r1], vec![], vec![]),
+        Instruction::Rotri(i, r) => (0x2b, vec![r], vec![], vec![i]),
+        Instruction::Sll(r, r1) => (0x2c, vec![r, r1], vec![], vec![]),
+        Instruction::Slli(i, r) => (0x2d, vec![r], vec![], vec![i]),
+        Instruction::Srl(r, r1) => (0x2e, vec![r, r1], vec![], vec![]),
+        Instruction::Srli(i, r) => (0x2f, vec![r], vec![], vec![i]),
+        Instruction::Not(r) => (0x30, vec![r], vec![], vec![]),
+        Instruction::And(r, r1, r2) => (0x31, vec![r, r1, r2], vec![], vec![]),
+        Instruction::Or(r, r1, r2) => (0x32, vec![r, r1, r2], vec![], vec![]),
+        Instruction::Xor(r, r1, r2) => (0x33, vec![r, r1, r2], vec![], vec![]),
+        Instruction::Cmp(r, r1) => (0x34, vec![r, r1], vec![], vec![]),
+        Instruction::Cmpi(r, r1) => (0x35, vec![r, r1], vec![], vec![]),
+        Instruction::Cmpf(r, r1) => (0x36, vec![r, r1], vec![], vec![]),
+        Instruction::Jmp(a) => (0x37, vec![], vec![a], vec![]),
+        Instruction::Jeq(a) => (0x38, vec![], vec![a], vec![]),
+        Instruction::Jne(a) => (0x39, vec![], vec![a], vec![]),
+        Instruction::Jge(a) => (0x3a, vec![], vec![a], vec![]),
+        Instruction::Jgt(a) => (0x3b, vec![], vec![a], vec![]),
+        Instruction::Jle(a) => (0x3c, vec![], vec![a], vec![]),
+        Instruction::Jlt(a) => (0x3d, vec![], vec![a], vec![]),
+        Instruction::I2f(r) => (0x3e, vec![r], vec![], vec![]),
+        Instruction::F2i(r) => (0x3f, vec![r], vec![], vec![]),
+        Instruction::Swpa(a, a1) => (0x40, vec![], vec![a, a1], vec![]),
+        Instruction::Swpar(r, r1) => (0x41, vec![r, r1], vec![], vec![]),
+        Instruction::Swpr(r, r1) => (0x42, vec![r, r1], vec![], vec![]),
+        Instruction::Call(a) => (0x43, vec![], vec![a], vec![]),
+        Instruction::Ret => (0x44, vec![], vec![], vec![]),
+        Instruction::Halt => (0x45, vec![], vec![], vec![]),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::loader::{ChecksumAlgorithm, Loader};
+    use crate::validator::BulkValidator;
+    use alloc::string::String;
+
+    fn all_instructions() -> Vec<Instruction> {
+        let i = Immediate::from(0x0123_4567_89ab_cdefu64);
+        let i1 = Immediate::from(-2i64);
+        let i2 = Immediate::from(63.24f64);
+        let a = Address::from(3u64);
+        let a1 = Address::from(u64::MAX);
+        let (r, r1, r2, r3, r4) = (
+            Register::R0,
+            Register::ROU,
+            Register::R9,
+            Register::RFL,
+            Register::R5,
+        );
+
+        return vec![
+            Instruction::Nop,
+            Instruction::Syscall(i),
+            Instruction::Ldb(i, r),
+            Instruction::Ldi(i1, r1),
+            Instruction::Ldf(i2, r2),
+            Instruction::Mov(r, r1),
+            Instruction::Push(r),
+            Instruction::Pop(r1),
+            Instruction::Sget(r, r1),
+            Instruction::Malloc(r, r1),
+            Instruction::Malloci(i, r),
+            Instruction::Free(r),
+            Instruction::Freea(a),
+            Instruction::Setb(r, r1, r2),
+            Instruction::Seti(r, r1, r2),
+            Instruction::Isetb(i, r, r1),
+            Instruction::Iseti(i, r, r1),
+            Instruction::Getb(r, r1, r2),
+            Instruction::Geti(r, r1, r2),
+            Instruction::Igetb(i, r, r1),
+            Instruction::Igeti(i, r, r1),
+            Instruction::Last(r, r1),
+            Instruction::Length(r, r1),
+            Instruction::Clone(r, r1),
+            Instruction::Copy(r, r1, r2, r3, r4),
+            Instruction::Copyi(i, i1, i2, r, r1),
+            Instruction::Addi(r, r1, r2),
+            Instruction::Subi(r, r1, r2),
+            Instruction::Muli(r, r1, r2),
+            Instruction::Divi(r, r1, r2),
+            Instruction::Modi(r, r1, r2),
+            Instruction::Addu(r, r1, r2),
+            Instruction::Subu(r, r1, r2),
+            Instruction::Mulu(r, r1, r2),
+            Instruction::Divu(r, r1, r2),
+            Instruction::Modu(r, r1, r2),
+            Instruction::Addf(r, r1, r2),
+            Instruction::Subf(r, r1, r2),
+            Instruction::Mulf(r, r1, r2),
+            Instruction::Divf(r, r1, r2),
+            Instruction::Rotl(r, r1),
+            Instruction::Rotli(i, r),
+            Instruction::Rotr(r, r1),
+            Instruction::Rotri(i, r),
+            Instruction::Sll(r, r1),
+            Instruction::Slli(i, r),
+            Instruction::Srl(r, r1),
+            Instruction::Srli(i, r),
+            Instruction::Not(r),
+            Instruction::And(r, r1, r2),
+            Instruction::Or(r, r1, r2),
+            Instruction::Xor(r, r1, r2),
+            Instruction::Cmp(r, r1),
+            Instruction::Cmpi(r, r1),
+            Instruction::Cmpf(r, r1),
+            Instruction::Jmp(a),
+            Instruction::Jeq(a),
+            Instruction::Jne(a),
+            Instruction::Jge(a),
+            Instruction::Jgt(a),
+            Instruction::Jle(a),
+            Instruction::Jlt(a),
+            Instruction::I2f(r),
+            Instruction::F2i(r),
+            Instruction::Swpa(a, a1),
+            Instruction::Swpar(r, r1),
+            Instruction::Swpr(r, r1),
+            Instruction::Call(a),
+            Instruction::Ret,
+            Instruction::Halt,
+        ];
+    }
+
+    fn round_trip(algorithm: ChecksumAlgorithm) {
+        let instructions = all_instructions();
+        let bytes = Writer::new(instructions.clone(), 4, algorithm).to_bytes();
+
+        let loader = Loader::load_bytes(&bytes).unwrap();
+        assert_eq!(loader.validate(), Ok(()));
+
+        let (header, decoded) = loader.to_instructions(BulkValidator::new()).unwrap();
+
+        assert_eq!(header.starting_offset(), 4);
+        assert_eq!(
+            header.file_size() as usize,
+            bytes.len() - VXLHeader::HEADER_SIZE
+        );
+        assert_eq!(decoded, instructions);
+    }
+
+    #[test]
+    fn test_round_trip_sha2() {
+        round_trip(ChecksumAlgorithm::Sha2_224);
+    }
+
+    #[test]
+    fn test_round_trip_sha3() {
+        round_trip(ChecksumAlgorithm::Sha3_224);
+    }
+
+    #[test]
+    fn test_round_trip_other_algorithms() {
+        for algorithm in ChecksumAlgorithm::ALL {
+            round_trip(algorithm);
+
+            assert_eq!(
+                ChecksumAlgorithm::from_flags(algorithm.flags()),
+                Some(algorithm)
+            );
+            assert_eq!(
+                ChecksumAlgorithm::from_name(algorithm.name()),
+                Some(algorithm)
+            );
+        }
+    }
+
+    #[test]
+    fn test_encode_registers() {
+        let mut bytes = Vec::new();
+
+        // addi $r2, $r0, $r1 (r0 + r1 -> r2)
+        encode_instruction(
+            Instruction::Addi(Register::R2, Register::R0, Register::R1),
+            &mut bytes,
+        );
+
+        assert_eq!(bytes, vec![0b0001_1010, 0b1000_0110, 0b0111_0000]);
+    }
+
+    #[test]
+    fn test_data_sections() {
+        let instructions = all_instructions();
+        let mut writer = Writer::new(instructions.clone(), 0, ChecksumAlgorithm::Sha3_224);
+
+        assert_eq!(writer.add_data_section(b"hello".to_vec()), 0);
+        assert_eq!(writer.add_data_section(Vec::new()), 1);
+
+        let loader = Loader::load_bytes(&writer.to_bytes()).unwrap();
+        assert_eq!(loader.validate(), Ok(()));
+        assert_eq!(loader.get_header().version(), Loader::SECTIONED_VERSION);
+        assert_eq!(loader.data_sections(), vec![&b"hello"[..], &[][..]]);
+
+        let (_header, decoded) = loader.to_instructions(BulkValidator::new()).unwrap();
+        assert_eq!(decoded, instructions);
+    }
+
+    #[test]
+    fn test_symbols() {
+        let mut symbols = SymbolTable::new();
+        symbols.add_function(0, String::from("main"));
+
+        let mut writer = Writer::new(vec![Instruction::Halt], 0, ChecksumAlgorithm::Sha2_224);
+        writer.set_symbols(symbols.clone());
+
+        let loader = Loader::load_bytes(&writer.to_bytes()).unwrap();
+
+        assert_eq!(loader.symbols(), Some(&symbols));
+        assert!(loader.data_sections().is_empty());
+        assert_eq!(loader.code_bytes(), &[0x45]);
+    }
+
+    #[test]
+    fn test_empty_program() {
+        let bytes = Writer::new(Vec::new(), 0, ChecksumAlgorithm::Sha2_224).to_bytes();
+
+        assert_eq!(bytes.len(), VXLHeader::HEADER_SIZE);
+        assert_eq!(Loader::load_bytes(&bytes).unwrap().validate(), Ok(()));
+    }
+}
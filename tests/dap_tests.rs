@@ -0,0 +1,214 @@
+#![cfg(feature = "with-binary")]
+
+use serde_json::{json, Value};
+use vxlvm::assembler::assemble;
+use vxlvm::loader::ChecksumAlgorithm;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+const PROGRAM: &str = ".entry main
+message: .string \"hi\\n\"
+
+square: push $r0
+        muli $rou, $r0, $r0
+        pop $r1
+        ret
+
+main:   ldi message, $r0
+        syscall 2
+        ldi 7, $r0
+        call square
+        mov $r0, $rou
+        syscall 0
+        halt
+";
+
+/// Talks to `vxlvm dap` the way an editor would.
+struct Client {
+    child: Child,
+    input: ChildStdin,
+    output: BufReader<ChildStdout>,
+    sequence: u64,
+    /// Events received while waiting for a response.
+    events: Vec<Value>,
+}
+
+impl Client {
+    fn start() -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_vxlvm"))
+            .arg("dap")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        return Self {
+            input: child.stdin.take().unwrap(),
+            output: BufReader::new(child.stdout.take().unwrap()),
+            child,
+            sequence: 0,
+            events: Vec::new(),
+        };
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut length = 0;
+
+        loop {
+            let mut line = String::new();
+            assert!(self.output.read_line(&mut line).unwrap() > 0);
+
+            match line.trim().strip_prefix("Content-Length:") {
+                Some(value) => length = value.trim().parse().unwrap(),
+                None if line.trim().is_empty() => break,
+                None => panic!("Unexpected header {}", line),
+            }
+        }
+
+        let mut body = vec![0; length];
+        self.output.read_exact(&mut body).unwrap();
+
+        return serde_json::from_slice(&body).unwrap();
+    }
+
+    /// Sends a request and returns the body of its successful response.
+    fn request(&mut self, command: &str, arguments: Value) -> Value {
+        self.sequence += 1;
+
+        let message = json!({
+            "seq": self.sequence,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        })
+        .to_string();
+
+        write!(
+            self.input,
+            "Content-Length: {}\r\n\r\n{}",
+            message.len(),
+            message
+        )
+        .unwrap();
+        self.input.flush().unwrap();
+
+        loop {
+            let message = self.receive();
+
+            if message["type"] == "event" {
+                self.events.push(message);
+                continue;
+            }
+
+            assert_eq!(message["request_seq"], self.sequence);
+            assert_eq!(message["success"], true, "{}", message);
+
+            return message["body"].clone();
+        }
+    }
+
+    /// Waits for the next event with the name, skipping any others before it.
+    fn event(&mut self, name: &str) -> Value {
+        loop {
+            let event = if self.events.is_empty() {
+                self.receive()
+            } else {
+                self.events.remove(0)
+            };
+
+            if event["event"] == name {
+                return event["body"].clone();
+            }
+        }
+    }
+}
+
+fn write_program() -> String {
+    let path = std::env::temp_dir().join(format!("vxlvm-dap-{}.xvl", std::process::id()));
+    let bytes = assemble("dap.vsm", PROGRAM)
+        .unwrap()
+        .to_writer(ChecksumAlgorithm::Sha2_224)
+        .to_bytes();
+
+    std::fs::write(&path, bytes).unwrap();
+
+    return path.to_string_lossy().into_owned();
+}
+
+fn variable<'a>(variables: &'a Value, name: &str) -> &'a Value {
+    return variables["variables"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|variable| variable["name"] == name)
+        .map(|variable| &variable["value"])
+        .unwrap();
+}
+
+#[test]
+fn test_dap_session() {
+    let program = write_program();
+    let mut client = Client::start();
+
+    let capabilities = client.request("initialize", json!({ "adapterID": "vxlvm" }));
+    assert_eq!(capabilities["supportsConfigurationDoneRequest"], true);
+
+    client.request("launch", json!({ "program": program }));
+    client.event("initialized");
+
+    // The blank line moves to the first instruction after it, nothing comes after the last.
+    let breakpoints = client.request(
+        "setBreakpoints",
+        json!({
+            "source": { "path": "/home/user/project/dap.vsm" },
+            "breakpoints": [{ "line": 3 }, { "line": 40 }],
+        }),
+    );
+    assert_eq!(breakpoints["breakpoints"][0]["verified"], true);
+    assert_eq!(breakpoints["breakpoints"][0]["line"], 4);
+    assert_eq!(breakpoints["breakpoints"][1]["verified"], false);
+
+    client.request("configurationDone", json!({}));
+    assert_eq!(client.event("output")["output"], "hi\n");
+    assert_eq!(client.event("stopped")["reason"], "breakpoint");
+
+    let trace = client.request("stackTrace", json!({ "threadId": 1 }));
+    let frames = trace["stackFrames"].as_array().unwrap();
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[0]["name"], "square");
+    assert_eq!(frames[0]["line"], 4);
+    assert_eq!(frames[0]["source"]["name"], "dap.vsm");
+    assert_eq!(frames[1]["name"], "main");
+    assert_eq!(frames[1]["line"], 12);
+
+    client.request("next", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "step");
+
+    let scopes = client.request("scopes", json!({ "frameId": 0 }));
+    let reference = scopes["scopes"][0]["variablesReference"].clone();
+    let stack = client.request("variables", json!({ "variablesReference": reference }));
+    assert_eq!(variable(&stack, "[0]"), "7 (0x7)");
+
+    let registers_reference = scopes["scopes"][1]["variablesReference"].clone();
+    let registers = client.request(
+        "variables",
+        json!({ "variablesReference": registers_reference }),
+    );
+    assert_eq!(variable(&registers, "$r0"), "7 (0x7)");
+
+    client.request("stepOut", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "step");
+
+    let trace = client.request("stackTrace", json!({ "threadId": 1 }));
+    assert_eq!(trace["stackFrames"][0]["line"], 13);
+
+    client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.event("exited")["exitCode"], 49);
+    client.event("terminated");
+
+    client.request("disconnect", json!({}));
+    assert!(client.child.wait().unwrap().success());
+
+    std::fs::remove_file(program).unwrap();
+}
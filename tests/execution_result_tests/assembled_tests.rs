@@ -1,8 +1,15 @@
 use super::handler::System;
-use vxl_iset::instruction_arguments::Register;
-use vxlvm::loader::Loader;
+use vxl_iset::instruction::Instruction;
+use vxl_iset::instruction_arguments::{Address, Immediate, Register};
+use vxlvm::assembler::{assemble, assemble_instructions};
+use vxlvm::error::{AssemblyError, AssemblyErrorKind, LinkError, ProgramError};
+use vxlvm::linker::{self, Module};
+use vxlvm::loader::{ChecksumAlgorithm, LoadPolicy, Loader};
+use vxlvm::signature::{sign_file, SigningKey, TrustedKeys};
+use vxlvm::stdlib::StandardLibrary;
 use vxlvm::validator::BulkValidator;
-use vxlvm::vm::VM;
+use vxlvm::vm::{HeapBackend, Memory, TreeHeap, VM};
+use vxlvm::writer::Writer;
 
 #[test]
 fn test_full_file_single_load() {
@@ -37,3 +44,545 @@ fn test_power() {
     vm.run(&mut handler).unwrap();
     assert_eq!(vm.registers().get_value(Register::ROU as u8), 256);
 }
+
+#[test]
+fn test_power_fused() {
+    // See the examples in the vxasm repository, this is the pow.vsm
+    let bytes = hex::decode("6558564c005b00000000000000000000000000000001c58043c2580e23d9465f0b1558d09b9bfc1ee1105a08417c6f6839e7aa030400000000000000600304000000000000007043040000000000000045020100000000000000200586056702000000000000000070020100000000000000f034763a0e000000000000002122801f77f037090000000000000044").unwrap();
+
+    let mut handler = System::new();
+    let (_header, instructions) = Loader::load_bytes(&bytes)
+        .unwrap()
+        .to_instructions(BulkValidator::new())
+        .unwrap();
+
+    let mut vm = VM::new(instructions);
+    vm.fuse_superinstructions();
+
+    vm.run(&mut handler).unwrap();
+    assert_eq!(vm.registers().get_value(Register::ROU as u8), 256);
+}
+
+#[test]
+fn test_write_power() {
+    // See the examples in the vxasm repository, this is the pow.vsm
+    let bytes = hex::decode("6558564c005b00000000000000000000000000000001c58043c2580e23d9465f0b1558d09b9bfc1ee1105a08417c6f6839e7aa030400000000000000600304000000000000007043040000000000000045020100000000000000200586056702000000000000000070020100000000000000f034763a0e000000000000002122801f77f037090000000000000044").unwrap();
+
+    let (_header, instructions) = Loader::load_bytes(&bytes)
+        .unwrap()
+        .to_instructions(BulkValidator::new())
+        .unwrap();
+
+    let written = Writer::new(instructions, 0, ChecksumAlgorithm::Sha3_224).to_bytes();
+
+    assert_eq!(written, bytes);
+}
+
+#[test]
+fn test_data_section() {
+    // ldi 1, $r0
+    // syscall 0x104
+    // igetb 1, $r1, $rou
+    // ldi 2, $r0
+    // syscall 0x104
+    let instructions = vec![
+        Instruction::Ldi(Immediate::from(1u64), Register::R0),
+        Instruction::Syscall(Immediate::from(StandardLibrary::DATA_SECTION)),
+        Instruction::Igetb(Immediate::from(1u64), Register::R1, Register::ROU),
+        Instruction::Ldi(Immediate::from(2u64), Register::R0),
+        Instruction::Syscall(Immediate::from(StandardLibrary::DATA_SECTION)),
+    ];
+
+    let mut writer = Writer::new(instructions.clone(), 0, ChecksumAlgorithm::Sha3_224);
+    writer.add_data_section(b"first".to_vec());
+    writer.add_data_section(b"second".to_vec());
+
+    let loader = Loader::load_bytes(&writer.to_bytes()).unwrap();
+    assert_eq!(loader.validate(), Ok(()));
+
+    let data_sections = loader
+        .data_sections()
+        .into_iter()
+        .map(|section| section.to_vec())
+        .collect();
+    let (_header, decoded) = loader.to_instructions(BulkValidator::new()).unwrap();
+    assert_eq!(decoded, instructions);
+
+    let mut handler = System::new();
+    let mut vm = VM::new(decoded);
+    vm.load_data_sections(data_sections).unwrap();
+
+    for _ in 0..3 {
+        vm.run_next(&mut handler).unwrap();
+    }
+
+    assert_eq!(vm.registers().get_value(Register::R1 as u8), b'e' as u64);
+
+    vm.run(&mut handler).unwrap();
+
+    // There are only two data sections.
+    assert_eq!(vm.registers().get_value(Register::ROU as u8), u64::MAX);
+}
+
+#[test]
+fn test_link_library() {
+    // ldi 5, $r0
+    // call square
+    // halt
+    let mut main = Module::new(
+        vec![
+            Instruction::Ldi(Immediate::from(5u64), Register::R0),
+            Instruction::Call(Address::from(0u64)),
+            Instruction::Halt,
+        ],
+        0,
+    );
+    main.link_table_mut().add_import(String::from("square"), 1);
+
+    // square:
+    // muli $rou, $r0, $r0
+    // ret
+    let mut library = Module::new(
+        vec![
+            Instruction::Muli(Register::ROU, Register::R0, Register::R0),
+            Instruction::Ret,
+        ],
+        0,
+    );
+    library
+        .link_table_mut()
+        .add_export(String::from("square"), 0);
+
+    // Both modules are written and loaded again as they would be from separate files.
+    let modules: Vec<Module> = [main, library]
+        .iter()
+        .map(|module| {
+            let bytes = module.to_writer(ChecksumAlgorithm::Sha2_224).to_bytes();
+
+            return Module::from_loader(Loader::load_bytes(&bytes).unwrap()).unwrap();
+        })
+        .collect();
+
+    let linked = linker::link(&modules).unwrap();
+
+    let mut handler = System::new();
+    let mut vm = VM::new(linked.instructions().to_vec());
+
+    vm.run(&mut handler).unwrap();
+    assert_eq!(vm.registers().get_value(Register::ROU as u8), 25);
+}
+
+#[test]
+fn test_load_module() {
+    // square:
+    // muli $rou, $r1, $r1
+    // ret
+    let mut library = Module::new(
+        vec![
+            Instruction::Muli(Register::ROU, Register::R1, Register::R1),
+            Instruction::Ret,
+        ],
+        0,
+    );
+    library
+        .link_table_mut()
+        .add_export(String::from("square"), 0);
+
+    // ldi 0, $r0
+    // syscall 0x104
+    // mov $r0, $rou
+    // syscall 0x105
+    // mov $r2, $rou
+    // ldi 1, $r0
+    // syscall 0x104
+    // mov $r1, $rou
+    // mov $r0, $r2
+    // syscall 0x106
+    // mov $r0, $rou
+    // ldi 5, $r1
+    // syscall 0x107
+    // halt
+    let instructions = vec![
+        Instruction::Ldi(Immediate::from(0u64), Register::R0),
+        Instruction::Syscall(Immediate::from(StandardLibrary::DATA_SECTION)),
+        Instruction::Mov(Register::R0, Register::ROU),
+        Instruction::Syscall(Immediate::from(StandardLibrary::LOAD_MODULE)),
+        Instruction::Mov(Register::R2, Register::ROU),
+        Instruction::Ldi(Immediate::from(1u64), Register::R0),
+        Instruction::Syscall(Immediate::from(StandardLibrary::DATA_SECTION)),
+        Instruction::Mov(Register::R1, Register::ROU),
+        Instruction::Mov(Register::R0, Register::R2),
+        Instruction::Syscall(Immediate::from(StandardLibrary::RESOLVE_EXPORT)),
+        Instruction::Mov(Register::R0, Register::ROU),
+        Instruction::Ldi(Immediate::from(5u64), Register::R1),
+        Instruction::Syscall(Immediate::from(StandardLibrary::CALL)),
+        Instruction::Halt,
+    ];
+
+    let mut handler = System::new();
+    let mut vm = VM::new(instructions);
+    vm.load_data_sections(vec![
+        library.to_writer(ChecksumAlgorithm::Sha2_224).to_bytes(),
+        b"square".to_vec(),
+    ])
+    .unwrap();
+
+    vm.run(&mut handler).unwrap();
+
+    assert_eq!(vm.registers().get_value(Register::R2 as u8), 1);
+    assert_eq!(vm.registers().get_value(Register::R0 as u8), 14);
+    assert_eq!(vm.registers().get_value(Register::ROU as u8), 25);
+    assert_eq!(vm.resolve_export(1, "square"), Some(14));
+    assert_eq!(vm.describe_instruction(15), "square+1");
+
+    // A second copy would export the same name.
+    assert_eq!(
+        vm.load_module(&library),
+        Err(LinkError::DuplicateSymbol(String::from("square")))
+    );
+
+    // An export must be one of the module's instructions.
+    let mut invalid_export = Module::new(vec![Instruction::Ret], 0);
+    invalid_export
+        .link_table_mut()
+        .add_export(String::from("cube"), u64::MAX);
+    assert_eq!(
+        vm.load_module(&invalid_export),
+        Err(LinkError::InvalidExport(2, u64::MAX))
+    );
+
+    // Anything that isn't a valid file is rejected.
+    let mut vm = VM::new(vec![
+        Instruction::Ldi(Immediate::from(0u64), Register::R0),
+        Instruction::Syscall(Immediate::from(StandardLibrary::DATA_SECTION)),
+        Instruction::Mov(Register::R0, Register::ROU),
+        Instruction::Syscall(Immediate::from(StandardLibrary::LOAD_MODULE)),
+    ]);
+    vm.load_data_sections(vec![b"square".to_vec()]).unwrap();
+
+    vm.run(&mut handler).unwrap();
+    assert_eq!(vm.registers().get_value(Register::ROU as u8), u64::MAX);
+}
+
+#[test]
+fn test_load_module_unchecked() {
+    let library = Module::new(vec![Instruction::Ret], 0);
+    let bytes = library.to_writer(ChecksumAlgorithm::Unchecked).to_bytes();
+
+    let mut vm = VM::new(vec![Instruction::Halt]);
+    let address = vm.memory_mut().allocate_with(bytes).unwrap();
+    vm.registers_mut().set_value(Register::R0 as u8, address);
+
+    // Files without a checksum are refused unless the policy allows them.
+    assert_eq!(
+        StandardLibrary::new().execute_call(StandardLibrary::LOAD_MODULE, &mut vm),
+        Some(u64::MAX)
+    );
+    assert_eq!(
+        StandardLibrary::with_policy(LoadPolicy::new(true, None))
+            .execute_call(StandardLibrary::LOAD_MODULE, &mut vm),
+        Some(1)
+    );
+}
+
+#[test]
+fn test_load_module_trusted() {
+    let library = Module::new(vec![Instruction::Ret], 0);
+    let unsigned = library.to_writer(ChecksumAlgorithm::Sha3_224).to_bytes();
+
+    let key = SigningKey::from_bytes(&[7; 32]);
+    let mut keys = TrustedKeys::new();
+    keys.add_key(&key.verifying_key().to_bytes());
+    let signed = sign_file(&unsigned, &key).unwrap();
+
+    let mut vm = VM::new(vec![Instruction::Halt]);
+    let mut standard_library = StandardLibrary::with_policy(LoadPolicy::new(false, Some(keys)));
+
+    // Only files signed by a trusted key are loaded.
+    for (bytes, expected) in [(unsigned, u64::MAX), (signed, 1)] {
+        let address = vm.memory_mut().allocate_with(bytes).unwrap();
+        vm.registers_mut().set_value(Register::R0 as u8, address);
+
+        assert_eq!(
+            standard_library.execute_call(StandardLibrary::LOAD_MODULE, &mut vm),
+            Some(expected)
+        );
+    }
+}
+
+#[test]
+fn test_load_module_unverified() {
+    // The call has no instruction to go to.
+    let library = Module::new(
+        vec![Instruction::Call(Address::from(5u64)), Instruction::Ret],
+        0,
+    );
+    let bytes = library.to_writer(ChecksumAlgorithm::Sha3_224).to_bytes();
+
+    let mut vm = VM::new(vec![Instruction::Halt]);
+    let address = vm.memory_mut().allocate_with(bytes).unwrap();
+    vm.registers_mut().set_value(Register::R0 as u8, address);
+
+    assert_eq!(
+        StandardLibrary::new().execute_call(StandardLibrary::LOAD_MODULE, &mut vm),
+        Some(u64::MAX)
+    );
+    assert_eq!(vm.instructions().len(), 1);
+
+    // An exported function is checked even though the entry can't reach it.
+    // 0: ret
+    // 1: nop
+    let mut library = Module::new(vec![Instruction::Ret, Instruction::Nop], 0);
+    library.link_table_mut().add_export(String::from("nop"), 1);
+
+    assert_eq!(
+        vm.load_module(&library),
+        Err(LinkError::InvalidProgram(1, ProgramError::FallsOffEnd(2)))
+    );
+    assert_eq!(vm.instructions().len(), 1);
+
+    // The target of an import is only checked once it has been resolved.
+    let mut library = Module::new(vec![Instruction::Ret], 0);
+    library.link_table_mut().add_export(String::from("ret"), 0);
+    assert_eq!(vm.load_module(&library), Ok(1));
+
+    let mut importer = Module::new(
+        vec![Instruction::Call(Address::from(u64::MAX)), Instruction::Ret],
+        0,
+    );
+    importer.link_table_mut().add_import(String::from("ret"), 0);
+    assert_eq!(vm.load_module(&importer), Ok(2));
+}
+
+#[test]
+fn test_load_module_data_sections() {
+    let mut library = Module::new(vec![Instruction::Ret], 0);
+    library.add_data_section(vec![7, 8]);
+
+    // ldi 1, $r0
+    // ldi 0, $r1
+    // syscall 0x108
+    // mov $r2, $rou
+    // ldi 1, $r1
+    // syscall 0x108
+    // halt
+    let instructions = vec![
+        Instruction::Ldi(Immediate::from(1u64), Register::R0),
+        Instruction::Ldi(Immediate::from(0u64), Register::R1),
+        Instruction::Syscall(Immediate::from(StandardLibrary::MODULE_DATA_SECTION)),
+        Instruction::Mov(Register::R2, Register::ROU),
+        Instruction::Ldi(Immediate::from(1u64), Register::R1),
+        Instruction::Syscall(Immediate::from(StandardLibrary::MODULE_DATA_SECTION)),
+        Instruction::Halt,
+    ];
+
+    let mut handler = System::new();
+    let mut vm = VM::new(instructions);
+    vm.load_data_sections(vec![vec![1]]).unwrap();
+
+    assert_eq!(vm.load_module(&library), Ok(1));
+    assert_eq!(vm.module_data_section(0, 0), vm.data_section(0));
+
+    vm.run(&mut handler).unwrap();
+
+    let handle = vm.registers().get_value(Register::R2 as u8);
+    assert_eq!(vm.module_data_section(1, 0), Some(handle));
+    assert_eq!(vm.memory().retrieve(&handle).unwrap(), &vec![7, 8]);
+    assert!(vm.memory().is_read_only(&handle));
+    assert_eq!(vm.registers().get_value(Register::ROU as u8), u64::MAX);
+}
+
+/// A heap that can only hold a fixed number of blocks.
+#[derive(Debug)]
+struct BoundedHeap {
+    heap: TreeHeap,
+    capacity: usize,
+}
+
+impl HeapBackend for BoundedHeap {
+    fn allocate(&mut self, size: u64) -> Option<u64> {
+        return self.allocate_with(vec![0; size as usize]);
+    }
+
+    fn allocate_with(&mut self, bytes: Vec<u8>) -> Option<u64> {
+        if self.heap.addresses().len() >= self.capacity {
+            return None;
+        }
+
+        return self.heap.allocate_with(bytes);
+    }
+
+    fn assign(&mut self, address: u64, data: Vec<u8>) -> bool {
+        return self.heap.assign(address, data);
+    }
+
+    fn assign_empty(&mut self, address: u64, data: Vec<u8>) -> bool {
+        return self.heap.assign_empty(address, data);
+    }
+
+    fn free(&mut self, address: &u64) -> bool {
+        return self.heap.free(address);
+    }
+
+    fn take(&mut self, address: &u64) -> Option<Vec<u8>> {
+        return self.heap.take(address);
+    }
+
+    fn retrieve(&self, address: &u64) -> Option<&Vec<u8>> {
+        return self.heap.retrieve(address);
+    }
+
+    fn retrieve_mutable(&mut self, address: &u64) -> Option<&mut Vec<u8>> {
+        return self.heap.retrieve_mutable(address);
+    }
+
+    fn total_allocated(&self) -> u64 {
+        return self.heap.total_allocated();
+    }
+
+    fn addresses(&self) -> Vec<u64> {
+        return self.heap.addresses();
+    }
+}
+
+#[test]
+fn test_load_module_data_sections_out_of_memory() {
+    let mut library = Module::new(vec![Instruction::Ret], 0);
+    library.add_data_section(vec![1]);
+    library.add_data_section(vec![2]);
+    library.add_data_section(vec![3]);
+
+    let heap = BoundedHeap {
+        heap: TreeHeap::new(),
+        capacity: 2,
+    };
+    let memory = Memory::with_backend(Box::new(heap));
+    let mut vm = VM::new_with_memory(memory, 1024, vec![Instruction::Halt], 0, Default::default());
+
+    assert_eq!(
+        vm.load_module(&library),
+        Err(LinkError::FailedDataSectionAllocation(1))
+    );
+
+    // The sections allocated before the failure are released again.
+    assert_eq!(vm.memory().addresses(), vec![]);
+    assert_eq!(vm.module_data_section(1, 0), None);
+}
+
+#[test]
+fn test_call_current_instruction() {
+    // ldi 1, $r0
+    // syscall 0x107
+    let instructions = vec![
+        Instruction::Ldi(Immediate::from(1u64), Register::R0),
+        Instruction::Syscall(Immediate::from(StandardLibrary::CALL)),
+    ];
+
+    let mut handler = System::new();
+    let mut vm = VM::new(instructions);
+
+    vm.run_next(&mut handler).unwrap();
+    vm.run_next(&mut handler).unwrap();
+
+    // The call moved the IP to where it already was, it must not be advanced past the target.
+    assert_eq!(vm.ip(), 1);
+    assert_eq!(vm.call_stack(), vec![1, 1]);
+}
+
+#[test]
+fn test_assemble_power() {
+    let source = "
+        ; Computes 4 to the power of 4 in $rou.
+                ldi 4, $r0
+                ldi 4, $r1
+                call pow
+                halt
+
+        pow:    ldb 1, $rou
+                mov $r2, $r0
+                mov $r0, $r1
+                ldb 0, $r1
+                ldb 1, $r9
+        .loop:  cmp $r1, $r0
+                jge .done
+                mulu $rou, $rou, $r2
+                addu $r1, $r1, $r9
+                jmp .loop
+        .done:  ret
+    ";
+
+    // The same program as assembled by vxasm.
+    let bytes = hex::decode("6558564c005b00000000000000000000000000000001c58043c2580e23d9465f0b1558d09b9bfc1ee1105a08417c6f6839e7aa030400000000000000600304000000000000007043040000000000000045020100000000000000200586056702000000000000000070020100000000000000f034763a0e000000000000002122801f77f037090000000000000044").unwrap();
+
+    let instructions = assemble_instructions(source).unwrap();
+    let written = Writer::new(instructions.clone(), 0, ChecksumAlgorithm::Sha3_224).to_bytes();
+
+    assert_eq!(written, bytes);
+
+    let mut handler = System::new();
+    let mut vm = VM::new(instructions);
+
+    vm.run(&mut handler).unwrap();
+    assert_eq!(vm.registers().get_value(Register::ROU as u8), 256);
+}
+
+#[test]
+fn test_assemble_modules() {
+    let main = "
+        .import square
+
+        message: .string \"square\"
+
+        start:  ldi message, $r0
+                syscall 0x104
+                igetb 2, $r3, $rou
+                ldi 5, $r0
+                call square
+                halt
+        .entry start
+    ";
+
+    let library = "
+        .export square
+
+        square: muli $rou, $r0, $r0
+                ret
+    ";
+
+    // Both modules are written and loaded again as they would be from separate files.
+    let modules: Vec<Module> = [("main.vsm", main), ("square.vsm", library)]
+        .iter()
+        .map(|(name, source)| {
+            let module = assemble(name, source).unwrap();
+            let bytes = module.to_writer(ChecksumAlgorithm::Sha2_224).to_bytes();
+
+            return Module::from_loader(Loader::load_bytes(&bytes).unwrap()).unwrap();
+        })
+        .collect();
+
+    let linked = linker::link(&modules).unwrap();
+
+    let mut handler = System::new();
+    let mut vm = VM::new(linked.instructions().to_vec());
+    vm.load_data_sections(linked.data_sections().to_vec())
+        .unwrap();
+    vm.set_symbols(linked.symbols().unwrap().clone());
+
+    vm.run(&mut handler).unwrap();
+    assert_eq!(vm.registers().get_value(Register::R3 as u8), b'u' as u64);
+    assert_eq!(vm.registers().get_value(Register::ROU as u8), 25);
+    assert_eq!(vm.describe_instruction(6), "square (square.vsm:4)");
+}
+
+#[test]
+fn test_assembly_error() {
+    let source = "
+        ldi 1, $r0
+        jmp end
+    ";
+
+    assert_eq!(
+        assemble_instructions(source),
+        Err(AssemblyError::new(3, 13, AssemblyErrorKind::UnknownLabel))
+    );
+}
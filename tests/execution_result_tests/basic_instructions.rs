@@ -1,6 +1,7 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
+use vxl_iset::execute_instruction::ExecuteInstruction;
 use vxl_iset::instruction_arguments::Register;
 use vxlvm::error::VMError;
 use vxlvm::validator::{BulkValidator, Validator};
@@ -324,3 +325,65 @@ fn test_halt() {
         VMError::SystemHalted
     );
 }
+
+#[test]
+fn test_no_instruction() {
+    // ldi $r0, 0x663
+    let bytes: Vec<u8> = vec![
+        0b0000_0011, // ldi
+        0b0110_0011, // 663
+        0b0000_0110,
+        0x0,
+        0x0,
+        0x0,
+        0x0,
+        0x0,
+        0x0,
+        0b0110_0000, // r0
+    ];
+
+    let mut handler = System::new();
+    let validator = BulkValidator::with_bytes(bytes);
+    let mut vm = VM::new(validator.process_all_instructions().unwrap());
+
+    vm.run_next(&mut handler).unwrap();
+
+    assert_eq!(vm.registers().get_value(Register::R0 as u8), 0x663);
+
+    assert_eq!(
+        vm.run_next(&mut handler).unwrap_err(),
+        VMError::NoInstruction
+    );
+}
+
+#[test]
+fn test_execute_instruction_directly() {
+    // ldi $r0, 0x663
+    // mov $r1, $r0
+    let bytes: Vec<u8> = vec![
+        0b0000_0011, // ldi
+        0b0110_0011, // 663
+        0b0000_0110,
+        0x0,
+        0x0,
+        0x0,
+        0x0,
+        0x0,
+        0x0,
+        0b0110_0000, // r0
+        0b0000_0101, // mov
+        0b0111_0110, // r1, r0
+    ];
+
+    let mut handler = System::new();
+    let validator = BulkValidator::with_bytes(bytes);
+    let mut vm = VM::new(validator.process_all_instructions().unwrap());
+
+    // Bypasses the pre-decoded instructions used by run_next.
+    for instruction in vm.instructions().to_vec() {
+        assert_eq!(vm.execute_instruction(instruction, &mut handler), Ok(false));
+    }
+
+    assert_eq!(vm.registers().get_value(Register::R0 as u8), 0x663);
+    assert_eq!(vm.registers().get_value(Register::R1 as u8), 0x663);
+}
@@ -801,6 +801,11 @@ mod jump_tests {
 mod call_return {
     use super::*;
 
+    use vxl_iset::instruction::Instruction;
+    use vxl_iset::instruction_arguments::Address;
+    use vxlvm::error::VMError;
+    use vxlvm::symbols::SymbolTable;
+
     #[test]
     fn test_call() {
         let bytes = vec![
@@ -1057,4 +1062,35 @@ mod call_return {
         assert_eq!(vm.registers().get_value(Register::RSP as u8), 0);
         assert_eq!(vm.registers().get_value(Register::RFP as u8), 0);
     }
+
+    #[test]
+    fn test_call_stack() {
+        let instructions = vec![
+            Instruction::Call(Address::from(2u64)),
+            Instruction::Halt,
+            Instruction::Call(Address::from(4u64)),
+            Instruction::Ret,
+            Instruction::Freea(Address::from(5u64)),
+        ];
+
+        let mut symbols = SymbolTable::new();
+        symbols.add_function(0, String::from("main"));
+        symbols.add_function(2, String::from("outer"));
+        symbols.add_function(4, String::from("inner"));
+
+        let mut handler = System::new();
+        let mut vm = VM::new(instructions);
+        assert_eq!(vm.call_stack(), vec![0]);
+        assert_eq!(vm.describe_instruction(0), "instruction 0");
+
+        vm.set_symbols(symbols);
+
+        assert_eq!(
+            vm.run(&mut handler),
+            Err(VMError::FailedFreeNoAddressError(5))
+        );
+        assert_eq!(vm.call_stack(), vec![4, 2, 0]);
+        assert_eq!(vm.describe_instruction(4), "inner");
+        assert_eq!(vm.describe_instruction(3), "outer+1");
+    }
 }
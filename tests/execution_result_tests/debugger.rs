@@ -0,0 +1,152 @@
+use vxl_iset::instruction_arguments::Register;
+use vxlvm::assembler::assemble_instructions;
+use vxlvm::debugger::{Debugger, Resume, StopReason};
+use vxlvm::error::VMError;
+use vxlvm::vm::VM;
+
+use super::handler::System;
+
+const PROGRAM: &str = "
+            ldi 3, $r0
+            push $r0
+            call double
+            call double
+            halt
+
+    double: push $r0
+            addu $r0, $r0, $r0
+            call nothing
+            pop $r1
+            ret
+
+    nothing: ret
+";
+
+fn debugger() -> Debugger {
+    return Debugger::new(VM::new(assemble_instructions(PROGRAM).unwrap()));
+}
+
+#[test]
+fn test_breakpoints() {
+    let mut handler = System::new();
+    let mut debugger = debugger();
+
+    assert!(debugger.add_breakpoint(7));
+    assert!(!debugger.add_breakpoint(11));
+
+    debugger.resume(Resume::Continue);
+    assert_eq!(debugger.run(&mut handler, 100), StopReason::Breakpoint);
+    assert_eq!(debugger.machine().ip(), 7);
+    assert_eq!(
+        debugger.machine().registers().get_value(Register::R0 as u8),
+        6
+    );
+
+    // The breakpoint doesn't stop the instruction it is on, the second call stops again.
+    assert_eq!(debugger.run(&mut handler, 100), StopReason::Breakpoint);
+    assert_eq!(debugger.machine().ip(), 7);
+    assert_eq!(
+        debugger.machine().registers().get_value(Register::R0 as u8),
+        12
+    );
+
+    assert!(debugger.remove_breakpoint(7));
+    assert_eq!(debugger.run(&mut handler, 2), StopReason::Limit);
+    assert_eq!(debugger.run(&mut handler, 100), StopReason::Halted);
+    assert_eq!(debugger.run(&mut handler, 100), StopReason::Halted);
+}
+
+#[test]
+fn test_stepping() {
+    let mut handler = System::new();
+    let mut debugger = debugger();
+
+    debugger.resume(Resume::Step);
+    assert_eq!(debugger.run(&mut handler, 100), StopReason::Step);
+    assert_eq!(debugger.machine().ip(), 1);
+
+    debugger.resume(Resume::StepOver);
+    assert_eq!(debugger.run(&mut handler, 100), StopReason::Step);
+    assert_eq!(debugger.machine().ip(), 2);
+
+    // Over the whole call.
+    debugger.resume(Resume::StepOver);
+    assert_eq!(debugger.run(&mut handler, 100), StopReason::Step);
+    assert_eq!(debugger.machine().ip(), 3);
+    assert_eq!(
+        debugger.machine().registers().get_value(Register::R0 as u8),
+        6
+    );
+
+    // A breakpoint inside the call still stops it.
+    debugger.add_breakpoint(10);
+    debugger.resume(Resume::StepOver);
+    assert_eq!(debugger.run(&mut handler, 100), StopReason::Breakpoint);
+    assert_eq!(debugger.machine().ip(), 10);
+
+    debugger.resume(Resume::StepOut);
+    assert_eq!(debugger.run(&mut handler, 100), StopReason::Step);
+    assert_eq!(debugger.machine().ip(), 8);
+
+    debugger.resume(Resume::StepOut);
+    assert_eq!(debugger.run(&mut handler, 100), StopReason::Step);
+    assert_eq!(debugger.machine().ip(), 4);
+
+    // The outermost function runs to the end.
+    debugger.resume(Resume::StepOut);
+    assert_eq!(debugger.run(&mut handler, 100), StopReason::Halted);
+}
+
+#[test]
+fn test_stepping_fused() {
+    let mut handler = System::new();
+    let mut machine = VM::new(assemble_instructions("cmp $r0, $r1\njeq 3\nnop\nhalt").unwrap());
+    machine.fuse_superinstructions();
+
+    let mut debugger = Debugger::new(machine);
+
+    // The compare and jump are stepped to one at a time.
+    debugger.resume(Resume::Step);
+    assert_eq!(debugger.run(&mut handler, 100), StopReason::Step);
+    assert_eq!(debugger.machine().ip(), 1);
+
+    debugger.resume(Resume::Step);
+    assert_eq!(debugger.run(&mut handler, 100), StopReason::Step);
+    assert_eq!(debugger.machine().ip(), 3);
+}
+
+#[test]
+fn test_frames() {
+    let mut handler = System::new();
+    let mut debugger = debugger();
+
+    debugger.add_breakpoint(10);
+    debugger.resume(Resume::Continue);
+    assert_eq!(debugger.run(&mut handler, 100), StopReason::Breakpoint);
+
+    let frames = debugger.frames();
+    let indices: Vec<usize> = frames.iter().map(|frame| frame.index()).collect();
+
+    assert_eq!(indices, vec![10, 7, 2]);
+    assert_eq!(indices, debugger.machine().call_stack());
+
+    // Both the program and double push a 3, nothing pushes no values of its own.
+    assert_eq!(debugger.frame_values(&frames[2]), vec![3]);
+    assert_eq!(debugger.frame_values(&frames[1]), vec![3]);
+    assert_eq!(debugger.frame_values(&frames[0]), vec![]);
+    assert_eq!(frames[2].base(), 0);
+    assert_eq!(frames[1].base(), 32);
+}
+
+#[test]
+fn test_error() {
+    let mut handler = System::new();
+    let mut debugger = Debugger::new(VM::new(assemble_instructions("nop\npop $r0\nnop").unwrap()));
+
+    debugger.resume(Resume::Continue);
+    assert_eq!(
+        debugger.run(&mut handler, 100),
+        StopReason::Error(VMError::AccessBeyondStackBounds)
+    );
+    assert_eq!(debugger.machine().ip(), 1);
+}
@@ -0,0 +1,121 @@
+use vxl_iset::instruction::Instruction;
+use vxl_iset::instruction_arguments::{Address, Immediate, Register};
+use vxlvm::error::VMError;
+use vxlvm::vm::VM;
+
+use super::handler::System;
+
+#[test]
+fn test_fused_compare_jump() {
+    let instructions = vec![
+        Instruction::Cmp(Register::R0, Register::R1),
+        Instruction::Jeq(Address::from(3u64)),
+        Instruction::Ldi(Immediate::from(1i64), Register::R2),
+        Instruction::Halt,
+    ];
+
+    let mut handler = System::new();
+    let mut vm = VM::new(instructions);
+    vm.fuse_superinstructions();
+
+    // cmp $r0, $r1 and jeq 3
+    vm.run_next(&mut handler).unwrap();
+
+    assert_eq!(vm.ip(), 3);
+    assert_eq!(vm.registers().get_value(Register::RFL as u8), 0b001);
+}
+
+#[test]
+fn test_fused_compare_jump_not_taken() {
+    let instructions = vec![
+        Instruction::Ldi(Immediate::from(1i64), Register::R0),
+        Instruction::Cmp(Register::R0, Register::R1),
+        Instruction::Jeq(Address::from(4u64)),
+        Instruction::Ldi(Immediate::from(1i64), Register::R2),
+        Instruction::Halt,
+    ];
+
+    let mut handler = System::new();
+    let mut vm = VM::new(instructions);
+    vm.fuse_superinstructions();
+
+    // ldi $r0, 1
+    vm.run_next(&mut handler).unwrap();
+    // cmp $r0, $r1 and jeq 4
+    vm.run_next(&mut handler).unwrap();
+
+    assert_eq!(vm.ip(), 3);
+    assert_eq!(vm.registers().get_value(Register::RFL as u8), 0b100);
+}
+
+#[test]
+fn test_jump_into_fused_pair() {
+    let instructions = vec![
+        Instruction::Jmp(Address::from(2u64)),
+        Instruction::Ldi(Immediate::from(5i64), Register::R1),
+        Instruction::Addi(Register::R0, Register::R0, Register::R1),
+        Instruction::Halt,
+    ];
+
+    let mut handler = System::new();
+    let mut vm = VM::new(instructions);
+    vm.fuse_superinstructions();
+
+    vm.run(&mut handler).unwrap();
+
+    // Only the addi half of the pair should have been executed.
+    assert_eq!(vm.registers().get_value(Register::R0 as u8), 0);
+    assert_eq!(vm.registers().get_value(Register::R1 as u8), 0);
+}
+
+#[test]
+fn test_fused_load_operation() {
+    let instructions = vec![
+        Instruction::Ldi(Immediate::from(5i64), Register::R1),
+        Instruction::Addi(Register::R0, Register::R0, Register::R1),
+        Instruction::Ldi(Immediate::from(3i64), Register::R1),
+        Instruction::Muli(Register::R0, Register::R0, Register::R1),
+        Instruction::Halt,
+    ];
+
+    let mut handler = System::new();
+    let mut vm = VM::new(instructions);
+    vm.fuse_superinstructions();
+
+    // ldi $r1, 5 and addi $r0, $r0, $r1
+    vm.run_next(&mut handler).unwrap();
+
+    assert_eq!(vm.ip(), 2);
+    assert_eq!(vm.registers().get_value(Register::R0 as u8), 5);
+
+    // ldi $r1, 3 and muli $r0, $r0, $r1
+    vm.run_next(&mut handler).unwrap();
+
+    assert_eq!(vm.ip(), 4);
+    assert_eq!(vm.registers().get_value(Register::R0 as u8), 15);
+}
+
+#[test]
+fn test_fused_load_operation_error() {
+    let instructions = vec![
+        Instruction::Ldi(Immediate::from(0i64), Register::R1),
+        Instruction::Modi(Register::R0, Register::R0, Register::R1),
+        Instruction::Halt,
+    ];
+
+    let mut handler = System::new();
+    let mut vm = VM::new(instructions);
+    vm.fuse_superinstructions();
+
+    assert_eq!(
+        vm.run_next(&mut handler).unwrap_err(),
+        VMError::AttemptedModuloZeroOperation
+    );
+
+    // The error is reported from the modi, as it would be without fusion.
+    assert_eq!(vm.ip(), 1);
+    assert_eq!(
+        vm.run_next(&mut handler).unwrap_err(),
+        VMError::AttemptedModuloZeroOperation
+    );
+}
@@ -3,6 +3,7 @@ use std::rc::Rc;
 
 use vxl_iset::instruction_arguments::Register;
 use vxl_iset::syscall_handler::SyscallHandler;
+use vxlvm::stdlib::StandardLibrary;
 use vxlvm::vm::VM;
 
 pub struct System {
@@ -22,8 +23,8 @@ impl<'a> System {
 }
 
 impl SyscallHandler<VM> for System {
-    fn execute_target_specific_call(&mut self, _call: u64, _machine: &mut VM) -> Option<u64> {
-        return None;
+    fn execute_target_specific_call(&mut self, call: u64, machine: &mut VM) -> Option<u64> {
+        return StandardLibrary::new().execute_call(call, machine);
     }
 
     fn exit(&mut self, _machine: &mut VM) -> Option<u64> {
@@ -0,0 +1,130 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use vxl_iset::instruction::Instruction;
+use vxl_iset::instruction_arguments::{Address, Immediate, Register};
+use vxlvm::error::ProgramError;
+use vxlvm::vm::VM;
+
+use super::handler::System;
+
+#[test]
+fn test_append_instructions() {
+    let mut handler = System::new();
+    let mut vm = VM::new(vec![Instruction::Ldi(Immediate::from(5i64), Register::R0)]);
+
+    vm.run(&mut handler).unwrap();
+    assert_eq!(vm.ip(), 1);
+
+    vm.append_instructions(vec![
+        Instruction::Push(Register::R0),
+        Instruction::Addi(Register::R1, Register::R0, Register::R0),
+    ])
+    .unwrap();
+    vm.run(&mut handler).unwrap();
+
+    assert_eq!(vm.ip(), 3);
+    assert_eq!(vm.registers().get_value(Register::R1 as u8), 10);
+
+    vm.append_instructions(vec![Instruction::Pop(Register::R2)])
+        .unwrap();
+    vm.run(&mut handler).unwrap();
+
+    // The stack is kept between chunks.
+    assert_eq!(vm.registers().get_value(Register::R2 as u8), 5);
+}
+
+#[test]
+fn test_append_invalid_target() {
+    let mut vm = VM::new(vec![Instruction::Nop]);
+
+    assert_eq!(
+        vm.append_instructions(vec![
+            Instruction::Nop,
+            Instruction::Jmp(Address::from(3u64))
+        ]),
+        Err(ProgramError::TargetOutOfBounds(2, 3))
+    );
+    assert_eq!(vm.instructions(), &[Instruction::Nop]);
+
+    assert_eq!(
+        vm.append_instructions(vec![
+            Instruction::Nop,
+            Instruction::Jmp(Address::from(2u64))
+        ]),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_patch_fused_pair() {
+    let instructions = vec![
+        Instruction::Cmp(Register::R0, Register::R1),
+        Instruction::Jeq(Address::from(3u64)),
+        Instruction::Ldi(Immediate::from(1i64), Register::R2),
+        Instruction::Halt,
+    ];
+
+    let mut handler = System::new();
+    let mut vm = VM::new(instructions);
+    vm.fuse_superinstructions();
+
+    vm.patch_instructions(1, vec![Instruction::Nop]).unwrap();
+
+    // The cmp is no longer fused with the jeq that was replaced.
+    vm.run_next(&mut handler).unwrap();
+    assert_eq!(vm.ip(), 1);
+
+    vm.run(&mut handler).unwrap();
+    assert_eq!(vm.registers().get_value(Register::R2 as u8), 1);
+}
+
+#[test]
+fn test_patch_creates_fused_pair() {
+    let instructions = vec![
+        Instruction::Cmp(Register::R0, Register::R1),
+        Instruction::Nop,
+        Instruction::Ldi(Immediate::from(1i64), Register::R2),
+        Instruction::Halt,
+    ];
+
+    let mut handler = System::new();
+    let mut vm = VM::new(instructions);
+    vm.fuse_superinstructions();
+
+    vm.patch_instructions(1, vec![Instruction::Jeq(Address::from(3u64))])
+        .unwrap();
+
+    vm.run_next(&mut handler).unwrap();
+    assert_eq!(vm.ip(), 3);
+}
+
+#[test]
+fn test_patch_bounds() {
+    let mut vm = VM::new(vec![Instruction::Nop, Instruction::Halt]);
+
+    assert_eq!(
+        vm.patch_instructions(1, vec![Instruction::Nop, Instruction::Nop]),
+        Err(ProgramError::PatchOutOfBounds(1))
+    );
+    assert_eq!(
+        vm.patch_instructions(0, vec![Instruction::Call(Address::from(2u64))]),
+        Err(ProgramError::TargetOutOfBounds(0, 2))
+    );
+    assert_eq!(vm.instructions(), &[Instruction::Nop, Instruction::Halt]);
+}
+
+#[test]
+fn test_code_change_hook() {
+    let changes = Rc::new(RefCell::new(Vec::new()));
+    let recorded = changes.clone();
+
+    let mut vm = VM::new(vec![Instruction::Nop, Instruction::Nop]);
+    vm.set_code_change_hook(move |range| recorded.borrow_mut().push(range));
+
+    vm.patch_instructions(1, vec![Instruction::Halt]).unwrap();
+    vm.append_instructions(vec![Instruction::Nop, Instruction::Nop])
+        .unwrap();
+
+    assert_eq!(*changes.borrow(), vec![1..2, 2..4]);
+}
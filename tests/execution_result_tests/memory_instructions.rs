@@ -1,6 +1,8 @@
-use vxl_iset::instruction_arguments::Register;
+use vxl_iset::instruction::Instruction;
+use vxl_iset::instruction_arguments::{Address, Immediate, Register};
+use vxlvm::error::VMError;
 use vxlvm::validator::{BulkValidator, Validator};
-use vxlvm::vm::VM;
+use vxlvm::vm::{Memory, VM};
 
 use super::handler::System;
 
@@ -2371,3 +2373,252 @@ fn test_copyi_2() {
         &vec![0x0, 0x0, 0x0, 0x78, 0x9a, 0x0, 0x0, 0x0, 0x0, 0x0,]
     );
 }
+
+#[test]
+fn test_slab_backend() {
+    let instructions = vec![
+        Instruction::Malloci(Immediate::from(4u64), Register::R0),
+        Instruction::Malloci(Immediate::from(4u64), Register::R1),
+        Instruction::Ldi(Immediate::from(2u64), Register::R2),
+        Instruction::Ldi(Immediate::from(0xabu64), Register::R3),
+        Instruction::Setb(Register::R1, Register::R2, Register::R3),
+        Instruction::Getb(Register::R4, Register::R1, Register::R2),
+        Instruction::Free(Register::R0),
+        Instruction::Malloci(Immediate::from(8u64), Register::R5),
+        Instruction::Halt,
+    ];
+
+    let mut handler = System::new();
+    let mut vm = VM::new_with_memory(Memory::slab(), 1024, instructions, 0, Default::default());
+
+    vm.run(&mut handler).unwrap();
+
+    assert_eq!(vm.registers().get_value(Register::R0 as u8), 0);
+    assert_eq!(vm.registers().get_value(Register::R1 as u8), 1);
+    assert_eq!(vm.registers().get_value(Register::R4 as u8), 0xab);
+    // The freed address is reused.
+    assert_eq!(vm.registers().get_value(Register::R5 as u8), 0);
+
+    assert_eq!(vm.memory().retrieve(&0).unwrap(), &vec![0u8; 8]);
+    assert_eq!(
+        vm.memory().retrieve(&1).unwrap(),
+        &vec![0x0, 0x0, 0xab, 0x0]
+    );
+    assert_eq!(vm.memory().total_allocated(), 12);
+}
+
+#[test]
+fn test_copy_overlapping() {
+    // copy $r0, $r1, $r0, $r2, $r3
+    // copy $r0, $r2, $r0, $r1, $r3
+    let instructions = vec![
+        Instruction::Copy(
+            Register::R0,
+            Register::R1,
+            Register::R0,
+            Register::R2,
+            Register::R3,
+        ),
+        Instruction::Copy(
+            Register::R0,
+            Register::R2,
+            Register::R0,
+            Register::R1,
+            Register::R3,
+        ),
+    ];
+
+    let mut handler = System::new();
+    let mut vm = VM::new(instructions);
+
+    vm.memory_mut()
+        .allocate_with(vec![1, 2, 3, 4, 5, 6, 7, 8])
+        .unwrap();
+    vm.registers_mut().set_value(Register::R0 as u8, 0);
+    vm.registers_mut().set_value(Register::R1 as u8, 2);
+    vm.registers_mut().set_value(Register::R2 as u8, 0);
+    vm.registers_mut().set_value(Register::R3 as u8, 5);
+
+    // Copying forwards over the source.
+    vm.run_next(&mut handler).unwrap();
+
+    assert_eq!(
+        vm.memory().retrieve(&0).unwrap(),
+        &vec![1, 2, 1, 2, 3, 4, 5, 8]
+    );
+
+    // Copying backwards over the source.
+    vm.run_next(&mut handler).unwrap();
+
+    assert_eq!(
+        vm.memory().retrieve(&0).unwrap(),
+        &vec![1, 2, 3, 4, 5, 4, 5, 8]
+    );
+}
+
+#[test]
+fn test_copyi_overlapping() {
+    // copyi 1, 0, 3, $r0, $r0
+    let instructions = vec![Instruction::Copyi(
+        Immediate::from(1u64),
+        Immediate::from(0u64),
+        Immediate::from(3u64),
+        Register::R0,
+        Register::R0,
+    )];
+
+    let mut handler = System::new();
+    let mut vm = VM::new(instructions);
+
+    vm.memory_mut().allocate_with(vec![1, 2, 3, 4]).unwrap();
+
+    vm.run_next(&mut handler).unwrap();
+
+    assert_eq!(vm.memory().retrieve(&0).unwrap(), &vec![1, 1, 2, 3]);
+}
+
+#[test]
+fn test_copy_out_of_bounds() {
+    // copy $r1, $r2, $r0, $r3, $r4
+    let instructions = vec![Instruction::Copy(
+        Register::R1,
+        Register::R2,
+        Register::R0,
+        Register::R3,
+        Register::R4,
+    )];
+
+    let mut vm = VM::new(instructions.clone());
+    vm.memory_mut().allocate_with(vec![1, 2, 3, 4]).unwrap();
+    vm.memory_mut().allocate_with(vec![0; 6]).unwrap();
+    vm.registers_mut().set_value(Register::R1 as u8, 1);
+    vm.registers_mut().set_value(Register::R2 as u8, 3);
+    vm.registers_mut().set_value(Register::R4 as u8, 4);
+
+    // The destination range ends beyond its block, nothing is written.
+    assert_eq!(
+        vm.run_next(&mut System::new()),
+        Err(VMError::IndexBeyondBoundsError(6, 6))
+    );
+    assert_eq!(vm.memory().retrieve(&0).unwrap(), &vec![1, 2, 3, 4]);
+    assert_eq!(vm.memory().retrieve(&1).unwrap(), &vec![0; 6]);
+
+    let mut vm = VM::new(instructions);
+    vm.memory_mut().allocate_with(vec![1, 2, 3, 4]).unwrap();
+    vm.memory_mut().allocate_with(vec![0; 6]).unwrap();
+    vm.registers_mut().set_value(Register::R1 as u8, 1);
+    vm.registers_mut().set_value(Register::R3 as u8, 2);
+    vm.registers_mut().set_value(Register::R4 as u8, 3);
+
+    // The source range ends beyond its block, nothing is written.
+    assert_eq!(
+        vm.run_next(&mut System::new()),
+        Err(VMError::IndexBeyondBoundsError(4, 4))
+    );
+    assert_eq!(vm.memory().retrieve(&0).unwrap(), &vec![1, 2, 3, 4]);
+    assert_eq!(vm.memory().retrieve(&1).unwrap(), &vec![0; 6]);
+}
+
+#[test]
+fn test_copy_error_order() {
+    // copy $r1, $r2, $r0, $r3, $r4
+    let instructions = vec![Instruction::Copy(
+        Register::R1,
+        Register::R2,
+        Register::R0,
+        Register::R3,
+        Register::R4,
+    )];
+
+    let run = |dest_offset: u64, src_offset: u64| {
+        let mut vm = VM::new(instructions.clone());
+        vm.memory_mut().allocate_with(vec![1, 2, 3, 4]).unwrap();
+        vm.memory_mut().allocate_with(vec![0; 6]).unwrap();
+        vm.registers_mut().set_value(Register::R1 as u8, 1);
+        vm.registers_mut()
+            .set_value(Register::R2 as u8, dest_offset);
+        vm.registers_mut().set_value(Register::R3 as u8, src_offset);
+        vm.registers_mut().set_value(Register::R4 as u8, 3);
+
+        return vm.run_next(&mut System::new());
+    };
+
+    // Both ranges end beyond their blocks, the destination at an earlier byte.
+    assert_eq!(run(5, 2), Err(VMError::IndexBeyondBoundsError(6, 6)));
+    // Both ranges end at the same byte, which is read before it is written.
+    assert_eq!(run(4, 2), Err(VMError::IndexBeyondBoundsError(4, 4)));
+    // The source ends at an earlier byte.
+    assert_eq!(run(4, 3), Err(VMError::IndexBeyondBoundsError(4, 4)));
+}
+
+#[test]
+fn test_copy_missing_block() {
+    // copyi 0, 0, 2, $r1, $r0
+    let instructions = vec![Instruction::Copyi(
+        Immediate::from(0u64),
+        Immediate::from(0u64),
+        Immediate::from(2u64),
+        Register::R1,
+        Register::R0,
+    )];
+
+    let mut vm = VM::new(instructions);
+    vm.memory_mut().allocate_with(vec![1, 2, 3, 4]).unwrap();
+    vm.registers_mut().set_value(Register::R1 as u8, 1);
+
+    assert_eq!(
+        vm.run_next(&mut System::new()),
+        Err(VMError::FailedSetNoAddressError(1))
+    );
+    // The source block is still in place after a failed copy.
+    assert_eq!(vm.memory().retrieve(&0).unwrap(), &vec![1, 2, 3, 4]);
+    assert_eq!(vm.memory_mut().allocate(1), Some(1));
+}
+
+#[test]
+fn test_read_only_block() {
+    // Each of these modifies, frees or moves the block in $r0.
+    let instructions = [
+        Instruction::Isetb(Immediate::from(0u64), Register::R0, Register::R1),
+        Instruction::Freea(Address::from(0u64)),
+        Instruction::Swpa(Address::from(1u64), Address::from(0u64)),
+        Instruction::Copyi(
+            Immediate::from(0u64),
+            Immediate::from(0u64),
+            Immediate::from(3u64),
+            Register::R0,
+            Register::R2,
+        ),
+    ];
+
+    for instruction in instructions {
+        let mut vm = VM::new(vec![instruction]);
+        vm.load_data_sections(vec![vec![1, 2, 3]]).unwrap();
+        vm.memory_mut().allocate(3).unwrap();
+        vm.registers_mut().set_value(Register::R2 as u8, 1);
+
+        assert_eq!(
+            vm.run_next(&mut System::new()),
+            Err(VMError::WriteToReadOnlyMemoryError(0))
+        );
+        assert_eq!(vm.memory().retrieve(&0).unwrap(), &vec![1, 2, 3]);
+    }
+
+    // copyi 0, 0, 3, $r2, $r0
+    let mut vm = VM::new(vec![Instruction::Copyi(
+        Immediate::from(0u64),
+        Immediate::from(0u64),
+        Immediate::from(3u64),
+        Register::R2,
+        Register::R0,
+    )]);
+    vm.load_data_sections(vec![vec![1, 2, 3]]).unwrap();
+    vm.memory_mut().allocate(3).unwrap();
+    vm.registers_mut().set_value(Register::R2 as u8, 1);
+
+    // Read only blocks can still be copied from.
+    vm.run_next(&mut System::new()).unwrap();
+    assert_eq!(vm.memory().retrieve(&1).unwrap(), &vec![1, 2, 3]);
+    assert!(vm.memory().is_read_only(&0));
+    assert!(!vm.memory().is_read_only(&1));
+}
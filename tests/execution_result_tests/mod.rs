@@ -2,5 +2,9 @@ mod arithmetic_instructions;
 mod assembled_tests;
 mod basic_instructions;
 mod control_flow_instructions;
+mod debugger;
+mod fused_instructions;
 mod handler;
+mod live_code;
 mod memory_instructions;
+mod stdlib_calls;
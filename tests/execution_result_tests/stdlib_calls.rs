@@ -0,0 +1,140 @@
+use vxl_iset::instruction::Instruction;
+use vxl_iset::instruction_arguments::{Immediate, Register};
+use vxlvm::stdlib::StandardLibrary;
+use vxlvm::vm::VM;
+
+use super::handler::System;
+
+/// Executes a single standard library call on a VM holding the given blocks, with the arguments
+/// placed in r0 onwards.
+fn execute_call(call: u64, blocks: Vec<Vec<u8>>, arguments: &[u64]) -> VM {
+    let mut handler = System::new();
+    let mut vm = VM::new(vec![Instruction::Syscall(Immediate::from(call))]);
+
+    for block in blocks {
+        vm.memory_mut().allocate_with(block).unwrap();
+    }
+
+    let registers = [
+        Register::R0,
+        Register::R1,
+        Register::R2,
+        Register::R3,
+        Register::R4,
+    ];
+
+    for (register, value) in registers.iter().zip(arguments) {
+        vm.registers_mut().set_value(*register as u8, *value);
+    }
+
+    vm.run_next(&mut handler).unwrap();
+
+    return vm;
+}
+
+#[test]
+fn test_memory_fill() {
+    let vm = execute_call(
+        StandardLibrary::MEMORY_FILL,
+        vec![vec![1, 2, 3, 4, 5, 6]],
+        &[0, 1, 4, 0x1ab],
+    );
+
+    assert_eq!(vm.registers().get_value(Register::ROU as u8), 0);
+    assert_eq!(
+        vm.memory().retrieve(&0).unwrap(),
+        &vec![1, 0xab, 0xab, 0xab, 0xab, 6]
+    );
+}
+
+#[test]
+fn test_memory_fill_out_of_bounds() {
+    let vm = execute_call(
+        StandardLibrary::MEMORY_FILL,
+        vec![vec![1, 2, 3, 4]],
+        &[0, 2, 3, 0xff],
+    );
+
+    assert_eq!(vm.registers().get_value(Register::ROU as u8), 2);
+    assert_eq!(vm.memory().retrieve(&0).unwrap(), &vec![1, 2, 3, 4]);
+
+    let vm = execute_call(StandardLibrary::MEMORY_FILL, vec![], &[0, 0, 1, 0xff]);
+
+    assert_eq!(vm.registers().get_value(Register::ROU as u8), 1);
+}
+
+#[test]
+fn test_memory_compare() {
+    let blocks = vec![vec![1, 2, 3, 4], vec![9, 2, 3, 5]];
+
+    // Equal ranges.
+    let vm = execute_call(
+        StandardLibrary::MEMORY_COMPARE,
+        blocks.clone(),
+        &[0, 1, 1, 1, 2],
+    );
+
+    assert_eq!(vm.registers().get_value(Register::ROU as u8), 0);
+    assert_eq!(vm.registers().get_value(Register::RFL as u8), 0b001);
+
+    // The first differing byte decides the result.
+    let vm = execute_call(
+        StandardLibrary::MEMORY_COMPARE,
+        blocks.clone(),
+        &[0, 0, 1, 0, 4],
+    );
+
+    assert_eq!(vm.registers().get_value(Register::ROU as u8), 0);
+    assert_eq!(vm.registers().get_value(Register::RFL as u8), 0b010);
+
+    let vm = execute_call(
+        StandardLibrary::MEMORY_COMPARE,
+        blocks.clone(),
+        &[1, 1, 0, 1, 3],
+    );
+
+    assert_eq!(vm.registers().get_value(Register::ROU as u8), 0);
+    assert_eq!(vm.registers().get_value(Register::RFL as u8), 0b100);
+
+    // The flags are untouched when the comparison fails.
+    let vm = execute_call(StandardLibrary::MEMORY_COMPARE, blocks, &[0, 1, 1, 1, 4]);
+
+    assert_eq!(vm.registers().get_value(Register::ROU as u8), 2);
+    assert_eq!(vm.registers().get_value(Register::RFL as u8), 0);
+}
+
+#[test]
+fn test_memory_find() {
+    let blocks = vec![vec![7, 3, 7, 3, 7]];
+
+    let vm = execute_call(StandardLibrary::MEMORY_FIND, blocks.clone(), &[0, 2, 3, 3]);
+    assert_eq!(vm.registers().get_value(Register::ROU as u8), 3);
+
+    let vm = execute_call(StandardLibrary::MEMORY_FIND, blocks.clone(), &[0, 0, 1, 3]);
+    assert_eq!(vm.registers().get_value(Register::ROU as u8), u64::MAX);
+
+    let vm = execute_call(StandardLibrary::MEMORY_FIND, blocks, &[0, 4, 2, 7]);
+    assert_eq!(vm.registers().get_value(Register::ROU as u8), u64::MAX);
+}
+
+#[test]
+fn test_memory_resize() {
+    let vm = execute_call(
+        StandardLibrary::MEMORY_RESIZE,
+        vec![vec![1, 2], vec![3, 4, 5]],
+        &[1, 5],
+    );
+
+    assert_eq!(vm.registers().get_value(Register::ROU as u8), 0);
+    assert_eq!(vm.memory().retrieve(&0).unwrap(), &vec![1, 2]);
+    assert_eq!(vm.memory().retrieve(&1).unwrap(), &vec![3, 4, 5, 0, 0]);
+
+    let vm = execute_call(StandardLibrary::MEMORY_RESIZE, vec![vec![3, 4, 5]], &[0, 1]);
+
+    assert_eq!(vm.registers().get_value(Register::ROU as u8), 0);
+    assert_eq!(vm.memory().retrieve(&0).unwrap(), &vec![3]);
+
+    let vm = execute_call(StandardLibrary::MEMORY_RESIZE, vec![], &[0, 1]);
+
+    assert_eq!(vm.registers().get_value(Register::ROU as u8), 1);
+}
@@ -0,0 +1,204 @@
+#![cfg(feature = "with-binary")]
+
+use vxlvm::assembler::assemble;
+use vxlvm::loader::ChecksumAlgorithm;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::{Child, Command, Stdio};
+
+const PROGRAM: &str = ".entry main
+message: .string \"hi\"
+buffer: .zero 0x3000
+
+square: push $r0
+        muli $rou, $r0, $r0
+        pop $r1
+        ret
+
+main:   ldi message, $r2
+        ldi 7, $r0
+        call square
+        mov $r0, $rou
+        syscall 0
+        halt
+";
+
+const LOOP: &str = "
+start:  jmp start
+        halt
+";
+
+/// Talks to `vxlvm gdbserver` the way gdb would.
+struct Client {
+    child: Child,
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    acknowledge: bool,
+}
+
+impl Client {
+    fn start(name: &str, source: &str) -> (Self, String) {
+        let path = std::env::temp_dir().join(format!("vxlvm-{}-{}.xvl", name, std::process::id()));
+        let bytes = assemble("gdb.vsm", source)
+            .unwrap()
+            .to_writer(ChecksumAlgorithm::Sha2_224)
+            .to_bytes();
+        std::fs::write(&path, bytes).unwrap();
+
+        let path = path.to_string_lossy().into_owned();
+        let mut child = Command::new(env!("CARGO_BIN_EXE_vxlvm"))
+            .args(["gdbserver", "--port", "0", &path])
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        // The server says which port it picked once it is listening.
+        let mut line = String::new();
+        BufReader::new(child.stderr.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        let address = line.trim().strip_prefix("Listening on ").unwrap();
+
+        let stream = TcpStream::connect(address).unwrap();
+
+        let client = Self {
+            child,
+            reader: BufReader::new(stream.try_clone().unwrap()),
+            writer: stream,
+            acknowledge: true,
+        };
+
+        return (client, path);
+    }
+
+    fn send(&mut self, data: &str) {
+        let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+
+        write!(self.writer, "${}#{:02x}", data, checksum).unwrap();
+        self.writer.flush().unwrap();
+
+        if self.acknowledge {
+            assert_eq!(self.read_byte(), b'+');
+        }
+    }
+
+    fn receive(&mut self) -> String {
+        assert_eq!(self.read_byte(), b'$');
+
+        let mut data = Vec::new();
+        self.reader.read_until(b'#', &mut data).unwrap();
+        data.pop();
+
+        let mut checksum = [0; 2];
+        self.reader.read_exact(&mut checksum).unwrap();
+        assert_eq!(
+            u8::from_str_radix(std::str::from_utf8(&checksum).unwrap(), 16).unwrap(),
+            data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+        );
+
+        if self.acknowledge {
+            self.writer.write_all(b"+").unwrap();
+        }
+
+        return String::from_utf8(data).unwrap();
+    }
+
+    fn request(&mut self, data: &str) -> String {
+        self.send(data);
+
+        return self.receive();
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let mut byte = [0];
+        self.reader.read_exact(&mut byte).unwrap();
+
+        return byte[0];
+    }
+
+    fn register(&mut self, number: u8) -> u64 {
+        let reply = self.request(&format!("p{:x}", number));
+        let bytes = hex::decode(reply).unwrap();
+
+        return u64::from_le_bytes(bytes.try_into().unwrap());
+    }
+}
+
+#[test]
+fn test_gdbserver_session() {
+    let (mut client, path) = Client::start("gdb", PROGRAM);
+
+    assert!(client
+        .request("qSupported:swbreak+")
+        .contains("qXfer:features:read+"));
+    assert_eq!(client.request("QStartNoAckMode"), "OK");
+    client.acknowledge = false;
+
+    assert_eq!(client.request("?"), "T05thread:1;");
+    assert_eq!(client.register(0), 4);
+
+    let description = client.request("qXfer:features:read:target.xml:0,fff");
+    assert!(description.starts_with('l'));
+    assert!(description.contains("<reg name=\"r0\" bitsize=\"64\" type=\"int64\" regnum=\"6\"/>"));
+
+    // After the push in square.
+    assert_eq!(client.request("Z0,1,1"), "OK");
+    assert_eq!(client.request("Z0,40,1"), "E01");
+    assert_eq!(client.request("c"), "T05thread:1;swbreak:;");
+    assert_eq!(client.register(0), 1);
+
+    let registers = client.request("g");
+    assert_eq!(registers.len(), 16 * 16);
+    assert_eq!(&registers[6 * 16..7 * 16], "0700000000000000");
+
+    // The stack is addressed by offset, so rsp points at the value square pushed.
+    let rsp = client.register(3);
+    assert_eq!(client.request(&format!("m{:x},8", rsp)), "0700000000000000");
+
+    // Heap blocks have the top bit set and the handle above the offset.
+    let message = (1 << 63) | (client.register(8) << 32);
+    assert_eq!(client.request(&format!("m{:x},10", message)), "6869");
+    assert_eq!(client.request(&format!("m{:x},1", message + 1)), "69");
+    assert_eq!(client.request(&format!("m{:x},1", message + 2)), "E01");
+
+    // Long reads are cut short rather than overflowing or exceeding the packet size.
+    assert_eq!(
+        client.request(&format!("m{:x},ffffffffffffffff", message + 1)),
+        "69"
+    );
+    assert_eq!(client.request("m0,ffffffffffffffff").len(), 32 * 2);
+    let buffer: u64 = (1 << 63) | (1 << 32);
+    assert_eq!(client.request(&format!("m{:x},3000", buffer)).len(), 0x4000);
+
+    // Changing the argument changes the result.
+    assert_eq!(client.request("P6=0300000000000000"), "OK");
+    assert_eq!(client.request("s"), "T05thread:1;");
+    assert_eq!(client.register(0), 2);
+    assert_eq!(client.register(2), 9);
+
+    assert_eq!(client.request("z0,1,1"), "OK");
+    assert_eq!(client.request("vCont;c"), "W09");
+    assert_eq!(client.request("?"), "W09");
+
+    client.send("k");
+    assert!(client.child.wait().unwrap().success());
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_gdbserver_interrupt() {
+    let (mut client, path) = Client::start("gdb-loop", LOOP);
+
+    client.send("c");
+    client.writer.write_all(&[0x03]).unwrap();
+    assert_eq!(client.receive(), "T02thread:1;");
+    assert_eq!(client.register(0), 0);
+
+    client.send("D");
+    assert_eq!(client.receive(), "OK");
+    assert!(client.child.wait().unwrap().success());
+
+    std::fs::remove_file(path).unwrap();
+}